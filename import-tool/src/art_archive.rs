@@ -403,6 +403,7 @@ async fn import_given_art_piece(
             .filter(|tag| !["sfw", "nsfw"].contains(&tag.as_str()))
             .collect(),
         creation_date: frontmatter.date,
+        sources: frontmatter.sources,
     };
 
     reqwest::Client::new()
@@ -432,6 +433,10 @@ struct ArtFrontmatter {
     tags: Vec<String>,
 
     date: chrono::NaiveDate,
+
+    /// Where the art was originally posted. Older entries don't have it.
+    #[serde(rename = "source", default, deserialize_with = "utils::string_or_vec")]
+    sources: Vec<String>,
 }
 
 fn default_format() -> Format {
@@ -457,4 +462,5 @@ struct PostArt {
     pub tags: Vec<String>,
     pub art_keys: Vec<String>,
     pub creation_date: chrono::NaiveDate,
+    pub sources: Vec<String>,
 }
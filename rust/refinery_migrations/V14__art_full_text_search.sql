@@ -0,0 +1,29 @@
+-- Full text search over art. Title weighs the most, then tags and creators, then the description.
+-- This is kept up to date by a trigger rather than a generated column, since array_to_string isn't immutable.
+ALTER TABLE art
+ADD search_vector tsvector NOT NULL DEFAULT ''::tsvector;
+
+CREATE FUNCTION update_art_search_vector()
+RETURNS TRIGGER AS $$
+BEGIN
+    NEW.search_vector =
+        setweight(to_tsvector('english', NEW.title), 'A') ||
+        -- Tags are usually dash-separated ("nikki-beach"), so split them into words.
+        setweight(to_tsvector('english', translate(array_to_string(NEW.tags, ' '), '-_', '  ')), 'B') ||
+        setweight(to_tsvector('english', array_to_string(NEW.creators, ' ')), 'B') ||
+        setweight(to_tsvector('english', COALESCE(NEW.description, '')), 'C');
+    RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER art_search_vector_update
+BEFORE INSERT OR UPDATE OF title, tags, creators, description ON art
+FOR EACH ROW
+EXECUTE FUNCTION update_art_search_vector();
+
+-- Fill in the vector for everything that already exists, without bumping the last modified date.
+ALTER TABLE art DISABLE TRIGGER art_last_modified;
+UPDATE art SET title = title;
+ALTER TABLE art ENABLE TRIGGER art_last_modified;
+
+CREATE INDEX art_search_vector_index ON art USING GIN (search_vector);
@@ -0,0 +1,63 @@
+CREATE TYPE tag_category AS ENUM (
+    'general', -- Default, anything that doesn't fit elsewhere.
+    'character', -- A character appearing in the piece.
+    'artist', -- Tags about who made it, for stuff that isn't covered by the creators column.
+    'meta', -- Info about the post itself, like "comic" or "wip".
+    'content_warning' -- Stuff people might not want to see.
+);
+
+CREATE TABLE tag (
+    id int PRIMARY KEY GENERATED ALWAYS AS IDENTITY, -- Created by db, auto-increments.
+    last_modified_date timestamp with time zone NOT NULL DEFAULT CURRENT_TIMESTAMP, -- Updates whenever this row is modified, see `update_last_modified_date()`.
+
+    name text NOT NULL UNIQUE CHECK (TRIM(name) != ''), -- The canonical name of the tag, the one that's actually written in art.tags.
+    category tag_category NOT NULL DEFAULT 'general',
+    description text CHECK (TRIM(description) != '')
+);
+
+CREATE TRIGGER tag_last_modified
+BEFORE UPDATE ON tag
+FOR EACH ROW
+EXECUTE FUNCTION update_last_modified_date();
+
+-- Other names a tag goes by. Whenever one of these is given, it's replaced with the tag it points to.
+CREATE TABLE tag_alias (
+    alias text PRIMARY KEY CHECK (TRIM(alias) != ''),
+    tag_id int NOT NULL
+        REFERENCES tag(id)
+        ON DELETE CASCADE
+);
+
+-- If an art piece has `tag_id`, it automatically gets `implied_tag_id` too. (e.g. "nikki-swimsuit" implies "nikki")
+CREATE TABLE tag_implication (
+    tag_id int NOT NULL
+        REFERENCES tag(id)
+        ON DELETE CASCADE,
+    implied_tag_id int NOT NULL
+        REFERENCES tag(id)
+        ON DELETE CASCADE,
+
+    PRIMARY KEY (tag_id, implied_tag_id),
+    CHECK (tag_id != implied_tag_id)
+);
+
+-- An alias shadowing a real tag would make that tag impossible to use, so don't let that happen.
+CREATE FUNCTION check_tag_alias_isnt_tag()
+RETURNS TRIGGER AS $$
+BEGIN
+    IF EXISTS (SELECT 1 FROM tag WHERE name = NEW.alias) THEN
+        RAISE EXCEPTION 'Alias "%" is already the name of a tag', NEW.alias;
+    END IF;
+    RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER tag_alias_isnt_tag
+BEFORE INSERT OR UPDATE ON tag_alias
+FOR EACH ROW
+EXECUTE FUNCTION check_tag_alias_isnt_tag();
+
+-- Register every tag already in use.
+INSERT INTO tag (name)
+SELECT DISTINCT unnest(tags) FROM art
+ON CONFLICT DO NOTHING;
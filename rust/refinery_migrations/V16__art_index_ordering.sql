@@ -0,0 +1,2 @@
+-- Matches SQL_ORDER_BY_STATEMENT, so paging through the art index by cursor is an index scan rather than a sort.
+CREATE INDEX art_creation_date_page_slug_index ON art (creation_date DESC, page_slug);
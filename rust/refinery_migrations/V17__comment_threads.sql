@@ -0,0 +1,21 @@
+ALTER TABLE art_comment
+ADD reply_to int -- The comment this is replying to. NULL means it's directly under the post.
+    REFERENCES art_comment(id)
+    ON DELETE CASCADE
+    DEFAULT NULL;
+
+ALTER TABLE art_comment
+ADD edited_time timestamp with time zone DEFAULT NULL; -- NULL means it was never edited.
+
+-- Comments are soft-deleted, so replies to them still make sense. A deleted comment loses its contents.
+ALTER TABLE art_comment
+ADD deleted_time timestamp with time zone DEFAULT NULL;
+
+ALTER TABLE art_comment
+ALTER COLUMN contents DROP NOT NULL;
+
+ALTER TABLE art_comment
+ADD CONSTRAINT art_comment_has_contents_unless_deleted
+CHECK (contents IS NOT NULL OR deleted_time IS NOT NULL);
+
+CREATE INDEX art_comment_under_post_index ON art_comment (under_post);
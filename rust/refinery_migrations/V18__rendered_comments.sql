@@ -0,0 +1,4 @@
+-- The HTML of the comment, rendered from `contents` whenever it's posted or edited.
+-- NULL for deleted comments, and for comments posted before this column existed, which get rendered on the fly.
+ALTER TABLE art_comment
+ADD rendered_contents text DEFAULT NULL;
@@ -0,0 +1,7 @@
+-- When the user signed up. Used to stop brand new accounts from spamming comments.
+-- Everyone who signed up before this column existed is treated as having been here forever, since we don't know better.
+ALTER TABLE site_user
+ADD creation_time timestamp with time zone NOT NULL DEFAULT 'epoch';
+
+ALTER TABLE site_user
+ALTER COLUMN creation_time SET DEFAULT CURRENT_TIMESTAMP;
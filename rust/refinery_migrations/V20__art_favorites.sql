@@ -0,0 +1,17 @@
+-- Art that users saved because they liked it.
+CREATE TABLE art_favorite (
+    user_id integer NOT NULL
+        REFERENCES site_user(id)
+        ON DELETE CASCADE,
+
+    art_id int NOT NULL
+        REFERENCES art(id)
+        ON DELETE CASCADE,
+
+    created_at timestamp with time zone NOT NULL DEFAULT NOW(),
+
+    PRIMARY KEY (user_id, art_id)
+);
+
+-- The primary key covers looking up by user, this one's for counting favorites on an art piece.
+CREATE INDEX art_favorite_art_id_index ON art_favorite (art_id);
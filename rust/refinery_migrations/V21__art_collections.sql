@@ -0,0 +1,37 @@
+-- A named, ordered group of art posts. Mostly for comics and series that span more than one post.
+CREATE TABLE art_collection (
+    id int PRIMARY KEY GENERATED ALWAYS AS IDENTITY, -- Created by db, auto-increments.
+    last_modified_date timestamp with time zone NOT NULL DEFAULT CURRENT_TIMESTAMP, -- Updates whenever this row is modified, see `update_last_modified_date()`.
+
+    slug text NOT NULL UNIQUE CHECK (TRIM(slug) != ''),
+    title text NOT NULL CHECK (TRIM(title) != ''),
+    description text CHECK (TRIM(description) != ''), -- Assumed to be markdown.
+
+    owner_id integer -- The user who made the collection. NULL if they deleted their account, then only admins can edit it.
+        REFERENCES site_user(id)
+        ON DELETE SET NULL
+);
+
+CREATE TRIGGER art_collection_last_modified
+BEFORE UPDATE ON art_collection
+FOR EACH ROW
+EXECUTE FUNCTION update_last_modified_date();
+
+-- Which art is in which collection, and in what order.
+CREATE TABLE art_collection_member (
+    collection_id int NOT NULL
+        REFERENCES art_collection(id)
+        ON DELETE CASCADE,
+
+    art_id int NOT NULL
+        REFERENCES art(id)
+        ON DELETE CASCADE,
+
+    internal_order int NOT NULL, -- Lowest goes first. Gaps are fine, e.g. when a member gets deleted.
+
+    PRIMARY KEY (collection_id, art_id),
+    UNIQUE (collection_id, internal_order)
+);
+
+-- For finding which collections a given art piece is in.
+CREATE INDEX art_collection_member_art_id_index ON art_collection_member (art_id);
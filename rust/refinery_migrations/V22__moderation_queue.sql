@@ -0,0 +1,15 @@
+-- Looked at by an admin and turned down. Not visible, the reason is in the post's `rejection_reason`.
+ALTER TYPE post_state ADD VALUE 'rejected';
+
+-- Why an admin rejected the post, shown to whoever submitted it. NULL if it wasn't rejected.
+ALTER TABLE art
+ADD rejection_reason text CHECK (TRIM(rejection_reason) != '');
+
+ALTER TABLE character
+ADD rejection_reason text CHECK (TRIM(rejection_reason) != '');
+
+-- Characters didn't track who posted them, which we need now that anyone can submit one.
+ALTER TABLE character
+ADD uploading_user_id integer -- If NULL, points to "Unknown User", to handle deleted accounts or pre-website characters.
+    REFERENCES site_user(id)
+    ON DELETE SET NULL;
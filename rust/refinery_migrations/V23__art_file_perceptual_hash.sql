@@ -0,0 +1,5 @@
+-- A 64-bit difference hash of each art file, used to catch the same picture being uploaded twice.
+-- Stored as a bigint since postgres doesn't have unsigned ints; only the bits matter anyways.
+-- NULL for files we can't hash (videos, SVGs) and for files uploaded before this existed.
+ALTER TABLE art_file
+ADD perceptual_hash bigint DEFAULT NULL;
@@ -0,0 +1,11 @@
+-- What we know about each art file, so pages can reserve space for art before it loads, and searches can filter by shape.
+-- All NULL for files uploaded before this, and width/height stay NULL for anything we can't measure (SVGs, non-MP4 videos).
+ALTER TABLE art_file
+ADD mime_type text DEFAULT NULL,
+ADD byte_size bigint DEFAULT NULL,
+ADD width int DEFAULT NULL,
+ADD height int DEFAULT NULL,
+ADD duration_seconds double precision DEFAULT NULL; -- Only for videos.
+
+-- For orientation and resolution filters on the art index.
+CREATE INDEX art_file_dimensions ON art_file (belongs_to, width, height);
@@ -0,0 +1,20 @@
+-- Smaller WebP copies of each art file, so phones don't have to download the full-size original.
+-- The original itself isn't in here, it's the biggest size on the ladder by definition.
+CREATE TABLE art_file_variant (
+    id int PRIMARY KEY GENERATED ALWAYS AS IDENTITY, -- Created by db, auto-increments.
+    belongs_to int NOT NULL
+        REFERENCES art(id)
+        ON DELETE CASCADE,
+
+    -- The s3_key of the art_file this is a copy of. Not a foreign key, since art_file rows get replaced whenever art is reordered.
+    source_s3_key text NOT NULL,
+    s3_key text NOT NULL, -- Points to the public bucket key
+
+    width int NOT NULL,
+    height int NOT NULL,
+    byte_size bigint NOT NULL,
+
+    UNIQUE (source_s3_key, width)
+);
+
+CREATE INDEX art_file_variant_belongs_to ON art_file_variant (belongs_to);
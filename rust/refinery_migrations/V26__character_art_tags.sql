@@ -0,0 +1,12 @@
+-- Characters are linked to their art through relevant_tag, so it has to be the tag's canonical name, not an alias of it.
+UPDATE character SET relevant_tag = tag.name
+FROM tag_alias JOIN tag ON tag.id = tag_alias.tag_id
+WHERE character.relevant_tag = tag_alias.alias;
+
+-- Every character's tag is a character tag. Don't touch tags someone already put in some other category on purpose, though.
+INSERT INTO tag (name, category)
+SELECT DISTINCT relevant_tag, 'character'::tag_category FROM character WHERE relevant_tag IS NOT NULL
+ON CONFLICT (name) DO UPDATE SET category = 'character' WHERE tag.category = 'general';
+
+-- Character pages and the character index look up art by a single tag a lot.
+CREATE INDEX art_tags_index ON art USING GIN (tags);
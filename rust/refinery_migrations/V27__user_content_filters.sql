@@ -0,0 +1,8 @@
+-- What a user does with art that their filters catch. Hidden art is left out entirely, blurred art is shown but blurred out.
+CREATE TYPE filtered_art_display AS ENUM ('hide', 'blur');
+
+ALTER TABLE site_user
+    ADD COLUMN blacklisted_tags text[] NOT NULL DEFAULT ARRAY[]::text[], -- Canonical tag names, aliases get resolved before they're saved.
+    ADD COLUMN blacklisted_artists text[] NOT NULL DEFAULT ARRAY[]::text[],
+    ADD COLUMN show_nsfw_by_default boolean NOT NULL DEFAULT FALSE, -- If true, they never see the NSFW splash.
+    ADD COLUMN filtered_art_display filtered_art_display NOT NULL DEFAULT 'hide';
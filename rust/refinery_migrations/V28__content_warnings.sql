@@ -0,0 +1,13 @@
+-- Heads ups for stuff in art that isn't NSFW, but that people might still not want sprung on them.
+CREATE TYPE content_warning AS ENUM (
+    'gore',
+    'flashing_imagery',
+    'lore_spoilers' -- Spoilers for lore that isn't out yet.
+);
+
+ALTER TABLE art
+    ADD COLUMN content_warnings content_warning[] NOT NULL DEFAULT ARRAY[]::content_warning[];
+
+-- The warnings a user is fine with, so art with only those doesn't get covered up for them.
+ALTER TABLE site_user
+    ADD COLUMN shown_content_warnings content_warning[] NOT NULL DEFAULT ARRAY[]::content_warning[];
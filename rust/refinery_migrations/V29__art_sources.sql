@@ -0,0 +1,3 @@
+-- Links to wherever the art was originally posted, in order. Discord pins, tweets, tumblr posts and such.
+ALTER TABLE art
+    ADD COLUMN sources text[] NOT NULL DEFAULT ARRAY[]::text[];
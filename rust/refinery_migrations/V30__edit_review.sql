@@ -0,0 +1,7 @@
+-- Public posts edited by someone who can't post directly stay up, but an admin still needs to look at the edit.
+-- While this is true, the post shows up in the approval queue next to the new submissions.
+ALTER TABLE art
+    ADD COLUMN edited_since_review boolean NOT NULL DEFAULT false;
+
+ALTER TABLE character
+    ADD COLUMN edited_since_review boolean NOT NULL DEFAULT false;
@@ -0,0 +1,20 @@
+-- Content warnings live in art.content_warnings, which is what covers art and what the search filters use.
+-- A tag in the 'content_warning' category did neither, so drop the category rather than have it look like it does.
+UPDATE tag SET category = 'general' WHERE category = 'content_warning';
+
+-- Postgres can't remove a value from an enum, so swap the whole type out.
+ALTER TYPE tag_category RENAME TO tag_category_old;
+
+CREATE TYPE tag_category AS ENUM (
+    'general', -- Default, anything that doesn't fit elsewhere.
+    'character', -- A character appearing in the piece.
+    'artist', -- Tags about who made it, for stuff that isn't covered by the creators column.
+    'meta' -- Info about the post itself, like "comic" or "wip".
+);
+
+ALTER TABLE tag
+    ALTER COLUMN category DROP DEFAULT,
+    ALTER COLUMN category TYPE tag_category USING category::text::tag_category,
+    ALTER COLUMN category SET DEFAULT 'general';
+
+DROP TYPE tag_category_old;
@@ -0,0 +1,10 @@
+-- Whether we've already tried making smaller copies of this file. The scheduled backfill goes over the ones that aren't.
+-- Files that already have copies, and ones that never get any (videos, SVGs), count as checked.
+ALTER TABLE art_file
+    ADD COLUMN variants_checked boolean NOT NULL DEFAULT false;
+
+UPDATE art_file SET variants_checked = true
+WHERE EXISTS (SELECT 1 FROM art_file_variant WHERE art_file_variant.source_s3_key = art_file.s3_key)
+    OR (mime_type IS NOT NULL AND (mime_type NOT LIKE 'image/%' OR mime_type = 'image/svg+xml'));
+
+CREATE INDEX art_file_unchecked_variants ON art_file (id) WHERE NOT variants_checked;
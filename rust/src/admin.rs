@@ -11,6 +11,8 @@ use http::Uri;
 
 mod arbitrary_values;
 mod art_archival_project;
+mod queue;
+mod tags;
 
 pub fn router() -> Router<ServerState> {
     Router::new()
@@ -25,6 +27,11 @@ pub fn router() -> Router<ServerState> {
             get(art_archival_project::view_archival_progress)
                 .patch(art_archival_project::update_archival_progress),
         )
+        .route_with_tsr("/tags", get(tags::tag_panel).patch(tags::patch_tags))
+        .route_with_tsr(
+            "/queue",
+            get(queue::queue_panel).patch(queue::review_queue_item),
+        )
 }
 
 #[derive(Debug, Template)]
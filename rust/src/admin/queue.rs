@@ -0,0 +1,237 @@
+use crate::{
+    utils::{self, sql::PostState, template_to_response},
+    RootErrors, ServerState, User,
+};
+use askama::Template;
+use axum::{
+    extract::{OriginalUri, State},
+    response::{IntoResponse, Response},
+    Json,
+};
+use chrono::{DateTime, Utc};
+use deadpool::managed::Object;
+use deadpool_postgres::Manager;
+use http::{StatusCode, Uri};
+use serde::Deserialize;
+
+/// The kinds of posts that can end up in the approval queue.
+#[derive(Debug, Clone, Copy, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum QueueItemType {
+    Art,
+    Character,
+}
+
+impl QueueItemType {
+    /// The table where posts of this type live. Conveniently also how the type is written in requests.
+    fn table_name(&self) -> &'static str {
+        match self {
+            Self::Art => "art",
+            Self::Character => "character",
+        }
+    }
+}
+
+/// A post waiting for an admin to look at it.
+#[derive(Debug)]
+struct QueueItem {
+    item_type: QueueItemType,
+    id: i32,
+    title: String,
+    page_url: String,
+    thumbnail_url: String,
+    submitter: Option<User>, // None for deleted users.
+    submission_time: DateTime<Utc>,
+    is_edit: bool, // Already public, but edited by someone who can't post directly.
+}
+
+#[derive(Debug, Template)]
+#[template(path = "admin/queue.html")]
+struct QueuePanel {
+    user: Option<User>,
+    original_uri: Uri,
+
+    queue_items: Vec<QueueItem>,
+}
+
+/// If an admin is logged in, shows everything that's waiting for approval or has unreviewed edits, oldest first.
+pub async fn queue_panel(
+    State(state): State<ServerState>,
+    OriginalUri(original_uri): OriginalUri,
+    cookie_jar: tower_cookies::Cookies,
+) -> Result<Response, RootErrors> {
+    let db_connection = state
+        .db_pool
+        .get()
+        .await
+        .map_err(|_err| RootErrors::InternalServerError)?;
+
+    let user = User::get_from_cookie_jar(&db_connection, &cookie_jar).await;
+
+    if !super::user_is_admin(&user) {
+        return Err(RootErrors::NotFound(original_uri, cookie_jar, user));
+    }
+
+    let queue_items = get_queue_items(&db_connection).await.map_err(|err| {
+        eprintln!("[MODERATION QUEUE] Failed getting pending posts! {err:?}");
+        RootErrors::InternalServerError
+    })?;
+
+    Ok(template_to_response(QueuePanel {
+        user,
+        original_uri,
+        queue_items,
+    }))
+}
+
+/// Returns all the art and characters pending approval, plus public ones with unreviewed edits, oldest first.
+async fn get_queue_items(
+    db_connection: &Object<Manager>,
+) -> Result<Vec<QueueItem>, tokio_postgres::Error> {
+    // SAFETY: Nothing user-given in here.
+    const PENDING_POSTS_QUERY: &str = "
+        SELECT 'art' AS item_type, id, title, '/art/' || page_slug AS page_url, thumbnail, uploading_user_id, last_modified_date,
+                post_state = 'public' AS is_edit
+            FROM art WHERE post_state = 'pending_approval' OR (post_state = 'public' AND edited_since_review)
+        UNION ALL
+        SELECT 'character', id, short_name, '/characters/' || page_slug, thumbnail, uploading_user_id, last_modified_date,
+                post_state = 'public'
+            FROM character WHERE post_state = 'pending_approval' OR (post_state = 'public' AND edited_since_review)
+        ORDER BY last_modified_date";
+
+    let rows = db_connection.query(PENDING_POSTS_QUERY, &[]).await?;
+
+    let mut queue_items = Vec::with_capacity(rows.len());
+
+    for row in rows {
+        let submitter_id: Option<i32> = row.get("uploading_user_id");
+        let submitter = match submitter_id {
+            Some(submitter_id) => User::get_by_id(db_connection, &submitter_id).await,
+            None => None,
+        };
+
+        let item_type = match row.get::<_, &str>("item_type") {
+            "character" => QueueItemType::Character,
+            _ => QueueItemType::Art,
+        };
+
+        queue_items.push(QueueItem {
+            item_type,
+            id: row.get("id"),
+            title: row.get("title"),
+            page_url: row.get("page_url"),
+            thumbnail_url: utils::get_s3_public_object_url(row.get("thumbnail")),
+            submitter,
+            submission_time: row.get("last_modified_date"),
+            is_edit: row.get("is_edit"),
+        });
+    }
+
+    Ok(queue_items)
+}
+
+/// What the admin thinks of the post.
+#[derive(Debug, Deserialize)]
+#[serde(tag = "decision", rename_all = "snake_case")]
+pub enum ReviewDecision {
+    /// Make it public, or keep the edit if it already was.
+    Approve,
+    /// Keep it hidden, or take it down if it was public, and tell the submitter why.
+    Reject { reason: String },
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ReviewRequest {
+    item_type: QueueItemType,
+    id: i32,
+
+    #[serde(flatten)]
+    decision: ReviewDecision,
+}
+
+/// Approves or rejects a post in the queue.
+pub async fn review_queue_item(
+    State(state): State<ServerState>,
+    cookie_jar: tower_cookies::Cookies,
+    Json(review_request): Json<ReviewRequest>,
+) -> Result<Response, RootErrors> {
+    let db_connection = state
+        .db_pool
+        .get()
+        .await
+        .map_err(|_err| RootErrors::InternalServerError)?;
+
+    let requesting_user = User::get_from_cookie_jar(&db_connection, &cookie_jar).await;
+
+    let requesting_admin = match requesting_user {
+        Some(_) => {
+            if !super::user_is_admin(&requesting_user) {
+                return Err(RootErrors::Forbidden);
+            } else {
+                requesting_user.unwrap()
+            }
+        }
+        None => {
+            return Err(RootErrors::Unauthorized);
+        }
+    };
+
+    let (new_post_state, rejection_reason) = match &review_request.decision {
+        ReviewDecision::Approve => (PostState::Public, None),
+        ReviewDecision::Reject { reason } => {
+            let reason = reason.trim();
+
+            if reason.is_empty() {
+                return Err(RootErrors::BadRequest(
+                    "Give the submitter a reason, they'll want to know what to fix.".to_string(),
+                ));
+            }
+
+            (PostState::Rejected, Some(reason.to_string()))
+        }
+    };
+
+    // Either way, whatever edits the post had are reviewed now.
+    // SAFETY: The table name comes from our own enum, the user-given values are all passed as parameters.
+    let review_query = format!(
+        "UPDATE {} SET post_state=$1, rejection_reason=$2, edited_since_review=false
+            WHERE id=$3 AND (post_state='pending_approval' OR (post_state='public' AND edited_since_review))",
+        review_request.item_type.table_name()
+    );
+
+    let updated_rows = db_connection
+        .execute(
+            &review_query,
+            &[&new_post_state, &rejection_reason, &review_request.id],
+        )
+        .await
+        .map_err(|err| {
+            eprintln!("[MODERATION QUEUE] Failed reviewing {review_request:?}! {err:?}");
+            RootErrors::InternalServerError
+        })?;
+
+    // Either someone else got to it first, or the submitter edited it mid-review.
+    if updated_rows == 0 {
+        return Err(RootErrors::BadRequest(
+            "That post isn't pending approval anymore. Refresh the page.".to_string(),
+        ));
+    }
+
+    if review_request.item_type == QueueItemType::Art {
+        // Approved art should show up in the counts and other art's strips right away.
+        // Rejected edits take public art down, so it has to disappear from both just as fast.
+        state.art_count_cache.clear();
+        state.related_art_cache.clear();
+    }
+
+    println!(
+        "[MODERATION QUEUE] Admin {} (ID:{}) set {} ID {} to {:?}.",
+        requesting_admin.display_name,
+        requesting_admin.id,
+        review_request.item_type.table_name(),
+        review_request.id,
+        new_post_state
+    );
+
+    Ok((StatusCode::OK, "").into_response())
+}
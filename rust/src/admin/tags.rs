@@ -0,0 +1,540 @@
+use crate::{
+    art::tags::Tag,
+    utils::{self, template_to_response},
+    RootErrors, ServerState, User,
+};
+use askama::Template;
+use axum::{
+    extract::{OriginalUri, State},
+    response::{IntoResponse, Response},
+    Json,
+};
+use deadpool::managed::Object;
+use deadpool_postgres::{Manager, Transaction};
+use http::{StatusCode, Uri};
+use postgres::Row;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Template)]
+#[template(path = "admin/tags.html")]
+struct TagPanel {
+    user: Option<User>,
+    original_uri: Uri,
+
+    all_tags: Vec<Tag>,
+}
+
+/// If an admin is logged in, shows every tag (public art or not) and the bulk operations you can do on them.
+pub async fn tag_panel(
+    State(state): State<ServerState>,
+    OriginalUri(original_uri): OriginalUri,
+    cookie_jar: tower_cookies::Cookies,
+) -> Result<Response, RootErrors> {
+    let db_connection = state
+        .db_pool
+        .get()
+        .await
+        .map_err(|_err| RootErrors::InternalServerError)?;
+
+    let user = User::get_from_cookie_jar(&db_connection, &cookie_jar).await;
+
+    if !super::user_is_admin(&user) {
+        return Err(RootErrors::NotFound(original_uri, cookie_jar, user));
+    }
+
+    Ok(template_to_response(TagPanel {
+        user,
+        original_uri,
+        all_tags: get_every_tag(&db_connection)
+            .await
+            .ok_or(RootErrors::InternalServerError)?,
+    }))
+}
+
+/// Returns every tag there is, in the registry or on any art, public or not, alongside how many pieces use it.
+/// Unlike the tag list on the art index, which only counts public art, admins need to see everything they can rename or merge.
+/// None (after logging it) if the query failed.
+async fn get_every_tag(db_connection: &Object<Manager>) -> Option<Vec<Tag>> {
+    // Tags that somehow aren't in the registry are just treated as general tags.
+    let rows = db_connection
+        .query(
+            "SELECT COALESCE(tag.name, used_tags.name) AS name, COALESCE(used_tags.usage_count, 0) AS usage_count,
+                COALESCE(tag.category, 'general') AS category, tag.description
+            FROM tag
+            FULL JOIN (SELECT art_tag AS name, COUNT(*) AS usage_count FROM art, unnest(tags) AS art_tag GROUP BY art_tag) AS used_tags
+                ON used_tags.name = tag.name
+            ORDER BY category, name",
+            &[],
+        )
+        .await
+        .inspect_err(|err| eprintln!("[TAG PANEL] Failed getting every tag! {err:?}"))
+        .ok()?;
+
+    Some(
+        rows.iter()
+            .map(|row| Tag {
+                name: row.get("name"),
+                category: row.get("category"),
+                description: row.get("description"),
+                usage_count: row.get("usage_count"),
+            })
+            .collect(),
+    )
+}
+
+/// Something an admin wants to do to a tag across all the art in the site.
+#[derive(Debug, Deserialize)]
+#[serde(tag = "operation", rename_all = "snake_case")]
+pub enum TagOperation {
+    /// Renames `tag` to `new_name` everywhere. The old name is kept as an alias.
+    Rename { tag: String, new_name: String },
+    /// Replaces `tag` with `into` everywhere, and makes `tag` an alias of `into`.
+    Merge { tag: String, into: String },
+    /// Strips `tag` from every art piece, and drops it from the registry.
+    Delete { tag: String },
+}
+
+#[derive(Debug, Deserialize)]
+pub struct TagOperationRequest {
+    #[serde(flatten)]
+    operation: TagOperation,
+
+    /// If true, nothing is changed, we only return which art would be affected.
+    #[serde(default)]
+    preview: bool,
+}
+
+#[derive(Debug, Serialize)]
+struct TagOperationResponse {
+    affected_slugs: Vec<String>,
+}
+
+pub async fn patch_tags(
+    State(state): State<ServerState>,
+    cookie_jar: tower_cookies::Cookies,
+    Json(operation_request): Json<TagOperationRequest>,
+) -> Result<Response, RootErrors> {
+    let mut db_connection = state
+        .db_pool
+        .get()
+        .await
+        .map_err(|_err| RootErrors::InternalServerError)?;
+
+    let requesting_user = User::get_from_cookie_jar(&db_connection, &cookie_jar).await;
+
+    let requesting_admin = match requesting_user {
+        Some(_) => {
+            if !super::user_is_admin(&requesting_user) {
+                return Err(RootErrors::Forbidden);
+            } else {
+                requesting_user.unwrap()
+            }
+        }
+        None => {
+            return Err(RootErrors::Unauthorized);
+        }
+    };
+
+    let operation = sanitize_tag_operation(operation_request.operation);
+    validate_tag_operation(&operation).map_err(RootErrors::BadRequest)?;
+
+    let affected_tag = match &operation {
+        TagOperation::Rename { tag, .. }
+        | TagOperation::Merge { tag, .. }
+        | TagOperation::Delete { tag } => tag.clone(),
+    };
+
+    // Both a preview and the real deal need to know what's going to get hit.
+    const AFFECTED_SLUGS_QUERY: &str =
+        "SELECT page_slug FROM art WHERE tags @> ARRAY[$1::text] ORDER BY page_slug";
+
+    if operation_request.preview {
+        let affected_slugs = rows_to_slugs(
+            db_connection
+                .query(AFFECTED_SLUGS_QUERY, &[&affected_tag])
+                .await,
+            &affected_tag,
+        )
+        .ok_or(RootErrors::InternalServerError)?;
+
+        return Ok(Json(TagOperationResponse { affected_slugs }).into_response());
+    }
+
+    // Either everything happens, or nothing does. Half-renamed tags are worse than misspelled ones.
+    let sql_transaction = db_connection.transaction().await.map_err(|err| {
+        eprintln!("[TAG OPERATION] Errored trying to create an SQL Transaction! {err:?}");
+        RootErrors::InternalServerError
+    })?;
+
+    // Locked, so nobody can tag more art with it between us listing it and rewriting it. The list is then exactly what got changed.
+    let affected_slugs = rows_to_slugs(
+        sql_transaction
+            .query(
+                &format!("{AFFECTED_SLUGS_QUERY} FOR UPDATE"),
+                &[&affected_tag],
+            )
+            .await,
+        &affected_tag,
+    )
+    .ok_or(RootErrors::InternalServerError)?;
+
+    let operation_result = match &operation {
+        TagOperation::Rename { tag, new_name } => rename_tag(&sql_transaction, tag, new_name).await,
+        TagOperation::Merge { tag, into } => merge_tags(&sql_transaction, tag, into).await,
+        TagOperation::Delete { tag } => delete_tag(&sql_transaction, tag).await,
+    };
+
+    if let Err(err) = operation_result {
+        eprintln!("[TAG OPERATION] Failed running {operation:?}! Rolling back. {err:?}");
+
+        // A failed constraint is most likely the admin asking for something nonsensical, not us breaking.
+        return Err(match err.code() {
+            Some(_) => RootErrors::BadRequest(format!("The database refused this change: {err}")),
+            None => RootErrors::InternalServerError,
+        });
+    }
+
+    sql_transaction.commit().await.map_err(|err| {
+        eprintln!("[TAG OPERATION] Errored trying to run SQL Transaction! {err:?}");
+        RootErrors::InternalServerError
+    })?;
+
+    // Tags just changed on who knows how many posts, so every cached count and strip could be off now.
+    state.art_count_cache.clear();
+    state.related_art_cache.clear();
+
+    println!(
+        "[TAG OPERATION] Admin {} (ID:{}) ran {operation:?}, affecting {} art pieces.",
+        requesting_admin.display_name,
+        requesting_admin.id,
+        affected_slugs.len()
+    );
+
+    Ok((
+        StatusCode::OK,
+        Json(TagOperationResponse { affected_slugs }),
+    )
+        .into_response())
+}
+
+/// Pulls the slugs out of an affected art query. None (after logging it) if the query failed.
+fn rows_to_slugs(
+    query_result: Result<Vec<Row>, tokio_postgres::Error>,
+    affected_tag: &str,
+) -> Option<Vec<String>> {
+    match query_result {
+        Ok(rows) => Some(rows.iter().map(|row| row.get("page_slug")).collect()),
+        Err(err) => {
+            eprintln!("[TAG OPERATION] Failed getting art tagged {affected_tag}! {err:?}");
+            None
+        }
+    }
+}
+
+/// Trims and lowercases all the tag names in the operation, the same way tags are cleaned up when posting art.
+fn sanitize_tag_operation(operation: TagOperation) -> TagOperation {
+    let clean = |tag: String| tag.trim().to_lowercase();
+
+    match operation {
+        TagOperation::Rename { tag, new_name } => TagOperation::Rename {
+            tag: clean(tag),
+            new_name: clean(new_name),
+        },
+        TagOperation::Merge { tag, into } => TagOperation::Merge {
+            tag: clean(tag),
+            into: clean(into),
+        },
+        TagOperation::Delete { tag } => TagOperation::Delete { tag: clean(tag) },
+    }
+}
+
+/// Makes sure the operation makes sense. If it doesn't, returns a readable explanation why.
+fn validate_tag_operation(operation: &TagOperation) -> Result<(), String> {
+    let target_tag = match operation {
+        TagOperation::Rename { tag, new_name } => {
+            if tag == new_name {
+                return Err("The new name is the same as the old one.".to_string());
+            }
+            Some(new_name)
+        }
+        TagOperation::Merge { tag, into } => {
+            if tag == into {
+                return Err("Can't merge a tag into itself.".to_string());
+            }
+            Some(into)
+        }
+        TagOperation::Delete { .. } => None,
+    };
+
+    if let Some(target_tag) = target_tag {
+        if !utils::is_valid_tag(target_tag) {
+            return Err(format!("\"{target_tag}\" isn't a valid tag. Tags must be lowercase letters, numbers, and may include hyphens and underscores in the middle."));
+        }
+    }
+
+    Ok(())
+}
+
+/// Renames a tag in all art and in the registry, keeping the old name as an alias.
+async fn rename_tag(
+    sql_transaction: &Transaction<'_>,
+    tag: &str,
+    new_name: &str,
+) -> Result<(), tokio_postgres::Error> {
+    let new_name_in_use = sql_transaction
+        .query_one(
+            "SELECT EXISTS (SELECT 1 FROM art WHERE tags @> ARRAY[$1::text])
+                OR EXISTS (SELECT 1 FROM tag WHERE name = $1)",
+            &[&new_name],
+        )
+        .await?
+        .get::<_, bool>(0);
+
+    // If the name's taken, this is a merge, and merges need to handle duplicates. Just do that instead.
+    if new_name_in_use {
+        return merge_tags(sql_transaction, tag, new_name).await;
+    }
+
+    sql_transaction
+        .execute(
+            "UPDATE art SET tags = array_replace(tags, $1, $2) WHERE tags @> ARRAY[$1::text]",
+            &[&tag, &new_name],
+        )
+        .await?;
+    retag_characters(sql_transaction, tag, Some(new_name)).await?;
+
+    // If the new name used to be an alias for something, it's a real tag now.
+    sql_transaction
+        .execute("DELETE FROM tag_alias WHERE alias = $1", &[&new_name])
+        .await?;
+
+    // Anything pointing at the old name (aliases, implications) follows it, since it's the same row.
+    sql_transaction
+        .execute(
+            "INSERT INTO tag (name) VALUES ($1) ON CONFLICT DO NOTHING",
+            &[&tag],
+        )
+        .await?;
+    sql_transaction
+        .execute(
+            "UPDATE tag SET name = $2 WHERE name = $1",
+            &[&tag, &new_name],
+        )
+        .await?;
+
+    add_alias(sql_transaction, tag, new_name).await
+}
+
+/// Replaces `tag` with `into` in all art, moves everything attached to `tag` over to `into`, and makes `tag` an alias.
+async fn merge_tags(
+    sql_transaction: &Transaction<'_>,
+    tag: &str,
+    into: &str,
+) -> Result<(), tokio_postgres::Error> {
+    // If the art already has both, just drop the old one so we don't get duplicates.
+    sql_transaction
+        .execute(
+            "UPDATE art SET tags = CASE
+                WHEN tags @> ARRAY[$2::text] THEN array_remove(tags, $1)
+                ELSE array_replace(tags, $1, $2)
+            END
+            WHERE tags @> ARRAY[$1::text]",
+            &[&tag, &into],
+        )
+        .await?;
+    retag_characters(sql_transaction, tag, Some(into)).await?;
+
+    sql_transaction
+        .execute("DELETE FROM tag_alias WHERE alias = $1", &[&into])
+        .await?;
+    sql_transaction
+        .execute(
+            "INSERT INTO tag (name) VALUES ($1), ($2) ON CONFLICT DO NOTHING",
+            &[&tag, &into],
+        )
+        .await?;
+
+    // Move over the aliases and implications. Anything that'd end up pointing at itself or already exists is skipped,
+    // and whatever's left on the old tag gets removed by the cascade when it's deleted.
+    sql_transaction
+        .execute(
+            "UPDATE tag_alias SET tag_id = (SELECT id FROM tag WHERE name = $2)
+            WHERE tag_id = (SELECT id FROM tag WHERE name = $1)",
+            &[&tag, &into],
+        )
+        .await?;
+    sql_transaction
+        .execute(
+            "INSERT INTO tag_implication (tag_id, implied_tag_id)
+            SELECT new_tag.id, tag_implication.implied_tag_id
+            FROM tag_implication, tag AS old_tag, tag AS new_tag
+            WHERE old_tag.name = $1 AND new_tag.name = $2
+                AND tag_implication.tag_id = old_tag.id
+                AND tag_implication.implied_tag_id != new_tag.id
+            ON CONFLICT DO NOTHING",
+            &[&tag, &into],
+        )
+        .await?;
+    sql_transaction
+        .execute(
+            "INSERT INTO tag_implication (tag_id, implied_tag_id)
+            SELECT tag_implication.tag_id, new_tag.id
+            FROM tag_implication, tag AS old_tag, tag AS new_tag
+            WHERE old_tag.name = $1 AND new_tag.name = $2
+                AND tag_implication.implied_tag_id = old_tag.id
+                AND tag_implication.tag_id != new_tag.id
+            ON CONFLICT DO NOTHING",
+            &[&tag, &into],
+        )
+        .await?;
+
+    sql_transaction
+        .execute("DELETE FROM tag WHERE name = $1", &[&tag])
+        .await?;
+
+    add_alias(sql_transaction, tag, into).await
+}
+
+/// Strips a tag from all art, and removes it (and its aliases) from the registry.
+async fn delete_tag(
+    sql_transaction: &Transaction<'_>,
+    tag: &str,
+) -> Result<(), tokio_postgres::Error> {
+    sql_transaction
+        .execute(
+            "UPDATE art SET tags = array_remove(tags, $1) WHERE tags @> ARRAY[$1::text]",
+            &[&tag],
+        )
+        .await?;
+    retag_characters(sql_transaction, tag, None).await?;
+
+    sql_transaction
+        .execute("DELETE FROM tag WHERE name = $1", &[&tag])
+        .await?;
+
+    Ok(())
+}
+
+/// Characters are linked to their art by tag, so they need to follow it wherever it goes.
+/// If the tag's gone, they just aren't linked to any art anymore.
+async fn retag_characters(
+    sql_transaction: &Transaction<'_>,
+    tag: &str,
+    new_tag: Option<&str>,
+) -> Result<(), tokio_postgres::Error> {
+    sql_transaction
+        .execute(
+            "UPDATE character SET relevant_tag = $2 WHERE relevant_tag = $1",
+            &[&tag, &new_tag],
+        )
+        .await?;
+
+    Ok(())
+}
+
+/// Makes `alias` point at the tag named `tag_name`, so old links and habits still work.
+async fn add_alias(
+    sql_transaction: &Transaction<'_>,
+    alias: &str,
+    tag_name: &str,
+) -> Result<(), tokio_postgres::Error> {
+    sql_transaction
+        .execute(
+            "INSERT INTO tag_alias (alias, tag_id) SELECT $1, id FROM tag WHERE name = $2
+            ON CONFLICT (alias) DO UPDATE SET tag_id = EXCLUDED.tag_id",
+            &[&alias, &tag_name],
+        )
+        .await?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request_from_json(json: &str) -> TagOperationRequest {
+        serde_json::from_str(json).expect("test request should deserialize")
+    }
+
+    #[test]
+    fn requests_deserialize() {
+        let request = request_from_json(r#"{"operation":"rename","tag":"kat","new_name":"kate"}"#);
+        assert!(!request.preview);
+        assert!(matches!(
+            request.operation,
+            TagOperation::Rename { tag, new_name } if tag == "kat" && new_name == "kate"
+        ));
+
+        let request =
+            request_from_json(r#"{"operation":"merge","tag":"kat","into":"kate","preview":true}"#);
+        assert!(request.preview);
+        assert!(matches!(
+            request.operation,
+            TagOperation::Merge { tag, into } if tag == "kat" && into == "kate"
+        ));
+
+        let request = request_from_json(r#"{"operation":"delete","tag":"kat"}"#);
+        assert!(matches!(request.operation, TagOperation::Delete { tag } if tag == "kat"));
+
+        assert!(serde_json::from_str::<TagOperationRequest>(r#"{"operation":"nuke"}"#).is_err());
+    }
+
+    #[test]
+    fn operations_are_trimmed_and_lowercased() {
+        let operation = sanitize_tag_operation(TagOperation::Merge {
+            tag: "  Kat ".to_string(),
+            into: "KATE\n".to_string(),
+        });
+
+        assert!(matches!(
+            operation,
+            TagOperation::Merge { tag, into } if tag == "kat" && into == "kate"
+        ));
+    }
+
+    #[test]
+    fn nonsensical_operations_are_refused() {
+        let refused = [
+            TagOperation::Rename {
+                tag: "kate".to_string(),
+                new_name: "kate".to_string(),
+            },
+            TagOperation::Merge {
+                tag: "kate".to_string(),
+                into: "kate".to_string(),
+            },
+            TagOperation::Rename {
+                tag: "kate".to_string(),
+                new_name: "kate fynn".to_string(),
+            },
+            TagOperation::Merge {
+                tag: "kate".to_string(),
+                into: "-kate".to_string(),
+            },
+        ];
+
+        for operation in refused {
+            assert!(validate_tag_operation(&operation).is_err(), "{operation:?}");
+        }
+
+        let accepted = [
+            TagOperation::Rename {
+                tag: "kat".to_string(),
+                new_name: "kate".to_string(),
+            },
+            TagOperation::Merge {
+                tag: "kat".to_string(),
+                into: "kate-and-fynn".to_string(),
+            },
+            // Deleting doesn't care whether the old name was a valid tag, it's how bad ones get cleaned up.
+            TagOperation::Delete {
+                tag: "Bad Tag!".to_string(),
+            },
+        ];
+
+        for operation in accepted {
+            assert!(validate_tag_operation(&operation).is_ok(), "{operation:?}");
+        }
+    }
+}
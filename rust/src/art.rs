@@ -1,11 +1,17 @@
 use std::cmp::{self, min};
+use std::collections::HashSet;
 
-use crate::{nsfw_splash, user::User, utils::template_to_response, RootErrors, ServerState};
+use crate::{
+    nsfw_splash,
+    user::{FilteredArtDisplay, User},
+    utils::template_to_response,
+    RootErrors, ServerState,
+};
 use askama::Template;
 use axum::{
-    extract::{DefaultBodyLimit, OriginalUri, Query, State},
+    extract::{DefaultBodyLimit, OriginalUri, Path, Query, State},
     response::Response,
-    routing::{get, post},
+    routing::{get, patch, post},
     Router,
 };
 use axum_extra::routing::RouterExt;
@@ -15,12 +21,26 @@ use http::Uri;
 use structs::ArtSearchParameters;
 use tower_cookies::Cookies;
 
+mod collections;
 mod comment;
+pub mod content_warnings;
+mod cursor;
+mod download;
+mod duplicates;
 mod edit;
+mod favorite;
 mod page;
 mod post;
 mod random;
+mod related;
+mod sources;
 mod structs;
+mod tag_query;
+pub mod tags;
+mod variants;
+
+pub use structs::BaseArt;
+pub use variants::backfill_art_file_variants;
 
 pub fn router() -> Router<ServerState> {
     Router::new()
@@ -35,8 +55,22 @@ pub fn router() -> Router<ServerState> {
                 .put(post::edit_art_put_request)
                 .post(post::edit_art_put_request),
         )
+        .route_with_tsr("/collections", post(collections::create_collection))
+        .route_with_tsr(
+            "/collections/{collection_slug}",
+            get(collections::collection_page).put(collections::reorder_collection),
+        )
         .route_with_tsr("/{art_slug}/comment", post(comment::add_comment))
+        .route_with_tsr(
+            "/{art_slug}/comment/{comment_id}",
+            patch(comment::edit_comment).delete(comment::delete_comment),
+        )
+        .route_with_tsr(
+            "/{art_slug}/favorite",
+            post(favorite::add_favorite).delete(favorite::remove_favorite),
+        )
         .route_with_tsr("/{art_slug}/edit", get(edit::edit_art_page))
+        .route_with_tsr("/{art_slug}/download", get(download::download_art))
 }
 
 /// To keep things consistent, whenever you need to present art to the user, sort it by this.
@@ -48,6 +82,11 @@ struct ArtIndexPage<'a> {
     user: Option<User>,
     original_uri: Uri,
 
+    /// The title of the page. "Art" for the regular index.
+    heading: String,
+    /// The page that searches and page links lead back to. "/art" for the regular index.
+    base_url: String,
+
     random_quote: String,
 
     current_page_number: i64,
@@ -59,25 +98,97 @@ struct ArtIndexPage<'a> {
     last_page_url: Option<String>,
 
     art_pieces: Vec<structs::BaseArt>,
+    /// Art on this page that the user's filters caught, if they'd rather have it blurred than hidden.
+    blurred_art_ids: HashSet<i32>,
+    /// How much art the user's filters left out of this search, if they'd rather have it hidden.
+    hidden_by_filters_count: i64,
 
-    all_tags: Vec<String>,
+    all_tags: Vec<tags::Tag>,
 
     show_upload_button: bool,
+    /// Whether the user's uploads go straight to public. If not, they're submitted for approval instead.
+    user_can_post_directly: bool,
 
     user_search_params: &'a ArtSearchParameters,
 }
 
+impl ArtIndexPage<'_> {
+    fn is_blurred(&self, art: &structs::BaseArt) -> bool {
+        self.blurred_art_ids.contains(&art.id)
+    }
+}
+
 async fn art_index(
     State(state): State<ServerState>,
     Query(query_params): Query<ArtSearchParameters>,
     OriginalUri(original_uri): OriginalUri,
     cookie_jar: Cookies,
+) -> Result<Response, RootErrors> {
+    render_art_index(
+        state,
+        query_params,
+        original_uri,
+        cookie_jar,
+        "Art".to_string(),
+        "/art".to_string(),
+    )
+    .await
+}
+
+/// The art index, but only for art in the given user's favorites.
+pub async fn user_favorites_page(
+    Path(user_id): Path<i32>,
+    State(state): State<ServerState>,
+    Query(mut query_params): Query<ArtSearchParameters>,
+    OriginalUri(original_uri): OriginalUri,
+    cookie_jar: Cookies,
+) -> Result<Response, RootErrors> {
+    let favoriting_user = {
+        let db_connection = state
+            .db_pool
+            .get()
+            .await
+            .map_err(|_| RootErrors::InternalServerError)?;
+
+        match User::get_by_id(&db_connection, &user_id).await {
+            Some(favoriting_user) => favoriting_user,
+            None => {
+                let user = User::get_from_cookie_jar(&db_connection, &cookie_jar).await;
+                return Err(RootErrors::NotFound(original_uri, cookie_jar, user));
+            }
+        }
+    };
+
+    query_params.favorited_by = Some(favoriting_user.id);
+
+    render_art_index(
+        state,
+        query_params,
+        original_uri,
+        cookie_jar,
+        format!("{}'s Favorites", favoriting_user.display_name),
+        format!("/user/{}/favorites", favoriting_user.id),
+    )
+    .await
+}
+
+/// Shows a page of the art matching the given search, with all the search and page controls around it.
+async fn render_art_index(
+    state: ServerState,
+    mut query_params: ArtSearchParameters,
+    original_uri: Uri,
+    cookie_jar: Cookies,
+    heading: String,
+    base_url: String,
 ) -> Result<Response, RootErrors> {
     // Static Values
     const AMOUNT_OF_ART_PER_PAGE: i64 = 24;
 
     let db_connection = state.db_pool.get().await.unwrap();
 
+    query_params.resolve_tag_aliases(&db_connection).await;
+    query_params.ensure_random_seed();
+
     let user: Option<User> = User::get_from_cookie_jar(&db_connection, &cookie_jar).await;
 
     // If the user is looking for something spicy, make sure we allow them to.
@@ -109,32 +220,100 @@ async fn art_index(
             .get(0)
     };
 
-    let total_amount_of_art = get_total_amount_of_art(&db_connection, &query_params)
+    // Blacklisted art either gets left out of the search, or stays in and gets blurred.
+    let content_filters = user
+        .as_ref()
+        .map(|user| user.content_filters.clone())
+        .unwrap_or_default();
+    let hide_filtered_art = content_filters.filtered_art_display == FilteredArtDisplay::Hide;
+
+    if hide_filtered_art {
+        query_params.exclude_blacklisted_art(&user);
+    }
+
+    let total_amount_of_art = get_total_amount_of_art(&state, &db_connection, &query_params)
         .await
         .unwrap();
 
+    // Let them know their filters are doing something, so they don't think the archive's just empty.
+    let hidden_by_filters_count = if query_params.has_exclusions() {
+        let unfiltered_search_params = ArtSearchParameters {
+            excluded_tags: Vec::new(),
+            excluded_artists: Vec::new(),
+            ..query_params.clone()
+        };
+
+        get_total_amount_of_art(&state, &db_connection, &unfiltered_search_params)
+            .await
+            .map(|unfiltered_amount| cmp::max(0, unfiltered_amount - total_amount_of_art))
+            .unwrap_or_default()
+    } else {
+        0
+    };
+
     // Total / per_page, rounded up.
     let total_pages_available_for_search =
         (total_amount_of_art + AMOUNT_OF_ART_PER_PAGE - 1) / AMOUNT_OF_ART_PER_PAGE;
 
     // The requested page, with a minimal value of 1 and maximal value of the total pages available.
-    let page_number_to_show = cmp::max(1, min(total_pages_available_for_search, query_params.page));
+    let requested_page_number =
+        cmp::max(1, min(total_pages_available_for_search, query_params.page));
 
-    let art_pieces = structs::BaseArt::get_art_from_index(
+    let art_page = structs::BaseArt::get_art_page(
         &db_connection,
-        (page_number_to_show - 1) * AMOUNT_OF_ART_PER_PAGE,
         AMOUNT_OF_ART_PER_PAGE,
+        requested_page_number,
         &query_params,
     )
     .await;
 
-    let show_upload_button = user
+    let page_number_to_show = art_page.page_number;
+
+    let blurred_art_ids = if hide_filtered_art {
+        HashSet::new()
+    } else {
+        structs::BaseArt::get_ids_caught_by_filters(
+            &db_connection,
+            &art_page
+                .art_pieces
+                .iter()
+                .map(|art| art.id)
+                .collect::<Vec<_>>(),
+            &content_filters,
+        )
+        .await
+    };
+
+    // The count is cached, so it might be a bit behind. Don't let it claim we're past the last page.
+    let total_pages_available_for_search = cmp::max(
+        total_pages_available_for_search,
+        page_number_to_show.saturating_add(i64::from(art_page.has_next_page)),
+    );
+
+    // Uploading from someone's favorites page would be weird.
+    // Anyone logged in can upload, but only some get to skip the approval queue.
+    let show_upload_button = query_params.favorited_by.is_none() && user.is_some();
+    let user_can_post_directly = user
         .as_ref()
         .is_some_and(|user| user.user_type.permissions().can_post_art);
 
+    // Direct jumps to a page number go by OFFSET, neighbouring pages go by cursor when possible.
+    let get_page_number_url = |page| {
+        get_search_url(
+            &base_url,
+            ArtSearchParameters {
+                page,
+                cursor: None,
+                ..query_params.clone()
+            },
+        )
+    };
+
     Ok(template_to_response(ArtIndexPage {
         user,
         original_uri,
+        heading,
+        base_url: base_url.clone(),
         user_search_params: &query_params,
 
         random_quote,
@@ -145,49 +324,186 @@ async fn art_index(
         first_page_url: if page_number_to_show <= 2 {
             None
         } else {
-            Some(get_search_url(ArtSearchParameters {
-                page: 1,
-                ..query_params.clone()
-            }))
+            Some(get_page_number_url(1))
         },
-        prev_page_url: if page_number_to_show == 1 {
+        prev_page_url: if !art_page.has_previous_page {
             None
+        } else if page_number_to_show <= 2 || art_page.previous_page_cursor.is_none() {
+            Some(get_page_number_url(page_number_to_show - 1))
         } else {
-            Some(get_search_url(ArtSearchParameters {
-                page: page_number_to_show - 1,
-                ..query_params.clone()
-            }))
+            Some(get_search_url(
+                &base_url,
+                ArtSearchParameters {
+                    cursor: art_page.previous_page_cursor,
+                    ..query_params.clone()
+                },
+            ))
         },
-        next_page_url: if page_number_to_show >= total_pages_available_for_search {
+        next_page_url: if !art_page.has_next_page {
             None
+        } else if art_page.next_page_cursor.is_none() {
+            Some(get_page_number_url(page_number_to_show.saturating_add(1)))
         } else {
-            Some(get_search_url(ArtSearchParameters {
-                page: page_number_to_show + 1,
-                ..query_params.clone()
-            }))
+            Some(get_search_url(
+                &base_url,
+                ArtSearchParameters {
+                    cursor: art_page.next_page_cursor,
+                    ..query_params.clone()
+                },
+            ))
         },
         last_page_url: if page_number_to_show >= total_pages_available_for_search - 1 {
             None
         } else {
-            Some(get_search_url(ArtSearchParameters {
-                page: total_pages_available_for_search,
-                ..query_params.clone()
-            }))
+            Some(get_page_number_url(total_pages_available_for_search))
         },
 
         show_upload_button,
+        user_can_post_directly,
 
         all_tags: get_all_tags(&db_connection).await,
 
-        art_pieces,
+        art_pieces: art_page.art_pieces,
+        blurred_art_ids,
+        hidden_by_filters_count,
     }))
 }
 
-/// Returns the total amount of art currently in the db. May be given tags to constrain the search
+/// A page of art for galleries outside the art index (like on character and artist pages).
+pub struct ArtGalleryPage {
+    pub art_pieces: Vec<BaseArt>,
+    pub page_number: i64,
+    pub total_pages: i64,
+}
+
+/// Links to the other pages of an art gallery. Only the ones worth showing are Some, same as on the art index.
+pub struct ArtGalleryPageUrls {
+    pub first: Option<String>,
+    pub prev: Option<String>,
+    pub next: Option<String>,
+    pub last: Option<String>,
+}
+
+impl ArtGalleryPage {
+    /// Returns the links to the other pages of this gallery, on the page at the given path.
+    pub fn get_page_urls(&self, current_path: &str) -> ArtGalleryPageUrls {
+        // Jump straight back down to the gallery when changing pages.
+        let get_page_number_url =
+            |page_number: i64| format!("{current_path}?art_page={page_number}#art");
+
+        ArtGalleryPageUrls {
+            first: (self.page_number > 2).then(|| get_page_number_url(1)),
+            prev: (self.page_number > 1).then(|| get_page_number_url(self.page_number - 1)),
+            next: (self.page_number < self.total_pages)
+                .then(|| get_page_number_url(self.page_number + 1)),
+            last: (self.page_number < self.total_pages - 1)
+                .then(|| get_page_number_url(self.total_pages)),
+        }
+    }
+}
+
+/// Gets a page of the public, SFW art with the given tag, newest first.
+/// Page numbers past the end are clamped to the last page.
+pub async fn get_tagged_art_page(
+    state: &ServerState,
+    db_connection: &Object<Manager>,
+    tag: &str,
+    page_number: i64,
+    amount_per_page: i64,
+) -> ArtGalleryPage {
+    let search_params = ArtSearchParameters {
+        tags: vec![tag_query::TagQueryTerm::Include(tag.to_string())],
+        ..Default::default()
+    };
+
+    get_art_gallery_page(
+        state,
+        db_connection,
+        &search_params,
+        page_number,
+        amount_per_page,
+    )
+    .await
+}
+
+/// Gets a page of the public, SFW art the given artist worked on, newest first.
+/// Page numbers past the end are clamped to the last page.
+pub async fn get_artist_art_page(
+    state: &ServerState,
+    db_connection: &Object<Manager>,
+    artist_name: &str,
+    page_number: i64,
+    amount_per_page: i64,
+) -> ArtGalleryPage {
+    let search_params = ArtSearchParameters {
+        artist: Some(artist_name.to_string()),
+        ..Default::default()
+    };
+
+    get_art_gallery_page(
+        state,
+        db_connection,
+        &search_params,
+        page_number,
+        amount_per_page,
+    )
+    .await
+}
+
+async fn get_art_gallery_page(
+    state: &ServerState,
+    db_connection: &Object<Manager>,
+    search_params: &ArtSearchParameters,
+    page_number: i64,
+    amount_per_page: i64,
+) -> ArtGalleryPage {
+    let total_amount_of_art = get_total_amount_of_art(state, db_connection, search_params)
+        .await
+        .unwrap_or_default();
+
+    let total_pages = get_gallery_page_count(total_amount_of_art, amount_per_page);
+    let page_number = page_number.clamp(1, total_pages);
+
+    let art_page =
+        BaseArt::get_art_page(db_connection, amount_per_page, page_number, search_params).await;
+
+    ArtGalleryPage {
+        art_pieces: art_page.art_pieces,
+        page_number,
+        // The count is cached, so it might be a bit behind. Don't let it claim we're past the last page.
+        total_pages: cmp::max(
+            total_pages,
+            page_number.saturating_add(i64::from(art_page.has_next_page)),
+        ),
+    }
+}
+
+/// Total / per_page, rounded up. Always at least one page, even if it's empty.
+fn get_gallery_page_count(total_amount_of_art: i64, amount_per_page: i64) -> i64 {
+    cmp::max(
+        1,
+        (total_amount_of_art + amount_per_page - 1) / amount_per_page,
+    )
+}
+
+/// Returns the total amount of art currently in the db. May be given tags to constrain the search.
+/// Cached for a bit, so it may be slightly out of date.
 pub async fn get_total_amount_of_art(
+    state: &ServerState,
     db_connection: &Object<Manager>,
     search_params: &ArtSearchParameters,
 ) -> Result<i64, Box<dyn std::error::Error>> {
+    let cache_key = search_params.get_count_cache_key();
+
+    // Someone's favorites change every time they click a button, and they'd notice the count lagging behind. Don't cache those.
+    let use_cache = search_params.favorited_by.is_none();
+
+    if use_cache {
+        if let Some(cached_count) = state.art_count_cache.get(&cache_key) {
+            return Ok(cached_count);
+        }
+    }
+
     let mut query_params: Vec<&(dyn tokio_postgres::types::ToSql + Sync)> = Vec::new();
 
     let query_where = search_params.get_postgres_where(&mut query_params);
@@ -198,34 +514,51 @@ pub async fn get_total_amount_of_art(
     let row = db_connection.query_one(&query, &query_params).await?;
 
     let count: i64 = row.get(0);
+
+    if use_cache {
+        state.art_count_cache.insert(cache_key, count);
+    }
+
     Ok(count)
 }
 
-/// Given relevant query parameters, returns the relative URL of that art search.
-fn get_search_url(params: ArtSearchParameters) -> String {
-    format!("/art{}", params.to_uri_parameters(true))
+/// Given the page being searched and relevant query parameters, returns the relative URL of that art search.
+fn get_search_url(base_url: &str, params: ArtSearchParameters) -> String {
+    format!("{base_url}{}", params.to_uri_parameters(true))
 }
 
-/// Returns all the unique tags in all art.
+/// Returns all the unique tags in all public art, alongside their category and how many pieces use them, grouped by category.
 // TODO: Should probably cache this. Not a frequently changing field, and even if it does, a short discrepancy is ok.
-pub async fn get_all_tags(db_connection: &Object<Manager>) -> Vec<String> {
+pub async fn get_all_tags(db_connection: &Object<Manager>) -> Vec<tags::Tag> {
+    // Tags that somehow aren't in the registry are just treated as general tags.
     let answers = db_connection
-        .query("SELECT DISTINCT unnest(tags) AS tags FROM art;", &[])
+        .query(
+            "SELECT used_tags.name, used_tags.usage_count, COALESCE(tag.category, 'general') AS category, tag.description
+            FROM (SELECT art_tag AS name, COUNT(*) AS usage_count FROM art, unnest(tags) AS art_tag WHERE post_state = 'public' GROUP BY art_tag) AS used_tags
+            LEFT JOIN tag ON tag.name = used_tags.name
+            ORDER BY category, used_tags.name;",
+            &[],
+        )
         .await
         .unwrap();
 
     answers
         .iter()
-        .map(|row| row.get(0))
-        .collect::<Vec<String>>()
+        .map(|row| tags::Tag {
+            name: row.get("name"),
+            category: row.get("category"),
+            description: row.get("description"),
+            usage_count: row.get("usage_count"),
+        })
+        .collect()
 }
 
-/// Returns all the unique artists in all art.
+/// Returns all the unique artists in all public art.
 // TODO: Should probably cache this. Not a frequently changing field, and even if it does, a short discrepancy is ok.
 pub async fn get_all_artists(db_connection: &Object<Manager>) -> Vec<String> {
     let answers = db_connection
         .query(
-            "SELECT DISTINCT unnest(creators) AS creators FROM art;",
+            "SELECT DISTINCT unnest(creators) AS creators FROM art WHERE post_state = 'public';",
             &[],
         )
         .await
@@ -236,3 +569,78 @@ pub async fn get_all_artists(db_connection: &Object<Manager>) -> Vec<String> {
         .map(|row| row.get(0))
         .collect::<Vec<String>>()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn gallery_page(page_number: i64, total_pages: i64) -> ArtGalleryPage {
+        ArtGalleryPage {
+            art_pieces: Vec::new(),
+            page_number,
+            total_pages,
+        }
+    }
+
+    #[test]
+    fn gallery_page_count_rounds_up() {
+        let cases = [(0, 1), (1, 1), (12, 1), (13, 2), (24, 2), (25, 3)];
+
+        for (total_amount_of_art, expected) in cases {
+            assert_eq!(
+                get_gallery_page_count(total_amount_of_art, 12),
+                expected,
+                "{total_amount_of_art}"
+            );
+        }
+    }
+
+    #[test]
+    fn gallery_links_point_back_to_the_gallery() {
+        let page_urls = gallery_page(3, 5).get_page_urls("/characters/kate");
+
+        assert_eq!(
+            page_urls.first.as_deref(),
+            Some("/characters/kate?art_page=1#art")
+        );
+        assert_eq!(
+            page_urls.prev.as_deref(),
+            Some("/characters/kate?art_page=2#art")
+        );
+        assert_eq!(
+            page_urls.next.as_deref(),
+            Some("/characters/kate?art_page=4#art")
+        );
+        assert_eq!(
+            page_urls.last.as_deref(),
+            Some("/characters/kate?art_page=5#art")
+        );
+    }
+
+    #[test]
+    fn gallery_links_skip_redundant_pages() {
+        // (page, total pages, which of first/prev/next/last show up)
+        let cases = [
+            (1, 1, [false, false, false, false]),
+            (1, 2, [false, false, true, false]),
+            (2, 2, [false, true, false, false]),
+            (1, 5, [false, false, true, true]),
+            (2, 5, [false, true, true, true]),
+            (4, 5, [true, true, true, false]),
+            (5, 5, [true, true, false, false]),
+        ];
+
+        for (page_number, total_pages, expected) in cases {
+            let page_urls =
+                gallery_page(page_number, total_pages).get_page_urls("/characters/kate");
+            let shown = [
+                page_urls.first.is_some(),
+                page_urls.prev.is_some(),
+                page_urls.next.is_some(),
+                page_urls.last.is_some(),
+            ];
+
+            assert_eq!(shown, expected, "page {page_number} of {total_pages}");
+        }
+    }
+}
@@ -0,0 +1,590 @@
+//! Collections are named, ordered groups of art posts. Mostly for comics and series that span more than one post,
+//! so readers can go through them in order instead of relying on the global older/newer links.
+
+use super::structs::BaseArt;
+use crate::{
+    nsfw_splash,
+    user::{User, UsermadePost},
+    utils::{self, template_to_response},
+    RootErrors, ServerState,
+};
+use askama::Template;
+use axum::{
+    extract::{OriginalUri, Path, State},
+    response::{IntoResponse, Redirect, Response},
+    Json,
+};
+use comrak::markdown_to_html;
+use deadpool::managed::Object;
+use deadpool_postgres::{Manager, Transaction};
+use http::{StatusCode, Uri};
+use postgres::Row;
+use serde::Deserialize;
+
+#[derive(Debug, Clone)]
+pub struct ArtCollection {
+    pub id: i32,
+    pub slug: String,
+    pub title: String,
+    pub description: Option<String>, // Assumed to be markdown.
+    pub owner_id: Option<i32>,       // None means a deleted user.
+    /// The slugs of the public art in the collection, in order.
+    pub art_slugs: Vec<String>,
+}
+
+/// Where a given art piece sits in a collection, for the "part N of M" navigation on the art page.
+#[derive(Debug, Clone)]
+pub struct CollectionNavigation {
+    pub title: String,
+    pub slug: String,
+    pub part_number: usize,
+    pub total_parts: usize,
+    pub previous_art_slug: Option<String>,
+    pub next_art_slug: Option<String>,
+}
+
+/// Selects collections alongside their member slugs. Needs a WHERE (if any) and then `GROUP BY art_collection.id` after it.
+const COLLECTION_SELECT_QUERY: &str = "SELECT art_collection.id, art_collection.slug, art_collection.title,
+        art_collection.description, art_collection.owner_id,
+        COALESCE(
+            array_agg(art.page_slug ORDER BY art_collection_member.internal_order) FILTER (WHERE art.id IS NOT NULL),
+            '{}'
+        ) AS art_slugs
+    FROM art_collection
+    LEFT JOIN art_collection_member ON art_collection_member.collection_id = art_collection.id
+    LEFT JOIN art ON art.id = art_collection_member.art_id AND art.post_state = 'public'";
+
+impl ArtCollection {
+    pub async fn get_by_slug(db_connection: &Object<Manager>, slug: &str) -> Option<Self> {
+        let requested_collection = db_connection
+            .query_opt(
+                &format!(
+                    "{COLLECTION_SELECT_QUERY} WHERE art_collection.slug=$1 GROUP BY art_collection.id"
+                ),
+                &[&slug],
+            )
+            .await
+            .ok()??;
+
+        Some(Self::from_db_row(&requested_collection))
+    }
+
+    /// Returns every collection the given art piece is in, sorted by title.
+    pub async fn get_containing_art(db_connection: &Object<Manager>, art_id: i32) -> Vec<Self> {
+        db_connection
+            .query(
+                &format!(
+                    "{COLLECTION_SELECT_QUERY}
+                    WHERE art_collection.id IN (SELECT collection_id FROM art_collection_member WHERE art_id=$1)
+                    GROUP BY art_collection.id
+                    ORDER BY art_collection.title"
+                ),
+                &[&art_id],
+            )
+            .await
+            .unwrap_or_default()
+            .iter()
+            .map(Self::from_db_row)
+            .collect()
+    }
+
+    /// Converts a DB row from COLLECTION_SELECT_QUERY to an ArtCollection struct.
+    fn from_db_row(row: &Row) -> Self {
+        Self {
+            id: row.get("id"),
+            slug: row.get("slug"),
+            title: row.get("title"),
+            description: row.get("description"),
+            owner_id: row.get("owner_id"),
+            art_slugs: row.get("art_slugs"),
+        }
+    }
+
+    /// Returns where the given art sits in this collection. None if it isn't in it (or isn't public).
+    pub fn get_navigation_for(&self, art_slug: &str) -> Option<CollectionNavigation> {
+        let index = self.art_slugs.iter().position(|slug| slug == art_slug)?;
+
+        Some(CollectionNavigation {
+            title: self.title.clone(),
+            slug: self.slug.clone(),
+            part_number: index + 1,
+            total_parts: self.art_slugs.len(),
+            previous_art_slug: index
+                .checked_sub(1)
+                .and_then(|previous_index| self.art_slugs.get(previous_index))
+                .cloned(),
+            next_art_slug: self.art_slugs.get(index + 1).cloned(),
+        })
+    }
+}
+
+impl UsermadePost for ArtCollection {
+    fn can_be_modified_by(&self, user: &User) -> bool {
+        user.user_type.permissions().can_modify_others_content
+            || self.owner_id.is_some_and(|owner_id| owner_id == user.id)
+    }
+}
+
+#[derive(Debug, Template)]
+#[template(path = "art/collection.html")]
+struct CollectionPage {
+    user: Option<User>,
+    original_uri: Uri,
+
+    title: String,
+    description: Option<String>, // Rendered from markdown.
+    owner: Option<User>,
+    art_pieces: Vec<BaseArt>,
+}
+
+pub async fn collection_page(
+    Path(collection_slug): Path<String>,
+    State(state): State<ServerState>,
+    OriginalUri(original_uri): OriginalUri,
+    cookie_jar: tower_cookies::Cookies,
+) -> Result<Response, RootErrors> {
+    let db_connection = state
+        .db_pool
+        .get()
+        .await
+        .map_err(|_| RootErrors::InternalServerError)?;
+    let user = User::get_from_cookie_jar(&db_connection, &cookie_jar).await;
+
+    let requested_collection =
+        match ArtCollection::get_by_slug(&db_connection, &collection_slug).await {
+            Some(collection) => collection,
+            None => return Err(RootErrors::NotFound(original_uri, cookie_jar, user)),
+        };
+
+    let art_pieces = BaseArt::get_in_collection(&db_connection, requested_collection.id).await;
+
+    // If anything in here is spicy, make sure we allow them to see it.
+    if art_pieces.iter().any(|art| art.is_nsfw) {
+        if let Some(nsfw_splash) =
+            nsfw_splash::get_if_user_hasnt_enabled_nsfw(&user, &original_uri, &cookie_jar)
+        {
+            return Ok(nsfw_splash);
+        }
+    }
+
+    let owner = match requested_collection.owner_id {
+        Some(owner_id) => User::get_by_id(&db_connection, &owner_id).await,
+        None => None,
+    };
+
+    Ok(template_to_response(CollectionPage {
+        user,
+        original_uri,
+
+        title: requested_collection.title,
+        description: requested_collection
+            .description
+            .map(|description| markdown_to_html(&description, &comrak::Options::default())),
+        owner,
+        art_pieces,
+    }))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct NewCollectionRequest {
+    slug: String,
+    title: String,
+    #[serde(default)]
+    description: Option<String>,
+    /// The slugs of the art in the collection, in order.
+    art: Vec<String>,
+}
+
+/// Creates a new collection from the given art. Only for users that can post art.
+pub async fn create_collection(
+    State(state): State<ServerState>,
+    cookie_jar: tower_cookies::Cookies,
+    Json(mut new_collection): Json<NewCollectionRequest>,
+) -> Result<Response, RootErrors> {
+    let mut db_connection = state
+        .db_pool
+        .get()
+        .await
+        .map_err(|_| RootErrors::InternalServerError)?;
+
+    let requesting_user = match User::get_from_cookie_jar(&db_connection, &cookie_jar).await {
+        Some(user) => user,
+        None => return Err(RootErrors::Unauthorized),
+    };
+
+    if !requesting_user.user_type.permissions().can_post_art {
+        return Err(RootErrors::Forbidden);
+    }
+
+    new_collection.slug = new_collection.slug.trim().to_lowercase();
+    new_collection.title = new_collection.title.trim().to_string();
+    new_collection.description = new_collection
+        .description
+        .map(|description| description.trim().to_string())
+        .filter(|description| !description.is_empty());
+
+    if !utils::is_valid_slug(&new_collection.slug) {
+        return Err(RootErrors::BadRequest("Given invalid slug. Slugs must be made of either lowercase letters or numbers, and may include hyphens or underscores in the middle.".to_string()));
+    }
+
+    if new_collection.title.is_empty() {
+        return Err(RootErrors::BadRequest(
+            "Title mustn't be empty.".to_string(),
+        ));
+    }
+
+    if ArtCollection::get_by_slug(&db_connection, &new_collection.slug)
+        .await
+        .is_some()
+    {
+        return Err(RootErrors::BadRequest(format!(
+            "The slug {} already exists.",
+            new_collection.slug
+        )));
+    }
+
+    let art_ids = get_member_art_ids(&db_connection, &new_collection.art)
+        .await
+        .map_err(RootErrors::BadRequest)?;
+
+    let sql_transaction = db_connection.transaction().await.map_err(|err| {
+        eprintln!("[COLLECTION CREATION] Errored trying to create an SQL Transaction! {err:?}");
+        RootErrors::InternalServerError
+    })?;
+
+    let collection_id: i32 = sql_transaction
+        .query_one(
+            "INSERT INTO art_collection (slug, title, description, owner_id) VALUES ($1,$2,$3,$4) RETURNING id",
+            &[
+                &new_collection.slug,
+                &new_collection.title,
+                &new_collection.description,
+                &requesting_user.id,
+            ],
+        )
+        .await
+        .map_err(|err| {
+            eprintln!(
+                "[COLLECTION CREATION] Inserting collection {} failed. {err:?}",
+                new_collection.slug
+            );
+            RootErrors::InternalServerError
+        })?
+        .get("id");
+
+    set_collection_members(&sql_transaction, collection_id, &art_ids)
+        .await
+        .map_err(|err| {
+            eprintln!(
+                "[COLLECTION CREATION] Inserting members of collection {} failed. {err:?}",
+                new_collection.slug
+            );
+            RootErrors::InternalServerError
+        })?;
+
+    sql_transaction.commit().await.map_err(|err| {
+        eprintln!("[COLLECTION CREATION] Errored trying to run SQL Transaction! {err:?}");
+        RootErrors::InternalServerError
+    })?;
+
+    println!(
+        "[COLLECTION CREATION] User {} (ID:{}) created collection {} (ID:{}, SLUG:{}) with {} art pieces.",
+        requesting_user.display_name,
+        requesting_user.id,
+        new_collection.title,
+        collection_id,
+        new_collection.slug,
+        art_ids.len()
+    );
+
+    Ok(Redirect::to(&format!("/art/collections/{}", new_collection.slug)).into_response())
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CollectionOrderRequest {
+    /// The slugs of the art in the collection, in the new order.
+    /// Anything left out is removed from the collection, anything new is added.
+    art: Vec<String>,
+}
+
+/// Replaces the art in a collection with the given list, in the given order.
+pub async fn reorder_collection(
+    Path(collection_slug): Path<String>,
+    State(state): State<ServerState>,
+    OriginalUri(original_uri): OriginalUri,
+    cookie_jar: tower_cookies::Cookies,
+    Json(order_request): Json<CollectionOrderRequest>,
+) -> Result<Response, RootErrors> {
+    let mut db_connection = state
+        .db_pool
+        .get()
+        .await
+        .map_err(|_| RootErrors::InternalServerError)?;
+
+    let requesting_user = match User::get_from_cookie_jar(&db_connection, &cookie_jar).await {
+        Some(user) => user,
+        None => return Err(RootErrors::Unauthorized),
+    };
+
+    let requested_collection =
+        match ArtCollection::get_by_slug(&db_connection, &collection_slug).await {
+            Some(collection) => collection,
+            None => {
+                return Err(RootErrors::NotFound(
+                    original_uri,
+                    cookie_jar,
+                    Some(requesting_user),
+                ))
+            }
+        };
+
+    if !requesting_user.user_type.permissions().can_post_art
+        || !requested_collection.can_be_modified_by(&requesting_user)
+    {
+        return Err(RootErrors::Forbidden);
+    }
+
+    let art_ids = get_member_art_ids(&db_connection, &order_request.art)
+        .await
+        .map_err(RootErrors::BadRequest)?;
+
+    let sql_transaction = db_connection.transaction().await.map_err(|err| {
+        eprintln!("[COLLECTION REORDER] Errored trying to create an SQL Transaction! {err:?}");
+        RootErrors::InternalServerError
+    })?;
+
+    sql_transaction
+        .execute(
+            "DELETE FROM art_collection_member WHERE collection_id=$1",
+            &[&requested_collection.id],
+        )
+        .await
+        .map_err(|err| {
+            eprintln!(
+                "[COLLECTION REORDER] Clearing members of collection ID {} failed. {err:?}",
+                requested_collection.id
+            );
+            RootErrors::InternalServerError
+        })?;
+
+    set_collection_members(&sql_transaction, requested_collection.id, &art_ids)
+        .await
+        .map_err(|err| {
+            eprintln!(
+                "[COLLECTION REORDER] Inserting members of collection ID {} failed. {err:?}",
+                requested_collection.id
+            );
+            RootErrors::InternalServerError
+        })?;
+
+    // Bumps last_modified_date, so there's a record of when the order changed.
+    sql_transaction
+        .execute(
+            "UPDATE art_collection SET title=title WHERE id=$1",
+            &[&requested_collection.id],
+        )
+        .await
+        .map_err(|err| {
+            eprintln!(
+                "[COLLECTION REORDER] Updating collection ID {} failed. {err:?}",
+                requested_collection.id
+            );
+            RootErrors::InternalServerError
+        })?;
+
+    sql_transaction.commit().await.map_err(|err| {
+        eprintln!("[COLLECTION REORDER] Errored trying to run SQL Transaction! {err:?}");
+        RootErrors::InternalServerError
+    })?;
+
+    println!(
+        "[COLLECTION REORDER] User {} (ID:{}) set collection {} (ID:{}) to {} art pieces.",
+        requesting_user.display_name,
+        requesting_user.id,
+        requested_collection.slug,
+        requested_collection.id,
+        art_ids.len()
+    );
+
+    Ok((StatusCode::OK, "").into_response())
+}
+
+/// Given the slugs of art to put in a collection, returns their IDs in the same order.
+/// If the list doesn't make sense (empty, duplicates, art that doesn't exist), returns a readable explanation why.
+async fn get_member_art_ids(
+    db_connection: &Object<Manager>,
+    art_slugs: &[String],
+) -> Result<Vec<i32>, String> {
+    let art_slugs: Vec<String> = art_slugs
+        .iter()
+        .map(|slug| slug.trim().to_lowercase())
+        .collect();
+
+    if art_slugs.is_empty() {
+        return Err("A collection needs at least one art piece.".to_string());
+    }
+
+    if let Some((index, duplicate_slug)) = art_slugs
+        .iter()
+        .enumerate()
+        .find(|(index, slug)| art_slugs[..*index].contains(slug))
+    {
+        return Err(format!(
+            "{duplicate_slug} is in the collection more than once (again at position {}).",
+            index + 1
+        ));
+    }
+
+    let found_art: Vec<(String, i32)> = db_connection
+        .query(
+            "SELECT page_slug, id FROM art WHERE page_slug = ANY($1)",
+            &[&art_slugs],
+        )
+        .await
+        .map_err(|err| {
+            eprintln!("[COLLECTION MEMBERS] Failed getting art IDs for {art_slugs:?}! {err:?}");
+            "Failed looking up the given art.".to_string()
+        })?
+        .iter()
+        .map(|row| (row.get("page_slug"), row.get("id")))
+        .collect();
+
+    art_slugs
+        .iter()
+        .map(|slug| {
+            found_art
+                .iter()
+                .find(|(found_slug, _)| found_slug == slug)
+                .map(|(_, id)| *id)
+                .ok_or(format!("There's no art with the slug {slug}."))
+        })
+        .collect()
+}
+
+/// Puts the given art into the collection, in the order given. Assumes the collection has no members yet.
+async fn set_collection_members(
+    sql_transaction: &Transaction<'_>,
+    collection_id: i32,
+    art_ids: &[i32],
+) -> Result<(), tokio_postgres::Error> {
+    sql_transaction
+        .execute(
+            "INSERT INTO art_collection_member (collection_id, art_id, internal_order)
+            SELECT $1, member.art_id, member.internal_order::int
+            FROM unnest($2::int[]) WITH ORDINALITY AS member(art_id, internal_order)",
+            &[&collection_id, &art_ids],
+        )
+        .await?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::user::UserType;
+    use chrono::Utc;
+
+    fn collection(art_slugs: &[&str], owner_id: Option<i32>) -> ArtCollection {
+        ArtCollection {
+            id: 1,
+            slug: "the-comic".to_string(),
+            title: "The Comic".to_string(),
+            description: None,
+            owner_id,
+            art_slugs: art_slugs.iter().map(|slug| slug.to_string()).collect(),
+        }
+    }
+
+    fn user(id: i32, user_type: UserType) -> User {
+        User {
+            id,
+            user_type,
+            display_name: "someone".to_string(),
+            profile_pic_s3_key: None,
+            last_modified: Utc::now(),
+            creator_name: None,
+            creation_time: Utc::now(),
+            content_filters: Default::default(),
+        }
+    }
+
+    /// (part number, total parts, previous slug, next slug), so the cases fit on a line each.
+    fn navigation_of(
+        collection: &ArtCollection,
+        art_slug: &str,
+    ) -> Option<(usize, usize, Option<String>, Option<String>)> {
+        collection.get_navigation_for(art_slug).map(|navigation| {
+            (
+                navigation.part_number,
+                navigation.total_parts,
+                navigation.previous_art_slug,
+                navigation.next_art_slug,
+            )
+        })
+    }
+
+    #[test]
+    fn navigation_links_to_neighbouring_parts() {
+        let comic = collection(&["one", "two", "three"], Some(1));
+        let some = |slug: &str| Some(slug.to_string());
+
+        assert_eq!(
+            navigation_of(&comic, "one"),
+            Some((1, 3, None, some("two")))
+        );
+        assert_eq!(
+            navigation_of(&comic, "two"),
+            Some((2, 3, some("one"), some("three")))
+        );
+        assert_eq!(
+            navigation_of(&comic, "three"),
+            Some((3, 3, some("two"), None))
+        );
+    }
+
+    #[test]
+    fn navigation_keeps_the_collection_info() {
+        let navigation = collection(&["one"], Some(1))
+            .get_navigation_for("one")
+            .unwrap();
+
+        assert_eq!(navigation.title, "The Comic");
+        assert_eq!(navigation.slug, "the-comic");
+    }
+
+    #[test]
+    fn single_part_collections_have_no_neighbours() {
+        let comic = collection(&["only"], Some(1));
+
+        assert_eq!(navigation_of(&comic, "only"), Some((1, 1, None, None)));
+    }
+
+    #[test]
+    fn art_outside_the_collection_has_no_navigation() {
+        assert_eq!(
+            navigation_of(&collection(&["one", "two"], Some(1)), "three"),
+            None
+        );
+        assert_eq!(navigation_of(&collection(&[], Some(1)), "one"), None);
+    }
+
+    #[test]
+    fn owners_and_admins_can_modify_collections() {
+        let owned = collection(&["one"], Some(1));
+
+        assert!(owned.can_be_modified_by(&user(1, UserType::Uploader)));
+        assert!(owned.can_be_modified_by(&user(1, UserType::Normal)));
+        assert!(!owned.can_be_modified_by(&user(2, UserType::Uploader)));
+        assert!(owned.can_be_modified_by(&user(2, UserType::Admin)));
+        assert!(owned.can_be_modified_by(&user(2, UserType::Superadmin)));
+    }
+
+    #[test]
+    fn collections_of_deleted_users_are_admin_only() {
+        let orphaned = collection(&["one"], None);
+
+        assert!(!orphaned.can_be_modified_by(&user(1, UserType::Uploader)));
+        assert!(orphaned.can_be_modified_by(&user(1, UserType::Admin)));
+    }
+}
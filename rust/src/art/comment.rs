@@ -1,15 +1,36 @@
-use crate::art::structs::BaseArt;
+use crate::art::structs::{BaseArt, Comment};
+use crate::user::UsermadePost;
 use crate::{RootErrors, ServerState, User};
-use axum::extract::{OriginalUri, Path, State};
+use axum::extract::{ConnectInfo, OriginalUri, Path, Query, State};
 use axum::http;
 use axum::response::{IntoResponse, Response};
-use http::StatusCode;
+use chrono::Utc;
+use deadpool::managed::Object;
+use deadpool_postgres::Manager;
+use http::{StatusCode, Uri};
+use serde::Deserialize;
+use std::collections::{HashMap, HashSet};
+use std::net::SocketAddr;
+use std::time::Duration;
+use tower_cookies::Cookies;
+
+/// Anything longer than this is an essay, not a comment.
+const MAX_COMMENT_LENGTH: usize = 5000;
+
+#[derive(Debug, Deserialize)]
+pub struct AddCommentParameters {
+    /// The ID of the comment this is replying to, if any.
+    #[serde(default)]
+    reply_to: Option<i32>,
+}
 
 /// Add comment under a given post.
 #[axum::debug_handler]
 pub async fn add_comment(
     Path(art_slug): Path<String>,
     State(state): State<ServerState>,
+    ConnectInfo(client_address): ConnectInfo<SocketAddr>,
+    Query(comment_parameters): Query<AddCommentParameters>,
     OriginalUri(original_uri): OriginalUri,
     cookie_jar: tower_cookies::Cookies,
     body: String,
@@ -48,14 +69,46 @@ pub async fn add_comment(
         ));
     }
 
+    // If it's a reply, make sure there's something real to reply to.
+    if let Some(reply_to) = comment_parameters.reply_to {
+        let replied_comment_is_valid = Comment::get_by_id(&db_connection, reply_to)
+            .await
+            .is_some_and(|comment| {
+                comment.under_post == requested_post.id && !comment.is_deleted()
+            });
+
+        if !replied_comment_is_valid {
+            return Err(RootErrors::BadRequest(
+                "The comment you're replying to doesn't exist.".to_string(),
+            ));
+        }
+    }
+
+    check_comment_limits(
+        &state,
+        &db_connection,
+        &requesting_user,
+        &client_address,
+        &sanitized_comment,
+    )
+    .await?;
+
     // Lovely! A new comment! Let's post it.
     const POST_COMMENT_QUERY: &str =
-        "INSERT INTO art_comment (under_post, poster_id, contents) VALUES ($1,$2,$3);";
+        "INSERT INTO art_comment (under_post, poster_id, contents, rendered_contents, reply_to) VALUES ($1,$2,$3,$4,$5);";
+
+    let rendered_comment = render_comment_contents(&sanitized_comment);
 
     db_connection
         .execute(
             POST_COMMENT_QUERY,
-            &[&requested_post.id, &requesting_user.id, &sanitized_comment],
+            &[
+                &requested_post.id,
+                &requesting_user.id,
+                &sanitized_comment,
+                &rendered_comment,
+                &comment_parameters.reply_to,
+            ],
         )
         .await
         .map_err(|err| {
@@ -66,17 +119,336 @@ pub async fn add_comment(
             RootErrors::InternalServerError
         })?;
 
+    state.comment_user_limiter.record(requesting_user.id);
+    state.comment_ip_limiter.record(client_address.ip());
+
     Ok((StatusCode::CREATED, "").into_response())
 }
 
+/// Makes sure the user is allowed to post this comment right now, and isn't just flooding the comment section.
+async fn check_comment_limits(
+    state: &ServerState,
+    db_connection: &Object<Manager>,
+    requesting_user: &User,
+    client_address: &SocketAddr,
+    sanitized_comment: &str,
+) -> Result<(), RootErrors> {
+    let comment_limits = &state.config.comment_limits;
+
+    // Fresh accounts are the favourite tool of spammers. Make them wait a bit.
+    let account_age = (Utc::now() - requesting_user.creation_time)
+        .to_std()
+        .unwrap_or(Duration::ZERO);
+    if account_age < comment_limits.minimum_account_age {
+        return Err(RootErrors::TooManyRequests(
+            comment_limits.minimum_account_age - account_age,
+            "Your account is too new to post comments. Give it a few minutes.".to_string(),
+        ));
+    }
+
+    let time_until_allowed = [
+        state
+            .comment_user_limiter
+            .time_until_allowed(&requesting_user.id),
+        state
+            .comment_ip_limiter
+            .time_until_allowed(&client_address.ip()),
+    ]
+    .into_iter()
+    .flatten()
+    .max();
+
+    if let Some(time_until_allowed) = time_until_allowed {
+        eprintln!(
+            "[POST ART COMMENT] User {} (ID:{}) from {} hit the comment rate limit.",
+            requesting_user.display_name,
+            requesting_user.id,
+            client_address.ip()
+        );
+        return Err(RootErrors::TooManyRequests(
+            time_until_allowed,
+            "You're posting comments too fast. Slow down a bit.".to_string(),
+        ));
+    }
+
+    const DUPLICATE_COMMENT_QUERY: &str = "SELECT EXISTS (SELECT 1 FROM art_comment
+        WHERE poster_id=$1 AND contents=$2 AND posting_time > NOW() - make_interval(secs => $3))";
+
+    let is_duplicate: bool = db_connection
+        .query_one(
+            DUPLICATE_COMMENT_QUERY,
+            &[
+                &requesting_user.id,
+                &sanitized_comment,
+                &comment_limits.duplicate_comment_window.as_secs_f64(),
+            ],
+        )
+        .await
+        .map_err(|err| {
+            eprintln!(
+                "[POST ART COMMENT] Checking for duplicate comments by user ID {} failed. {:?}",
+                requesting_user.id, err
+            );
+            RootErrors::InternalServerError
+        })?
+        .get(0);
+
+    if is_duplicate {
+        return Err(RootErrors::BadRequest(
+            "You just posted that exact comment. Once is enough.".to_string(),
+        ));
+    }
+
+    Ok(())
+}
+
+/// Edit the contents of an existing comment.
+pub async fn edit_comment(
+    Path((art_slug, comment_id)): Path<(String, i32)>,
+    State(state): State<ServerState>,
+    OriginalUri(original_uri): OriginalUri,
+    cookie_jar: tower_cookies::Cookies,
+    body: String,
+) -> Result<Response, RootErrors> {
+    let db_connection = state
+        .db_pool
+        .get()
+        .await
+        .map_err(|_| RootErrors::InternalServerError)?;
+
+    let (requesting_user, requested_comment) = get_modifiable_comment(
+        &db_connection,
+        &art_slug,
+        comment_id,
+        original_uri,
+        cookie_jar,
+    )
+    .await?;
+
+    let sanitized_comment = sanitize_comment_content(&body);
+
+    if !comment_content_is_valid(&sanitized_comment) {
+        return Err(RootErrors::BadRequest(
+            "Content of comment is invalid.".to_string(),
+        ));
+    }
+
+    const EDIT_COMMENT_QUERY: &str =
+        "UPDATE art_comment SET contents=$1, rendered_contents=$2, edited_time=NOW() WHERE id=$3;";
+
+    let rendered_comment = render_comment_contents(&sanitized_comment);
+
+    db_connection
+        .execute(
+            EDIT_COMMENT_QUERY,
+            &[&sanitized_comment, &rendered_comment, &requested_comment.id],
+        )
+        .await
+        .map_err(|err| {
+            eprintln!(
+                "[EDIT ART COMMENT] User {} (ID:{}) editing comment ID {} failed. {:?}",
+                requesting_user.display_name, requesting_user.id, requested_comment.id, err
+            );
+            RootErrors::InternalServerError
+        })?;
+
+    Ok((StatusCode::OK, "").into_response())
+}
+
+/// Soft-deletes a comment. Its contents are gone, but any replies to it stay where they are.
+pub async fn delete_comment(
+    Path((art_slug, comment_id)): Path<(String, i32)>,
+    State(state): State<ServerState>,
+    OriginalUri(original_uri): OriginalUri,
+    cookie_jar: tower_cookies::Cookies,
+) -> Result<Response, RootErrors> {
+    let db_connection = state
+        .db_pool
+        .get()
+        .await
+        .map_err(|_| RootErrors::InternalServerError)?;
+
+    let (requesting_user, requested_comment) = get_modifiable_comment(
+        &db_connection,
+        &art_slug,
+        comment_id,
+        original_uri,
+        cookie_jar,
+    )
+    .await?;
+
+    const DELETE_COMMENT_QUERY: &str =
+        "UPDATE art_comment SET contents=NULL, rendered_contents=NULL, deleted_time=NOW() WHERE id=$1;";
+
+    db_connection
+        .execute(DELETE_COMMENT_QUERY, &[&requested_comment.id])
+        .await
+        .map_err(|err| {
+            eprintln!(
+                "[DELETE ART COMMENT] User {} (ID:{}) deleting comment ID {} failed. {:?}",
+                requesting_user.display_name, requesting_user.id, requested_comment.id, err
+            );
+            RootErrors::InternalServerError
+        })?;
+
+    println!(
+        "[DELETE ART COMMENT] User {} (ID:{}) deleted comment ID {} on art {}",
+        requesting_user.display_name, requesting_user.id, requested_comment.id, art_slug
+    );
+
+    Ok(StatusCode::NO_CONTENT.into_response())
+}
+
+/// Gets the requesting user and the comment they want to change, making sure they're allowed to.
+/// A comment that's already deleted can't be touched anymore.
+async fn get_modifiable_comment(
+    db_connection: &deadpool::managed::Object<deadpool_postgres::Manager>,
+    art_slug: &str,
+    comment_id: i32,
+    original_uri: Uri,
+    cookie_jar: Cookies,
+) -> Result<(User, Comment), RootErrors> {
+    let requesting_user = match User::get_from_cookie_jar(db_connection, &cookie_jar).await {
+        Some(user) => user,
+        None => return Err(RootErrors::Unauthorized),
+    };
+
+    let requested_post = BaseArt::get_by_slug(db_connection, art_slug).await;
+    let requested_comment = Comment::get_by_id(db_connection, comment_id).await;
+
+    // If the comment isn't under the post in the URL, as far as we're concerned it doesn't exist.
+    let requested_comment = match (requested_post, requested_comment) {
+        (Some(post), Some(comment)) if comment.under_post == post.id && !comment.is_deleted() => {
+            comment
+        }
+        _ => {
+            return Err(RootErrors::NotFound(
+                original_uri,
+                cookie_jar,
+                Some(requesting_user),
+            ))
+        }
+    };
+
+    if !requested_comment.can_be_modified_by(&requesting_user) {
+        return Err(RootErrors::Forbidden);
+    }
+
+    Ok((requesting_user, requested_comment))
+}
+
 /// Given the textual content of a given comment, cleans up anything that may cause issues for the code.
+/// NOTE: This is the markdown source, not what's shown to users. See [render_comment_contents] for that.
 fn sanitize_comment_content(original_comment: &str) -> String {
-    // TODO: Sanitize it better, this is super minimal.
-    original_comment.trim().to_string()
+    // Windows line endings would count double against the length limit for no reason.
+    original_comment.trim().replace("\r\n", "\n")
 }
 
 /// Given the textual content of a given comment, returns whether it has anything contentwise that may cause problems.
 /// Probably best not to look for specific words, cunthrope problem and all.
 fn comment_content_is_valid(comment: &str) -> bool {
-    !comment.is_empty()
+    !comment.is_empty() && comment.chars().count() <= MAX_COMMENT_LENGTH
+}
+
+/// Turns the markdown a user wrote in a comment into HTML that's safe to put on the page as-is.
+/// Supports bold, italics, strikethrough, links, quotes, code, lists and ||spoilers||. Anything else gets stripped.
+pub fn render_comment_contents(comment: &str) -> String {
+    let mut markdown_options = comrak::Options::default();
+    markdown_options.extension.strikethrough = true;
+    markdown_options.extension.autolink = true;
+    markdown_options.extension.spoiler = true;
+    markdown_options.render.hardbreaks = true; // People writing comments expect a newline to be a newline.
+    markdown_options.render.escape = true; // Raw HTML shows up as text, rather than vanishing without explanation.
+
+    let unsafe_comment = comrak::markdown_to_html(comment, &markdown_options);
+
+    // Comrak shouldn't produce anything dangerous with these settings, but it's user input, so the allow-list
+    // has the final say. Notably no images, we don't want people hotlinking stuff into the comments.
+    let mut ammonia_settings = ammonia::Builder::empty();
+    ammonia_settings
+        .add_tags([
+            "p",
+            "br",
+            "strong",
+            "em",
+            "del",
+            "a",
+            "blockquote",
+            "code",
+            "pre",
+            "ul",
+            "ol",
+            "li",
+            "span",
+        ])
+        .tag_attributes(HashMap::from([("a", HashSet::from(["href"]))]))
+        .allowed_classes(HashMap::from([("span", HashSet::from(["spoiler"]))]))
+        .url_schemes(HashSet::from(["http", "https", "mailto"]))
+        .link_rel(Some("nofollow ugc"));
+
+    ammonia_settings.clean(&unsafe_comment).to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn markdown_is_rendered() {
+        let rendered = render_comment_contents("**bold** *italics* ~~struck~~ `code` ||spoiler||");
+
+        assert!(rendered.contains("<strong>bold</strong>"));
+        assert!(rendered.contains("<em>italics</em>"));
+        assert!(rendered.contains("<del>struck</del>"));
+        assert!(rendered.contains("<code>code</code>"));
+        assert!(rendered.contains(r#"<span class="spoiler">spoiler</span>"#));
+    }
+
+    #[test]
+    fn newlines_are_kept() {
+        assert!(render_comment_contents("first\nsecond").contains("first<br>"));
+    }
+
+    #[test]
+    fn links_get_nofollow_and_bad_schemes_are_dropped() {
+        let rendered =
+            render_comment_contents("[site](https://example.com) https://autolinked.example");
+        assert!(rendered.contains(r#"<a href="https://example.com" rel="nofollow ugc">site</a>"#));
+        assert!(rendered.contains(r#"href="https://autolinked.example""#));
+
+        let rendered = render_comment_contents("[click me](javascript:alert(1))");
+        assert!(!rendered.contains("javascript:"));
+        assert!(rendered.contains("click me"));
+    }
+
+    #[test]
+    fn raw_html_shows_up_as_text() {
+        let rendered = render_comment_contents(r#"<script>alert(1)</script><b onclick="x">hi</b>"#);
+
+        assert!(!rendered.contains("<script"));
+        assert!(!rendered.contains("<b"));
+        assert!(rendered.contains("&lt;script&gt;"));
+    }
+
+    #[test]
+    fn images_are_not_allowed() {
+        let rendered = render_comment_contents("![hotlinked](https://example.com/huge.png)");
+
+        assert!(!rendered.contains("<img"));
+    }
+
+    #[test]
+    fn contents_are_cleaned_up_before_checking_length() {
+        assert_eq!(sanitize_comment_content("  hi\r\nthere \n"), "hi\nthere");
+
+        assert!(!comment_content_is_valid(&sanitize_comment_content(
+            " \r\n "
+        )));
+        assert!(comment_content_is_valid(&"a".repeat(MAX_COMMENT_LENGTH)));
+        assert!(!comment_content_is_valid(
+            &"a".repeat(MAX_COMMENT_LENGTH + 1)
+        ));
+        // Counted in characters, not bytes.
+        assert!(comment_content_is_valid(&"é".repeat(MAX_COMMENT_LENGTH)));
+    }
 }
@@ -0,0 +1,124 @@
+use postgres_types::{FromSql, ToSql};
+use serde::{Deserialize, Deserializer};
+
+/// Stuff in art that people might want a heads up about. Separate from NSFW, which has its own splash and its own half of the archive.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, FromSql, ToSql, Deserialize)]
+#[postgres(name = "content_warning", rename_all = "snake_case")]
+#[serde(rename_all = "snake_case")]
+pub enum ContentWarning {
+    Gore,
+    FlashingImagery,
+    /// Spoilers for lore that isn't out yet.
+    LoreSpoilers,
+}
+
+impl ContentWarning {
+    pub const ALL: [ContentWarning; 3] = [
+        ContentWarning::Gore,
+        ContentWarning::FlashingImagery,
+        ContentWarning::LoreSpoilers,
+    ];
+
+    /// How it's shown to the user.
+    pub fn label(&self) -> &'static str {
+        match self {
+            ContentWarning::Gore => "Gore",
+            ContentWarning::FlashingImagery => "Flashing Imagery",
+            ContentWarning::LoreSpoilers => "Lore Spoilers",
+        }
+    }
+
+    /// How it's written in URLs and forms.
+    pub fn to_uri_value(self) -> &'static str {
+        match self {
+            ContentWarning::Gore => "gore",
+            ContentWarning::FlashingImagery => "flashing_imagery",
+            ContentWarning::LoreSpoilers => "lore_spoilers",
+        }
+    }
+
+    fn from_uri_value(value: &str) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|warning| warning.to_uri_value() == value)
+    }
+}
+
+/// Joins the labels of the given warnings, for showing on overlays.
+pub fn get_warning_labels(content_warnings: &[ContentWarning]) -> String {
+    content_warnings
+        .iter()
+        .map(|warning| warning.label())
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Deserializes a comma separated list of warnings from a URL. Anything that isn't a warning is ignored.
+pub fn deserialize_content_warnings<'de, D>(
+    deserializer: D,
+) -> Result<Vec<ContentWarning>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let s = String::deserialize(deserializer)?;
+
+    let mut content_warnings: Vec<ContentWarning> = Vec::new();
+
+    for warning in s.split(',').filter_map(ContentWarning::from_uri_value) {
+        if !content_warnings.contains(&warning) {
+            content_warnings.push(warning);
+        }
+    }
+
+    Ok(content_warnings)
+}
+
+/// Writes the given warnings as a comma separated list, for URLs.
+pub fn to_uri_list(content_warnings: &[ContentWarning]) -> String {
+    content_warnings
+        .iter()
+        .map(|warning| warning.to_uri_value())
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::de::{value::StrDeserializer, IntoDeserializer};
+
+    fn warnings_from_uri_list(list: &str) -> Vec<ContentWarning> {
+        let deserializer: StrDeserializer<serde::de::value::Error> = list.into_deserializer();
+        deserialize_content_warnings(deserializer).expect("a string should always deserialize")
+    }
+
+    #[test]
+    fn uri_lists_round_trip() {
+        for warnings in [
+            Vec::new(),
+            vec![ContentWarning::Gore],
+            ContentWarning::ALL.to_vec(),
+        ] {
+            assert_eq!(warnings_from_uri_list(&to_uri_list(&warnings)), warnings);
+        }
+    }
+
+    #[test]
+    fn unknown_and_repeated_warnings_are_dropped() {
+        assert_eq!(
+            warnings_from_uri_list("gore,nonsense,,gore,Lore_Spoilers,lore_spoilers"),
+            vec![ContentWarning::Gore, ContentWarning::LoreSpoilers]
+        );
+    }
+
+    #[test]
+    fn labels_are_joined_in_order() {
+        assert_eq!(
+            get_warning_labels(&[
+                ContentWarning::LoreSpoilers,
+                ContentWarning::FlashingImagery
+            ]),
+            "Lore Spoilers, Flashing Imagery"
+        );
+    }
+}
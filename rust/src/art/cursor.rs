@@ -0,0 +1,232 @@
+//! Cursors for keyset pagination on the art index.
+//! Instead of telling the DB to skip N rows (which it has to actually walk over), we tell it
+//! "give me whatever comes after this specific art piece", which the index on (creation_date, page_slug) makes cheap.
+
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use chrono::NaiveDate;
+use serde::{Deserialize, Deserializer};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CursorDirection {
+    /// The art that comes after the cursor, in the usual order. AKA older art, AKA the next page.
+    After,
+    /// The art that comes before the cursor. AKA newer art, AKA the previous page.
+    Before,
+}
+
+/// Points at a specific art piece in the index, and which direction from it we want to go.
+/// Passed around in the URL as an opaque token, see [ArtCursor::to_token].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ArtCursor {
+    pub direction: CursorDirection,
+    pub creation_date: NaiveDate,
+    pub page_slug: String,
+    /// Which page number the cursor leads to. Only for showing the user, the DB doesn't care.
+    pub page_number: i64,
+}
+
+const TOKEN_DELIMITER: char = '|';
+
+/// Nobody's paging through two billion pages of art. Anything past this is someone messing with the token,
+/// and letting it through just means overflowing the page math later.
+pub const MAX_PAGE_NUMBER: i64 = i32::MAX as i64;
+
+impl ArtCursor {
+    /// Turns the cursor into a URL-safe string.
+    /// It's not a secret or anything, just means nobody gets ideas about hand-editing it.
+    pub fn to_token(&self) -> String {
+        let direction = match self.direction {
+            CursorDirection::After => 'a',
+            CursorDirection::Before => 'b',
+        };
+
+        URL_SAFE_NO_PAD.encode(format!(
+            "{direction}{TOKEN_DELIMITER}{}{TOKEN_DELIMITER}{}{TOKEN_DELIMITER}{}",
+            self.page_number, self.creation_date, self.page_slug
+        ))
+    }
+
+    /// Reads a token made by [ArtCursor::to_token]. Returns None if it's garbage.
+    pub fn from_token(token: &str) -> Option<Self> {
+        let decoded = String::from_utf8(URL_SAFE_NO_PAD.decode(token.trim()).ok()?).ok()?;
+
+        // Slug goes last, so even if one somehow had the delimiter in it, splitn keeps it whole.
+        let mut sections = decoded.splitn(4, TOKEN_DELIMITER);
+
+        let direction = match sections.next()? {
+            "a" => CursorDirection::After,
+            "b" => CursorDirection::Before,
+            _ => return None,
+        };
+        let page_number: i64 = sections.next()?.parse().ok()?;
+        let creation_date: NaiveDate = sections.next()?.parse().ok()?;
+        let page_slug = sections.next()?.to_string();
+
+        if !(1..=MAX_PAGE_NUMBER).contains(&page_number) || page_slug.is_empty() {
+            return None;
+        }
+
+        Some(Self {
+            direction,
+            creation_date,
+            page_slug,
+            page_number,
+        })
+    }
+}
+
+/// Deserializes a cursor token. An invalid token is treated as no cursor at all, which just means starting at page 1.
+pub fn deserialize_cursor<'de, D>(deserializer: D) -> Result<Option<ArtCursor>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let s = String::deserialize(deserializer)?;
+
+    Ok(ArtCursor::from_token(&s))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_cursor(direction: CursorDirection, page_slug: &str) -> ArtCursor {
+        ArtCursor {
+            direction,
+            creation_date: NaiveDate::from_ymd_opt(2023, 4, 5).unwrap(),
+            page_slug: page_slug.to_string(),
+            page_number: 3,
+        }
+    }
+
+    /// Encodes a hand-written token body, for making tokens to_token never would.
+    fn raw_token(body: &str) -> String {
+        URL_SAFE_NO_PAD.encode(body)
+    }
+
+    #[test]
+    fn round_trips() {
+        for direction in [CursorDirection::After, CursorDirection::Before] {
+            let cursor = test_cursor(direction, "some-art_piece-2");
+
+            assert_eq!(ArtCursor::from_token(&cursor.to_token()), Some(cursor));
+        }
+    }
+
+    #[test]
+    fn round_trips_slugs_with_the_delimiter() {
+        for page_slug in ["with|pipe", "|", "a||b|", "ends-with|2023-04-05"] {
+            let cursor = test_cursor(CursorDirection::After, page_slug);
+
+            assert_eq!(
+                ArtCursor::from_token(&cursor.to_token()),
+                Some(cursor),
+                "slug {page_slug:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn tokens_are_url_safe() {
+        // Slugs full of stuff that encodes to + and / in normal base64.
+        let token = test_cursor(CursorDirection::Before, "???>>>~~~").to_token();
+
+        assert!(token
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_'));
+    }
+
+    #[test]
+    fn tolerates_surrounding_whitespace() {
+        let cursor = test_cursor(CursorDirection::After, "slug");
+
+        assert_eq!(
+            ArtCursor::from_token(&format!(" {} ", cursor.to_token())),
+            Some(cursor)
+        );
+    }
+
+    #[test]
+    fn rejects_malformed_tokens() {
+        for token in [
+            "",
+            "not base64!",
+            "====",
+            // Valid base64, but not UTF-8.
+            &URL_SAFE_NO_PAD.encode([0xff, 0xfe, 0xfd]),
+            &raw_token("just some text"),
+            &raw_token("a|3|2023-04-05"),
+            &raw_token("a|3|2023-04-05|"),
+            &raw_token("x|3|2023-04-05|slug"),
+            &raw_token("A|3|2023-04-05|slug"),
+            &raw_token("a|0|2023-04-05|slug"),
+            &raw_token("a|-1|2023-04-05|slug"),
+            &raw_token("a|three|2023-04-05|slug"),
+            &raw_token("a|99999999999999999999|2023-04-05|slug"),
+            &raw_token("a|9223372036854775807|2023-04-05|slug"),
+            &raw_token("b|2147483648|2023-04-05|slug"),
+            &raw_token("a|3|2023-13-05|slug"),
+            &raw_token("a|3|yesterday|slug"),
+            &raw_token("a||2023-04-05|slug"),
+            &raw_token("|3|2023-04-05|slug"),
+        ] {
+            assert_eq!(ArtCursor::from_token(token), None, "token {token:?}");
+        }
+    }
+
+    #[test]
+    fn accepts_the_max_page_number() {
+        let cursor = ArtCursor {
+            page_number: MAX_PAGE_NUMBER,
+            ..test_cursor(CursorDirection::After, "slug")
+        };
+
+        assert_eq!(ArtCursor::from_token(&cursor.to_token()), Some(cursor));
+    }
+
+    #[test]
+    fn rejects_tampered_tokens() {
+        let token = test_cursor(CursorDirection::After, "slug").to_token();
+
+        // Characters that aren't in the URL-safe alphabet.
+        for bad_character in ["!", "+", "/", "="] {
+            let mut tampered_token = token.clone();
+            tampered_token.replace_range(0..1, bad_character);
+
+            assert_eq!(ArtCursor::from_token(&tampered_token), None);
+        }
+
+        // Re-encoding a token with its direction swapped for nonsense.
+        let decoded = String::from_utf8(URL_SAFE_NO_PAD.decode(&token).unwrap()).unwrap();
+        assert_eq!(
+            ArtCursor::from_token(&raw_token(&decoded.replacen('a', "z", 1))),
+            None
+        );
+
+        // Chopped off tokens never decode back into the original cursor.
+        for cut_length in 1..token.len() {
+            assert_ne!(
+                ArtCursor::from_token(&token[..cut_length]),
+                ArtCursor::from_token(&token)
+            );
+        }
+
+        // Standard base64 with padding isn't what we hand out.
+        let padded_token = base64::engine::general_purpose::STANDARD.encode("a|3|2023-04-05|s");
+        assert!(padded_token.ends_with('='));
+        assert_eq!(ArtCursor::from_token(&padded_token), None);
+    }
+
+    #[test]
+    fn invalid_tokens_deserialize_to_no_cursor() {
+        use serde::de::{value::StrDeserializer, IntoDeserializer};
+
+        let deserializer: StrDeserializer<serde::de::value::Error> = "garbage".into_deserializer();
+        assert_eq!(deserialize_cursor(deserializer), Ok(None));
+
+        let cursor = test_cursor(CursorDirection::Before, "slug");
+        let token = cursor.to_token();
+        let deserializer: StrDeserializer<serde::de::value::Error> =
+            token.as_str().into_deserializer();
+        assert_eq!(deserialize_cursor(deserializer), Ok(Some(cursor)));
+    }
+}
@@ -0,0 +1,164 @@
+use super::structs::PageArt;
+use crate::{
+    errs::RootErrors,
+    nsfw_splash,
+    user::{User, UsermadePost},
+    utils::{sql::PostState, zip_stream::ZipStreamWriter},
+    ServerState,
+};
+use axum::{
+    extract::{OriginalUri, Path, State},
+    response::{IntoResponse, Response},
+};
+use http::header;
+use std::io;
+
+/// Sends the user a ZIP with every file of the given art, plus an info.txt describing it.
+/// The ZIP is put together while it's being sent, so even huge posts don't sit in memory.
+pub async fn download_art(
+    Path(art_slug): Path<String>,
+    State(state): State<ServerState>,
+    OriginalUri(original_uri): OriginalUri,
+    cookie_jar: tower_cookies::Cookies,
+) -> Result<Response, RootErrors> {
+    let db_connection = state
+        .db_pool
+        .get()
+        .await
+        .map_err(|_| RootErrors::InternalServerError)?;
+
+    let user = User::get_from_cookie_jar(&db_connection, &cookie_jar).await;
+
+    // Same rules as seeing the art page.
+    let Some(requested_art) = PageArt::get_by_slug(&db_connection, &art_slug)
+        .await
+        .filter(|art| {
+            art.post_state == PostState::Public || art.can_optionally_be_modified_by(&user)
+        })
+    else {
+        return Err(RootErrors::NotFound(original_uri, cookie_jar, user));
+    };
+
+    if requested_art.base_art.is_nsfw {
+        if let Some(nsfw_splash) =
+            nsfw_splash::get_if_user_hasnt_enabled_nsfw(&user, &original_uri, &cookie_jar)
+        {
+            return Ok(nsfw_splash);
+        }
+    }
+
+    // Don't need the connection while streaming, give it back to the pool.
+    drop(db_connection);
+
+    // Put together before streaming starts, so nothing in the task can blow up halfway through the archive.
+    let info_text = get_info_text(&requested_art, &crate::askama::WEBSITE_URL);
+
+    let (mut zip_writer, body) = ZipStreamWriter::new(requested_art.creation_date);
+
+    let art_id = requested_art.base_art.id;
+    tokio::spawn(async move {
+        let result = match write_art_zip(&state, &mut zip_writer, &requested_art, info_text).await {
+            Ok(()) => zip_writer.finish().await,
+            Err(err) => {
+                zip_writer.abort(&err.to_string()).await;
+                Err(err)
+            }
+        };
+
+        if let Err(err) = result {
+            // Usually just the user cancelling the download.
+            eprintln!("[ART DOWNLOAD] Streaming the ZIP of art ID {art_id} stopped early: {err:?}");
+        }
+    });
+
+    Ok((
+        [
+            (header::CONTENT_TYPE, "application/zip".to_string()),
+            (
+                header::CONTENT_DISPOSITION,
+                // Slugs are only ever lowercase letters, numbers, dashes and underscores, so no escaping needed.
+                format!("attachment; filename=\"{art_slug}.zip\""),
+            ),
+        ],
+        body,
+    )
+        .into_response())
+}
+
+/// Writes the given info file and every art file into the given ZIP, in order.
+async fn write_art_zip(
+    state: &ServerState,
+    zip_writer: &mut ZipStreamWriter,
+    art: &PageArt,
+    info_text: String,
+) -> Result<(), io::Error> {
+    zip_writer.write_file("info.txt", info_text.into()).await?;
+
+    // Pad the numbers so the files sort properly in file explorers.
+    let number_width = art.art_keys.len().to_string().len();
+
+    for (index, art_key) in art.art_keys.iter().enumerate() {
+        let file_extension = art_key.rsplit_once('.').map_or("bin", |(_, ext)| ext);
+        let file_name = format!(
+            "{}_{:0number_width$}.{file_extension}",
+            art.base_art.slug,
+            index + 1
+        );
+
+        let downloaded_file = match state
+            .s3_client
+            .get_object()
+            .bucket(&state.config.s3_public_bucket)
+            .key(art_key)
+            .send()
+            .await
+        {
+            Ok(downloaded_file) => downloaded_file,
+            Err(err) => {
+                eprintln!("[ART DOWNLOAD] Failed getting {art_key} from S3! {err:?}");
+                return Err(io::Error::other("Failed getting art file"));
+            }
+        };
+
+        // The whole file's needed before it can go in, since its size and CRC come first. Only one at a time though.
+        let file_bytes = match downloaded_file.body.collect().await {
+            Ok(file_bytes) => file_bytes.into_bytes(),
+            Err(err) => {
+                eprintln!("[ART DOWNLOAD] Failed reading {art_key} from S3! {err:?}");
+                return Err(io::Error::other("Failed reading art file"));
+            }
+        };
+
+        zip_writer.write_file(&file_name, file_bytes).await?;
+    }
+
+    Ok(())
+}
+
+/// The contents of the info.txt in the ZIP, so people know where the art came from after it's been sitting in their downloads for a year.
+fn get_info_text(art: &PageArt, website_url: &str) -> String {
+    let mut info_text = format!(
+        "Title: {}\nCreators: {}\nDate: {}\n",
+        art.base_art.title,
+        art.base_art.creators.join(", "),
+        art.creation_date
+    );
+
+    if !art.tags.is_empty() {
+        info_text.push_str(&format!("Tags: {}\n", art.tags.join(", ")));
+    }
+
+    if !art.sources.is_empty() {
+        info_text.push_str(&format!(
+            "Originally Posted At: {}\n",
+            art.sources.join(", ")
+        ));
+    }
+
+    info_text.push_str(&format!(
+        "Source: {website_url}/art/{}\n",
+        art.base_art.slug
+    ));
+
+    info_text
+}
@@ -0,0 +1,67 @@
+use crate::{RootErrors, ServerState};
+use deadpool::managed::Object;
+use deadpool_postgres::Manager;
+
+/// Returns the slugs of public art which has a file that looks like one of the given hashes, alphabetically.
+/// If given an art ID, that art is skipped, so edited art doesn't count as a duplicate of itself.
+async fn find_similar_art(
+    state: &ServerState,
+    db_connection: &Object<Manager>,
+    hashes: &[i64],
+    excluded_art_id: Option<i32>,
+) -> Result<Vec<String>, RootErrors> {
+    if hashes.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let max_distance = state.config.art_duplicate_hash_distance as i64;
+
+    // Hamming distance is just the amount of set bits after XORing the hashes.
+    const SIMILAR_ART_QUERY: &str = "
+        SELECT DISTINCT art.page_slug
+        FROM art_file
+        JOIN art ON art.id = art_file.belongs_to
+        JOIN unnest($1::bigint[]) AS new_file(perceptual_hash)
+            ON bit_count((art_file.perceptual_hash # new_file.perceptual_hash)::bit(64)) <= $2
+        WHERE art.post_state = 'public' AND art.id IS DISTINCT FROM $3
+        ORDER BY art.page_slug";
+
+    Ok(db_connection
+        .query(
+            SIMILAR_ART_QUERY,
+            &[&hashes, &max_distance, &excluded_art_id],
+        )
+        .await
+        .map_err(|err| {
+            eprintln!("[ART DUPLICATE CHECK] Failed looking for similar art! {err:?}");
+            RootErrors::InternalServerError
+        })?
+        .iter()
+        .map(|row| row.get("page_slug"))
+        .collect())
+}
+
+/// Makes sure none of the given file hashes belong to art that's already on the site, unless the user said they don't care.
+/// The hashes come from moving the files into place, see [crate::utils::MovedFile].
+pub async fn check_for_duplicate_art(
+    state: &ServerState,
+    db_connection: &Object<Manager>,
+    hashes: &[i64],
+    excluded_art_id: Option<i32>,
+    ignore_duplicates: bool,
+) -> Result<(), RootErrors> {
+    if ignore_duplicates {
+        return Ok(());
+    }
+
+    let similar_art_slugs = find_similar_art(state, db_connection, hashes, excluded_art_id).await?;
+
+    if !similar_art_slugs.is_empty() {
+        return Err(RootErrors::Conflict(format!(
+            "This looks a lot like art that's already on the site: {}. If it's actually different, you can upload it anyways.",
+            similar_art_slugs.join(", ")
+        )));
+    }
+
+    Ok(())
+}
@@ -0,0 +1,134 @@
+use crate::art::structs::{BaseArt, PageArt};
+use crate::utils::sql::PostState;
+use crate::{RootErrors, ServerState, User};
+use axum::extract::{OriginalUri, Path, State};
+use axum::response::{IntoResponse, Response};
+use axum::Json;
+use http::StatusCode;
+use serde::Serialize;
+
+#[derive(Debug, Serialize)]
+struct FavoriteResponse {
+    is_favorited: bool,
+    favorite_count: i64,
+}
+
+/// Adds the given art to the logged in user's favorites. Does nothing if it's already there.
+pub async fn add_favorite(
+    Path(art_slug): Path<String>,
+    State(state): State<ServerState>,
+    OriginalUri(original_uri): OriginalUri,
+    cookie_jar: tower_cookies::Cookies,
+) -> Result<Response, RootErrors> {
+    set_favorite(state, art_slug, original_uri, cookie_jar, true).await
+}
+
+/// Removes the given art from the logged in user's favorites. Does nothing if it wasn't there.
+pub async fn remove_favorite(
+    Path(art_slug): Path<String>,
+    State(state): State<ServerState>,
+    OriginalUri(original_uri): OriginalUri,
+    cookie_jar: tower_cookies::Cookies,
+) -> Result<Response, RootErrors> {
+    set_favorite(state, art_slug, original_uri, cookie_jar, false).await
+}
+
+async fn set_favorite(
+    state: ServerState,
+    art_slug: String,
+    original_uri: http::Uri,
+    cookie_jar: tower_cookies::Cookies,
+    favorite: bool,
+) -> Result<Response, RootErrors> {
+    let db_connection = state
+        .db_pool
+        .get()
+        .await
+        .map_err(|_| RootErrors::InternalServerError)?;
+
+    let requesting_user = match User::get_from_cookie_jar(&db_connection, &cookie_jar).await {
+        Some(user) => user,
+        None => {
+            return Err(RootErrors::Unauthorized);
+        }
+    };
+
+    let Some(requested_art) = PageArt::get_by_slug(&db_connection, &art_slug)
+        .await
+        .filter(|art| can_set_favorite(&art.post_state, favorite))
+        .map(|art| art.base_art)
+    else {
+        return Err(RootErrors::NotFound(
+            original_uri,
+            cookie_jar,
+            Some(requesting_user),
+        ));
+    };
+
+    // Both are idempotent, so double-clicking doesn't error out or double count.
+    // The insert checks the post state again, in case it changed since we looked.
+    let query = if favorite {
+        "INSERT INTO art_favorite (user_id, art_id) SELECT $1, id FROM art WHERE id=$2 AND post_state='public' ON CONFLICT DO NOTHING"
+    } else {
+        "DELETE FROM art_favorite WHERE user_id=$1 AND art_id=$2"
+    };
+
+    db_connection
+        .execute(query, &[&requesting_user.id, &requested_art.id])
+        .await
+        .map_err(|err| {
+            eprintln!(
+                "[ART FAVORITE] User {} (ID:{}) setting favorite={} on art ID {} failed. {:?}",
+                requesting_user.display_name, requesting_user.id, favorite, requested_art.id, err
+            );
+            RootErrors::InternalServerError
+        })?;
+
+    let favorite_count = BaseArt::get_favorite_count(&db_connection, requested_art.id).await;
+
+    Ok((
+        StatusCode::OK,
+        Json(FavoriteResponse {
+            is_favorited: favorite,
+            favorite_count,
+        }),
+    )
+        .into_response())
+}
+
+/// Whether art in the given state can be (un)favorited.
+/// Only public art can be favorited, no bookmarking stuff that's still in the approval queue.
+/// Unfavoriting works on anything though, so people can clean up after art that got taken down.
+fn can_set_favorite(post_state: &PostState, favorite: bool) -> bool {
+    !favorite || *post_state == PostState::Public
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_public_art_can_be_favorited() {
+        assert!(can_set_favorite(&PostState::Public, true));
+
+        for post_state in [
+            PostState::PendingApproval,
+            PostState::Processing,
+            PostState::Rejected,
+        ] {
+            assert!(!can_set_favorite(&post_state, true), "{post_state:?}");
+        }
+    }
+
+    #[test]
+    fn anything_can_be_unfavorited() {
+        for post_state in [
+            PostState::Public,
+            PostState::PendingApproval,
+            PostState::Processing,
+            PostState::Rejected,
+        ] {
+            assert!(can_set_favorite(&post_state, false), "{post_state:?}");
+        }
+    }
+}
@@ -1,10 +1,11 @@
-use super::{structs, SQL_ORDER_BY_STATEMENT};
+use super::{collections, related, sources, structs};
 use crate::{
-    art::structs::Comment,
+    art::{content_warnings::ContentWarning, structs::Comment},
+    characters::BaseCharacter,
     errs::RootErrors,
     nsfw_splash,
     user::{User, UsermadePost},
-    utils::template_to_response,
+    utils::{file_metadata::FileMetadata, sql::PostState, template_to_response},
     ServerState,
 };
 use askama::Template;
@@ -27,15 +28,37 @@ struct ArtPage<'a> {
 
     // Whether or not the user has the permissions to edit the page.
     user_can_edit_page: bool,
+    /// If the art isn't public, why. Only the submitter and admins get this far with non-public art.
+    review_notice: Option<String>,
 
     title: String,
     artists: Vec<String>,
     formatted_creation_date: String,
     art_urls: Vec<String>,
+    /// Same order as art_urls.
+    art_file_metadata: Vec<FileMetadata>,
+    /// The srcset of each art file, if it has smaller copies. Same order as art_urls.
+    art_srcsets: Vec<Option<String>>,
     tags: Vec<String>,
+    content_warnings: Vec<ContentWarning>,
+    /// Where the art was originally posted.
+    sources: Vec<sources::ArtSource>,
+    /// The warnings the user hasn't opted into, which cover the art until they click through.
+    covering_warnings: Vec<ContentWarning>,
+    /// The characters whose tags this art has.
+    characters: Vec<BaseCharacter>,
+    /// The "More like this" strip.
+    related_art: Vec<structs::BaseArt>,
     description: Option<String>, // Assumed to be markdown.
 
-    comments: Vec<super::structs::Comment>,
+    favorite_count: i64,
+    /// Whether the logged in user has this art in their favorites. Always false if nobody's logged in.
+    user_has_favorited: bool,
+
+    /// "Part N of M" for every collection this art is in.
+    collection_navigation: Vec<collections::CollectionNavigation>,
+
+    comments: Vec<DisplayedComment>,
 
     // The image shown in discord embeds.
     embed_image_url: String,
@@ -44,6 +67,13 @@ struct ArtPage<'a> {
     older_art_url: Option<String>,
 }
 
+/// A comment, alongside everything needed to show it on the art page.
+#[derive(Debug)]
+struct DisplayedComment {
+    comment: Comment,
+    user_can_modify: bool,
+}
+
 impl<'a> ArtPage<'a> {
     /// Given a URL, returns true if it's one that should be wrapped in a <video> tag.
     /// Assumes the URL has a file extension. If not, this breaks.
@@ -52,19 +82,41 @@ impl<'a> ArtPage<'a> {
             .iter()
             .any(|ext| url.ends_with(ext))
     }
+
+    /// The width and height of the art file at the given index, if we know them.
+    /// Lets the browser save space for the art before it loads, so the page doesn't jump around.
+    fn get_file_dimensions(&self, index: &usize) -> Option<(i32, i32)> {
+        let file_metadata = self.art_file_metadata.get(*index)?;
+
+        Some((file_metadata.width?, file_metadata.height?))
+    }
+
+    /// The srcset of the art file at the given index, if it has one.
+    fn get_file_srcset(&self, index: &usize) -> Option<&str> {
+        self.art_srcsets.get(*index)?.as_deref()
+    }
 }
 
 pub async fn art_page(
     Path(art_slug): Path<String>,
     State(state): State<ServerState>,
-    Query(query_params): Query<structs::ArtSearchParameters>,
+    Query(mut query_params): Query<structs::ArtSearchParameters>,
     OriginalUri(original_uri): OriginalUri,
     cookie_jar: tower_cookies::Cookies,
 ) -> Result<Response, RootErrors> {
     let db_connection = state.db_pool.get().await.unwrap();
     let user = User::get_from_cookie_jar(&db_connection, &cookie_jar).await;
 
-    if let Some(requested_art) = structs::PageArt::get_by_slug(&db_connection, &art_slug).await {
+    query_params.resolve_tag_aliases(&db_connection).await;
+    query_params.ensure_random_seed();
+
+    if let Some(requested_art) = structs::PageArt::get_by_slug(&db_connection, &art_slug)
+        .await
+        // Art that isn't public yet (or won't ever be) is only for the people who can do something about it.
+        .filter(|art| {
+            art.post_state == PostState::Public || art.can_optionally_be_modified_by(&user)
+        })
+    {
         // If the user is looking for something spicy, make sure we allow them to.
         if requested_art.base_art.is_nsfw {
             if let Some(nsfw_splash) =
@@ -74,27 +126,97 @@ pub async fn art_page(
             }
         }
 
+        // The arrows skip over anything the user blacklisted, even if they'd rather have it blurred in the index.
+        let neighbour_search_params = {
+            let mut neighbour_search_params = query_params.clone();
+            neighbour_search_params.exclude_blacklisted_art(&user);
+            neighbour_search_params
+        };
+
         let (older_art_url, newer_art_url) =
-            get_older_and_newer_art_slugs(&art_slug, &query_params, &db_connection).await;
+            get_older_and_newer_art_slugs(&art_slug, &neighbour_search_params, &db_connection)
+                .await;
         let art_urls = requested_art.get_art_urls();
+        let art_srcsets = (0..art_urls.len())
+            .map(|index| requested_art.get_srcset(index))
+            .collect();
 
         let user_can_edit_page: bool = user
             .as_ref()
             .is_some_and(|user| requested_art.can_be_modified_by(user));
 
+        let collection_navigation = collections::ArtCollection::get_containing_art(
+            &db_connection,
+            requested_art.base_art.id,
+        )
+        .await
+        .iter()
+        .filter_map(|collection| collection.get_navigation_for(&art_slug))
+        .collect();
+
+        let user_has_favorited = match &user {
+            Some(user) => {
+                structs::BaseArt::is_favorited_by(
+                    &db_connection,
+                    requested_art.base_art.id,
+                    user.id,
+                )
+                .await
+            }
+            None => false,
+        };
+
+        let characters = BaseCharacter::get_by_tags(&db_connection, &requested_art.tags).await;
+
+        let mut related_art = related::get_related_art(
+            &state,
+            &db_connection,
+            requested_art.base_art.id,
+            nsfw_splash::user_has_enabled_nsfw(&user, &cookie_jar),
+        )
+        .await;
+
+        // The strip is cached for everyone, so the user's own filters go on top. No point recommending blurred art.
+        if let Some(user) = &user {
+            let filtered_art_ids = structs::BaseArt::get_ids_caught_by_filters(
+                &db_connection,
+                &related_art.iter().map(|art| art.id).collect::<Vec<_>>(),
+                &user.content_filters,
+            )
+            .await;
+
+            related_art.retain(|art| !filtered_art_ids.contains(&art.id));
+        }
+
+        let covering_warnings = requested_art.base_art.get_unaccepted_warnings(&user);
+
+        // Embeds can't be clicked through, so warned art gets the logo instead of its thumbnail.
+        let embed_image_url = if requested_art.base_art.content_warnings.is_empty() {
+            requested_art.base_art.get_thumbnail_url()
+        } else {
+            format!(
+                "{}/static/img/pd_logo_with_stroke.png",
+                *crate::askama::WEBSITE_URL
+            )
+        };
+
+        let review_notice = requested_art
+            .post_state
+            .get_review_notice(&requested_art.rejection_reason);
+
         let markdownified_description = requested_art
             .description
             .map(|f| markdown_to_html(&f, &comrak::Options::default()));
 
-        let comments_with_sanitized_contents = requested_art
+        let displayed_comments = requested_art
             .comments
             .into_iter()
-            .map(|comment| {
-                // TODO: SANITIZE!!
-                Comment {
-                    contents: markdown_to_html(&comment.contents, &comrak::Options::default()),
-                    ..comment
-                }
+            .map(|comment| DisplayedComment {
+                user_can_modify: !comment.is_deleted()
+                    && user
+                        .as_ref()
+                        .is_some_and(|user| comment.can_be_modified_by(user)),
+                comment,
             })
             .collect();
 
@@ -103,18 +225,35 @@ pub async fn art_page(
             original_uri,
             user_search_params: &query_params,
 
-            embed_image_url: requested_art.base_art.get_thumbnail_url(),
+            embed_image_url,
 
             user_can_edit_page,
+            review_notice,
 
             title: requested_art.base_art.title,
             artists: requested_art.base_art.creators,
             formatted_creation_date: requested_art.creation_date.to_string(),
             art_urls,
+            art_file_metadata: requested_art.art_file_metadata,
+            art_srcsets,
             tags: requested_art.tags,
+            content_warnings: requested_art.base_art.content_warnings,
+            sources: requested_art
+                .sources
+                .iter()
+                .map(|source| sources::ArtSource::new(source))
+                .collect(),
+            covering_warnings,
+            characters,
+            related_art,
             description: markdownified_description,
 
-            comments: comments_with_sanitized_contents,
+            favorite_count: requested_art.favorite_count,
+            user_has_favorited,
+
+            collection_navigation,
+
+            comments: displayed_comments,
 
             older_art_url,
             newer_art_url,
@@ -133,7 +272,9 @@ async fn get_older_and_newer_art_slugs(
 ) -> (Option<String>, Option<String>) {
     let mut sql_params: Vec<&(dyn ToSql + Sync)> = vec![&slug];
 
-    // This query uses LAG and LEAD to get the previous and next page slugs, ordered by creation date first, and the page slug second.
+    let query_order_by = params.get_postgres_order_by(&mut sql_params);
+
+    // This query uses LAG and LEAD to get the previous and next page slugs, in the same order the index shows them.
     let query = format!(
         r#"SELECT
             previous_slug,
@@ -141,8 +282,8 @@ async fn get_older_and_newer_art_slugs(
         FROM (
             SELECT
                 page_slug,
-                LEAD(page_slug) OVER ({SQL_ORDER_BY_STATEMENT}) AS previous_slug,
-                LAG(page_slug) OVER ({SQL_ORDER_BY_STATEMENT}) AS next_slug
+                LEAD(page_slug) OVER ({query_order_by}) AS previous_slug,
+                LAG(page_slug) OVER ({query_order_by}) AS next_slug
             FROM art
             {}
         ) AS pages_with_navigation
@@ -205,8 +346,9 @@ pub async fn delete_art_page(
     // The request is valid? Lovely! Let's start nuking stuff. First of all, take aim at the S3 bucket.
     let s3_client = state.s3_client.clone();
 
-    // Get all of the art, and the thumbnail.
+    // Get all of the art, their smaller copies, and the thumbnail.
     let mut files_to_delete = requested_art.art_keys.clone();
+    files_to_delete.extend(requested_art.get_variant_keys());
     files_to_delete.push(requested_art.base_art.thumbnail_key.clone());
 
     crate::utils::delete_keys_from_s3(&s3_client, &state.config.s3_public_bucket, &files_to_delete)
@@ -225,6 +367,10 @@ pub async fn delete_art_page(
         .await
         .unwrap();
 
+    state.art_count_cache.clear();
+    // It might be in any number of other art's strips, and a dead link there is worse than a recalculation.
+    state.related_art_cache.clear();
+
     println!(
         "[ART UPLOAD] User {} (ID:{}) DELETED art {} (ID:{}, SLUG:{})",
         requesting_user.display_name,
@@ -1,18 +1,24 @@
+use crate::art::content_warnings::ContentWarning;
 use crate::art::get_all_artists;
 use crate::art::structs::{BaseArt, PageArt};
+use crate::art::{duplicates, sources, tags, variants};
 use crate::user::{User, UsermadePost};
-use crate::utils::{self, template_to_response, PostingSteps};
+use crate::utils::{self, file_metadata::FileMetadata, template_to_response, PostingSteps};
 use crate::{errs::RootErrors, ServerState};
 use askama::Template;
 use axum::extract::{OriginalUri, Path, State};
 use axum::response::{IntoResponse, Redirect, Response};
 use axum::{http, Json};
+use deadpool::managed::Object;
+use deadpool_postgres::Manager;
 use http::Uri;
+use serde::Deserialize;
+use std::collections::HashMap;
 use tokio::task::JoinSet;
 use utils::sql::PostState;
 
 const INSERT_INTO_ART_FILE_DB_QUERY: &str =
-    "INSERT INTO art_file (belongs_to,internal_order,s3_key) VALUES ($1,$2,$3)";
+    "INSERT INTO art_file (belongs_to,internal_order,s3_key,perceptual_hash,mime_type,byte_size,width,height,duration_seconds) VALUES ($1,$2,$3,$4,$5,$6,$7,$8,$9)";
 const DELETE_FROM_ART_FILE_DB_QUERY: &str =
     "DELETE FROM art_file WHERE belongs_to=$1 AND internal_order=$2";
 
@@ -21,14 +27,26 @@ const ART_THUMBNAIL_COMPRESSION_SETTINGS: utils::file_compression::LossyCompress
         max_width: Some(180),
         max_height: Some(150),
         quality: 60,
+        keep_animation: true,
     };
 
+/// What the client sends over when uploading or editing art.
+#[derive(Debug, Deserialize)]
+pub struct ArtUploadRequest {
+    #[serde(flatten)]
+    page_art: PageArt,
+
+    /// Set once the user confirmed their art isn't a duplicate of something already on the site.
+    #[serde(default)]
+    ignore_duplicates: bool,
+}
+
 /// Post Request Handler for art category.
 #[axum::debug_handler]
 pub async fn add_art(
     State(state): State<ServerState>,
     cookie_jar: tower_cookies::Cookies,
-    Json(posting_step): Json<PostingSteps<PageArt>>,
+    Json(posting_step): Json<PostingSteps<ArtUploadRequest>>,
 ) -> Result<Response, RootErrors> {
     let db_connection = state
         .db_pool
@@ -42,17 +60,20 @@ pub async fn add_art(
         None => return Err(RootErrors::Unauthorized),
     };
 
-    if !requesting_user.user_type.permissions().can_post_art {
-        return Err(RootErrors::Forbidden);
-    }
+    // Anyone logged in can submit art, but only some people's art goes up without an admin looking at it first.
+    let final_post_state =
+        PostState::after_upload(requesting_user.user_type.permissions().can_post_art);
 
     match posting_step {
         PostingSteps::RequestPresignedURLs { file_amount } => {
             give_user_presigned_s3_urls(file_amount, &state).await
         }
-        PostingSteps::UploadMetadata(mut page_art) => {
+        PostingSteps::UploadMetadata(ArtUploadRequest {
+            mut page_art,
+            ignore_duplicates,
+        }) => {
             // Let's fix up some values that the user may have passed incorrectly.
-            sanitize_recieved_page_art(&mut page_art, &state);
+            sanitize_recieved_page_art(&mut page_art, &state, &db_connection).await;
 
             // Now, let's make sure what we were given is even logical
             if let Err(err_explanation) = validate_recieved_page_art(&page_art) {
@@ -102,6 +123,14 @@ pub async fn add_art(
             columns.push("is_nsfw".into());
             values.push(&page_art.base_art.is_nsfw);
 
+            columns.push("content_warnings".into());
+            values.push(&page_art.base_art.content_warnings);
+
+            if !page_art.sources.is_empty() {
+                columns.push("sources".into());
+                values.push(&page_art.sources);
+            }
+
             let uploading_user = User::get_from_cookie_jar(&db_connection, &cookie_jar).await;
 
             if let Some(uploading_user) = &uploading_user {
@@ -217,7 +246,7 @@ pub async fn add_art(
                         s3_key.split_terminator("/").last().unwrap()
                     );
 
-                    let final_file_key = utils::move_temp_s3_file(
+                    let moved_file = utils::move_temp_s3_file(
                         &s3_client,
                         &config,
                         &s3_key,
@@ -225,25 +254,32 @@ pub async fn add_art(
                         &file_key,
                     )
                     .await
-                    .map_err(|err| format!("{err:?}"))?;
-
-                    let values: Vec<&(dyn tokio_postgres::types::ToSql + Sync)> =
-                        vec![&art_id, &index, &final_file_key];
-
-                    db_connection
-                        .execute(INSERT_INTO_ART_FILE_DB_QUERY, &values)
-                        .await
-                        .map_err(|err| format!("{err:?}"))?;
+                    .map_err(|err| (format!("{err:?}"), None))?;
+
+                    let insertion_result = insert_art_file(
+                        &db_connection,
+                        art_id,
+                        index,
+                        &moved_file.key,
+                        moved_file.perceptual_hash,
+                        &moved_file.metadata,
+                    )
+                    .await;
 
-                    Ok(final_file_key)
+                    // Hand back the key even if the record failed, so it can be cleaned up with the rest.
+                    match insertion_result {
+                        Ok(_) => Ok(moved_file),
+                        Err(err) => Err((format!("{err:?}"), Some(moved_file.key))),
+                    }
                 });
             }
 
             // Now collect everything that ran async, make sure nothing fucked up.
-            let art_upload_results: Vec<Result<String, String>> = art_upload_tasks.join_all().await;
+            let art_upload_results: Vec<Result<utils::MovedFile, (String, Option<String>)>> =
+                art_upload_tasks.join_all().await;
 
             // Let's get all the errors and the results
-            let (final_art_keys, failed_upload_errs) = art_upload_results.into_iter().fold(
+            let (moved_files, failed_upload_errs) = art_upload_results.into_iter().fold(
                 (Vec::new(), Vec::new()),
                 |(mut oks, mut errs), result| {
                     match result {
@@ -254,34 +290,78 @@ pub async fn add_art(
                 },
             );
 
+            // Everything we put on the bucket, in case we need to take it all back down.
+            let mut uploaded_keys: Vec<String> = moved_files
+                .iter()
+                .map(|moved_file| moved_file.key.clone())
+                .chain(failed_upload_errs.iter().filter_map(|(_, key)| key.clone()))
+                .collect();
+            uploaded_keys.push(thumbnail_key);
+
             if !failed_upload_errs.is_empty() {
-                // TODO: Handle if part of this method fails. It's already a fail-method, so what then?
+                discard_processing_art(&state, &db_connection, art_id, &uploaded_keys).await;
 
-                let _ = utils::delete_keys_from_s3(
-                    &state.s3_client.clone(),
-                    &state.config.s3_public_bucket,
-                    &final_art_keys,
-                )
-                .await;
+                eprintln!(
+                    "[ART POST] Failed to move files from temp to permanent location! [{}]",
+                    failed_upload_errs
+                        .into_iter()
+                        .map(|(err, _)| err)
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                );
 
-                let _ = db_connection
-                    .execute("DELETE FROM art WHERE id=$1", &[&art_id])
-                    .await;
+                return Err(RootErrors::InternalServerError);
+            }
+
+            // The archival project keeps re-importing the same pictures, so make sure this isn't one of them.
+            // The files are already moved by now, but the temp ones stick around, so trying again with the override still works.
+            if let Err(err) = duplicates::check_for_duplicate_art(
+                &state,
+                &db_connection,
+                &moved_files
+                    .iter()
+                    .filter_map(|moved_file| moved_file.perceptual_hash)
+                    .collect::<Vec<_>>(),
+                Some(art_id),
+                ignore_duplicates,
+            )
+            .await
+            {
+                discard_processing_art(&state, &db_connection, art_id, &uploaded_keys).await;
+                return Err(err);
+            }
+
+            if ignore_duplicates {
+                println!(
+                    "[ART UPLOAD] User {} (ID:{}) skipped the duplicate check for art {}",
+                    requesting_user.display_name, requesting_user.id, page_art.base_art.slug
+                );
+            }
 
+            // Any tags we haven't seen before go into the registry, so they can be categorized later.
+            // Only now that the art's definitely going up, so rejected uploads don't leave their tags behind.
+            if let Err(err) = tags::register_tags(&db_connection, &page_art.tags).await {
                 eprintln!(
-                    "[ART POST] Failed to move files from temp to permanent location! [{}]",
-                    failed_upload_errs.join(", ")
+                    "[TAG REGISTRATION] Failed registering tags {:?}! {err:?}",
+                    page_art.tags
                 );
 
+                discard_processing_art(&state, &db_connection, art_id, &uploaded_keys).await;
                 return Err(RootErrors::InternalServerError);
             }
 
+            // ---- The files are staying, so make their smaller copies. The page falls back to the originals until they're done. ----
+
+            for moved_file in &moved_files {
+                variants::spawn_art_file_variants(&state, art_id, moved_file);
+            }
+
             // ---- Now that we finished, set the appropriate art state. ----
 
             if let Err(err) = db_connection
                 .execute(
                     "UPDATE art SET post_state=$1 WHERE id=$2",
-                    &[&PostState::Public, &art_id],
+                    &[&final_post_state, &art_id],
                 )
                 .await
             {
@@ -298,14 +378,18 @@ pub async fn add_art(
             };
 
             println!(
-                "[ART UPLOAD] User {} (ID:{}) uploaded art {} (ID:{}, SLUG:{})",
+                "[ART UPLOAD] User {} (ID:{}) uploaded art {} (ID:{}, SLUG:{}) as {:?}",
                 requesting_user.display_name,
                 requesting_user.id,
                 page_art.base_art.title,
                 art_id,
-                page_art.base_art.slug
+                page_art.base_art.slug,
+                final_post_state
             );
 
+            // Someone uploading art should see it counted right away.
+            state.art_count_cache.clear();
+
             Ok(Redirect::to(&format!("/art/{}", page_art.base_art.slug)).into_response())
         }
     }
@@ -350,7 +434,7 @@ pub async fn edit_art_put_request(
     State(state): State<ServerState>,
     OriginalUri(original_uri): OriginalUri,
     cookie_jar: tower_cookies::Cookies,
-    Json(posting_step): Json<PostingSteps<PageArt>>,
+    Json(posting_step): Json<PostingSteps<ArtUploadRequest>>,
 ) -> Result<Response, RootErrors> {
     let db_connection = state
         .db_pool
@@ -384,9 +468,12 @@ pub async fn edit_art_put_request(
         PostingSteps::RequestPresignedURLs { file_amount } => {
             give_user_presigned_s3_urls(file_amount, &state).await
         }
-        PostingSteps::UploadMetadata(mut sent_page_art) => {
+        PostingSteps::UploadMetadata(ArtUploadRequest {
+            page_art: mut sent_page_art,
+            ignore_duplicates,
+        }) => {
             // Let's fix up some values that the user may have passed incorrectly.
-            sanitize_recieved_page_art(&mut sent_page_art, &state);
+            sanitize_recieved_page_art(&mut sent_page_art, &state, &db_connection).await;
 
             // Now let's make sure what we were given is even logical
             if let Err(err_explanation) = validate_recieved_page_art(&sent_page_art) {
@@ -395,6 +482,93 @@ pub async fn edit_art_put_request(
 
             // TODO: Check validity of art URLs. Don't move them yet, just ensure the user isn't fucking with us.
 
+            let s3_client = state.s3_client.clone();
+            let target_s3_folder = format!("art/{}", existing_art.base_art.id);
+
+            // Move the newly added files into place before touching anything else, so they can be checked for duplicates.
+            // If anything's wrong with them, the art is left exactly how it was.
+            let mut new_art_files: HashMap<String, utils::MovedFile> = HashMap::new();
+
+            for art_key in sent_page_art
+                .art_keys
+                .iter()
+                .filter(|key| !existing_art.art_keys.contains(key))
+            {
+                let target_file_key = format!(
+                    "{target_s3_folder}/{}",
+                    art_key.split_terminator("/").last().unwrap()
+                );
+
+                match utils::move_temp_s3_file(
+                    &s3_client,
+                    &state.config,
+                    art_key,
+                    &state.config.s3_public_bucket,
+                    &target_file_key,
+                )
+                .await
+                {
+                    Ok(moved_file) => {
+                        new_art_files.insert(art_key.clone(), moved_file);
+                    }
+                    Err(err) => {
+                        eprintln!(
+                            "[MODIFY ART] Failed moving new art for \"{}\", id:{}. Err:{:?}",
+                            &existing_art.base_art.title, &existing_art.base_art.id, err
+                        );
+
+                        discard_moved_art_files(&state, &new_art_files).await;
+                        return Err(RootErrors::InternalServerError);
+                    }
+                }
+            }
+
+            // Only the newly added files need checking, the rest were already checked when they got uploaded.
+            if let Err(err) = duplicates::check_for_duplicate_art(
+                &state,
+                &db_connection,
+                &new_art_files
+                    .values()
+                    .filter_map(|moved_file| moved_file.perceptual_hash)
+                    .collect::<Vec<_>>(),
+                Some(existing_art.base_art.id),
+                ignore_duplicates,
+            )
+            .await
+            {
+                discard_moved_art_files(&state, &new_art_files).await;
+                return Err(err);
+            }
+
+            // Any tags we haven't seen before go into the registry, so they can be categorized later.
+            if let Err(err) = tags::register_tags(&db_connection, &sent_page_art.tags).await {
+                eprintln!(
+                    "[TAG REGISTRATION] Failed registering tags {:?}! {err:?}",
+                    sent_page_art.tags
+                );
+
+                discard_moved_art_files(&state, &new_art_files).await;
+                return Err(RootErrors::InternalServerError);
+            }
+
+            // Files that just get moved around keep their hashes, so grab them before their records get replaced.
+            let existing_art_file_hashes: HashMap<String, i64> = db_connection
+                .query(
+                    "SELECT s3_key, perceptual_hash FROM art_file WHERE belongs_to=$1 AND perceptual_hash IS NOT NULL",
+                    &[&existing_art.base_art.id],
+                )
+                .await
+                .map_err(|err| {
+                    eprintln!(
+                        "[ART MODIFICATION] Getting the file hashes of art ID {} failed. {err:?}",
+                        existing_art.base_art.id
+                    );
+                    RootErrors::InternalServerError
+                })?
+                .iter()
+                .map(|row| (row.get("s3_key"), row.get("perceptual_hash")))
+                .collect();
+
             // Now that everything is uploaded properly, let's start modifying what needs to be changed.
             let mut columns: Vec<String> = Vec::new();
             let mut values: Vec<&(dyn tokio_postgres::types::ToSql + Sync)> = Vec::new();
@@ -432,6 +606,16 @@ pub async fn edit_art_put_request(
                 values.push(&sent_page_art.base_art.is_nsfw);
             }
 
+            if sent_page_art.base_art.content_warnings != existing_art.base_art.content_warnings {
+                columns.push("content_warnings".into());
+                values.push(&sent_page_art.base_art.content_warnings);
+            }
+
+            if sent_page_art.sources != existing_art.sources {
+                columns.push("sources".into());
+                values.push(&sent_page_art.sources);
+            }
+
             if sent_page_art.description != existing_art.description {
                 columns.push("description".into());
                 values.push(&sent_page_art.description);
@@ -465,10 +649,10 @@ pub async fn edit_art_put_request(
                     RootErrors::InternalServerError
                 })?;
 
-            // Now let's reorder and reorganize the art. Go over all of the given art keys, and see which have been modified.
-            let s3_client = state.s3_client.clone();
-            let target_s3_folder = format!("art/{}", existing_art.base_art.id);
+            // New files get their smaller copies made once their records are in.
+            let mut files_needing_variants: Vec<utils::MovedFile> = Vec::new();
 
+            // Now let's reorder and reorganize the art. Go over all of the given art keys, and see which have been modified.
             for (art_key, new_art_key_index) in sent_page_art.art_keys.iter().zip(0i8..) {
                 let previous_art_key_index = existing_art.art_keys
                     .iter()
@@ -482,32 +666,36 @@ pub async fn edit_art_put_request(
                 {
                     // The new art at index i is unlike the previous art at index i.
 
-                    let new_art_key = if previous_art_key_index.is_some() {
-                        art_key.to_string()
-                    } else {
-                        // If it's new art, move it into place.
-                        let target_file_key = format!(
-                            "{target_s3_folder}/{}",
-                            art_key.split_terminator("/").last().unwrap()
-                        );
-
-                        utils::move_temp_s3_file(
-                            &s3_client,
-                            &state.config,
-                            art_key,
-                            &state.config.s3_public_bucket,
-                            &target_file_key,
-                        )
-                        .await
-                        .map_err(|err| {
-                            eprintln!(
-                                "[MODIFY ART] Failed moving new art for \"{}\", id:{}. Err:{:?}",
-                                &existing_art.base_art.title, &existing_art.base_art.id, err
+                    let (new_art_key, new_art_metadata, perceptual_hash) =
+                        if let Some(previous_index) = previous_art_key_index {
+                            (
+                                art_key.to_string(),
+                                existing_art
+                                    .art_file_metadata
+                                    .get(previous_index)
+                                    .cloned()
+                                    .unwrap_or_default(),
+                                existing_art_file_hashes.get(art_key).copied(),
+                            )
+                        } else {
+                            // If it's new art, it was already moved into place up top.
+                            let Some(moved_file) = new_art_files.remove(art_key) else {
+                                eprintln!(
+                                    "[MODIFY ART] New art {art_key} for art ID {} was never moved?",
+                                    existing_art.base_art.id
+                                );
+                                return Err(RootErrors::InternalServerError);
+                            };
+
+                            let moved_file_info = (
+                                moved_file.key.clone(),
+                                moved_file.metadata.clone(),
+                                moved_file.perceptual_hash,
                             );
 
-                            RootErrors::InternalServerError
-                        })?
-                    };
+                            files_needing_variants.push(moved_file);
+                            moved_file_info
+                        };
 
                     // Remove the DB entry for the current index.
                     db_connection
@@ -526,23 +714,22 @@ pub async fn edit_art_put_request(
                         })?;
 
                     // Now insert a new value for this index.
-                    db_connection
-                        .execute(
-                            INSERT_INTO_ART_FILE_DB_QUERY,
-                            &[
-                                &existing_art.base_art.id,
-                                &((new_art_key_index + 1) as i32),
-                                &new_art_key,
-                            ],
-                        )
-                        .await
-                        .map_err(|err| {
-                            eprintln!(
-                                "[ART MODIFICATION] Adding a new record to art ID {} failed. {:?}",
-                                existing_art.base_art.id, err
-                            );
-                            RootErrors::InternalServerError
-                        })?;
+                    insert_art_file(
+                        &db_connection,
+                        existing_art.base_art.id,
+                        (new_art_key_index + 1) as i32,
+                        &new_art_key,
+                        perceptual_hash,
+                        &new_art_metadata,
+                    )
+                    .await
+                    .map_err(|err| {
+                        eprintln!(
+                            "[ART MODIFICATION] Adding a new record to art ID {} failed. {:?}",
+                            existing_art.base_art.id, err
+                        );
+                        RootErrors::InternalServerError
+                    })?;
                 }
             }
 
@@ -566,14 +753,32 @@ pub async fn edit_art_put_request(
                 }
             }
 
-            // Now that all the new art was moved in, let's delete the art that's no longer present.
-            let art_keys_that_were_removed: Vec<String> = existing_art
+            for moved_file in &files_needing_variants {
+                variants::spawn_art_file_variants(&state, existing_art.base_art.id, moved_file);
+            }
+
+            // Now that all the new art was moved in, let's delete the art that's no longer present, and its smaller copies.
+            let mut art_keys_that_were_removed: Vec<String> = existing_art
                 .art_keys
                 .iter()
                 .filter(|key| !sent_page_art.art_keys.contains(key))
                 .cloned()
                 .collect();
 
+            match variants::remove_art_file_variants(
+                &db_connection,
+                existing_art.base_art.id,
+                &art_keys_that_were_removed,
+            )
+            .await
+            {
+                Ok(variant_keys) => art_keys_that_were_removed.extend(variant_keys),
+                Err(err) => eprintln!(
+                    "[ART MODIFICATION] Failed removing the variants of art ID {}! {err:?}",
+                    existing_art.base_art.id
+                ),
+            }
+
             if let Err(err) = utils::delete_keys_from_s3(
                 &s3_client,
                 &state.config.s3_public_bucket,
@@ -594,8 +799,23 @@ pub async fn edit_art_put_request(
 
             let mut s3_keys_to_delete: Vec<String> = Vec::new();
 
+            let can_post_art = requesting_user.user_type.permissions().can_post_art;
+            let final_post_state = existing_art.post_state.after_edit(can_post_art);
+
             columns.push("post_state".into());
-            values.push(&PostState::Public);
+            values.push(&final_post_state);
+
+            // Going back into the queue means the old rejection doesn't apply anymore.
+            if final_post_state == PostState::PendingApproval {
+                columns.push("rejection_reason".into());
+                values.push(&None::<String>);
+            }
+
+            // Public art stays up, but the edit still shows up in the queue for an admin to look over.
+            if existing_art.post_state.edit_needs_review(can_post_art) {
+                columns.push("edited_since_review".into());
+                values.push(&true);
+            }
 
             // I need to create new_thumbnail_key here so that, incase we use it, it can survive enough.
             let new_thumbnail_key;
@@ -661,6 +881,9 @@ pub async fn edit_art_put_request(
                 eprintln!("[ART MODIFICATION] Failed to clean up redundant S3 keys! Proceeding as normal. Keys: {}. Err: {err:?}", s3_keys_to_delete.join(","));
             };
 
+            // Its tags, NSFW-ness or post state may have changed, and it could be in any number of other art's strips.
+            state.related_art_cache.clear();
+
             println!(
                 "[ART MODIFICATION] User {} (ID:{}) edited art {} (ID:{}, SLUG:{})",
                 requesting_user.display_name,
@@ -675,6 +898,86 @@ pub async fn edit_art_put_request(
     }
 }
 
+/// Takes down art that failed partway through being uploaded, along with whatever of it already got to the bucket.
+/// Its file records go with it through the cascade. This is already the failure path, so errors are only logged.
+async fn discard_processing_art(
+    state: &ServerState,
+    db_connection: &Object<Manager>,
+    art_id: i32,
+    uploaded_keys: &[String],
+) {
+    if let Err(err) = utils::delete_keys_from_s3(
+        &state.s3_client,
+        &state.config.s3_public_bucket,
+        uploaded_keys,
+    )
+    .await
+    {
+        eprintln!(
+            "[ART UPLOAD] Failed cleaning up the files of art {art_id}! Keys: {}. Err: {err:?}",
+            uploaded_keys.join(",")
+        );
+    }
+
+    if let Err(err) = db_connection
+        .execute("DELETE FROM art WHERE id=$1", &[&art_id])
+        .await
+    {
+        eprintln!("[ART UPLOAD] Failed deleting processing art {art_id}! {err:?}");
+    }
+}
+
+/// Deletes new files that were moved in for an edit that didn't go through. Errors are only logged.
+async fn discard_moved_art_files(
+    state: &ServerState,
+    moved_files: &HashMap<String, utils::MovedFile>,
+) {
+    let moved_keys: Vec<String> = moved_files
+        .values()
+        .map(|moved_file| moved_file.key.clone())
+        .collect();
+
+    if let Err(err) = utils::delete_keys_from_s3(
+        &state.s3_client,
+        &state.config.s3_public_bucket,
+        &moved_keys,
+    )
+    .await
+    {
+        eprintln!(
+            "[ART MODIFICATION] Failed cleaning up moved files! Keys: {}. Err: {err:?}",
+            moved_keys.join(",")
+        );
+    }
+}
+
+/// Adds a file to an art post's list of files, at the given position.
+async fn insert_art_file(
+    db_connection: &Object<Manager>,
+    art_id: i32,
+    internal_order: i32,
+    s3_key: &str,
+    perceptual_hash: Option<i64>,
+    metadata: &FileMetadata,
+) -> Result<u64, tokio_postgres::Error> {
+    db_connection
+        .execute(
+            INSERT_INTO_ART_FILE_DB_QUERY,
+            &[
+                &art_id,
+                &internal_order,
+                &s3_key,
+                &perceptual_hash,
+                &metadata.mime_type,
+                &metadata.byte_size,
+                &metadata.width,
+                &metadata.height,
+                &metadata.duration_seconds,
+            ],
+        )
+        .await
+}
+
 /// Given an amount of urls requested by the user, sends the user back the appropriate amount of new temp S3 presigned URLs. May also request an extra url for the thumbnail.
 async fn give_user_presigned_s3_urls(
     requested_amount_of_urls: u8,
@@ -729,6 +1032,22 @@ fn validate_recieved_page_art(recieved_page_art: &PageArt) -> Result<(), String>
         return Err("Invalid page title".to_owned());
     }
 
+    // Nobody needs this many, and they all end up on the page.
+    const MAX_SOURCES: usize = 20;
+    if recieved_page_art.sources.len() > MAX_SOURCES {
+        return Err(format!("Art can have at most {MAX_SOURCES} sources."));
+    }
+
+    if let Some(invalid_source) = recieved_page_art
+        .sources
+        .iter()
+        .find(|source| !sources::is_valid_source_url(source))
+    {
+        return Err(format!(
+            "The source {invalid_source} isn't a link. Sources must be full http(s) URLs."
+        ));
+    }
+
     // Comparing creation date against tomorrow rather than today to account for timezones.
     let tomorrow = chrono::offset::Local::now()
         .date_naive()
@@ -747,12 +1066,25 @@ fn validate_recieved_page_art(recieved_page_art: &PageArt) -> Result<(), String>
         return Err("Given invalid slug. Slugs must be made of either lowercase letters or numbers, and may include hyphens or underscores in the middle.".to_string());
     }
 
+    // These are taken by other pages under /art, so art with these slugs would be unreachable.
+    const RESERVED_ART_SLUGS: [&str; 3] = ["new", "random", "collections"];
+    if RESERVED_ART_SLUGS.contains(&recieved_page_art.base_art.slug.as_str()) {
+        return Err(format!(
+            "The slug {} is reserved, pick a different one.",
+            recieved_page_art.base_art.slug
+        ));
+    }
+
     Ok(())
 }
 
 /// Given a Page Art, cleans up any invalid or nonsensical values, such as empty strings for artist names.
 /// NOTE: Does not make sure the values make _logical_ sense, only that we don't deal with trivially incorrect data.
-fn sanitize_recieved_page_art(recieved_page_art: &mut PageArt, state: &ServerState) {
+async fn sanitize_recieved_page_art(
+    recieved_page_art: &mut PageArt,
+    state: &ServerState,
+    db_connection: &Object<Manager>,
+) {
     // Clean up any empty tags
     recieved_page_art.tags = recieved_page_art
         .tags
@@ -771,6 +1103,9 @@ fn sanitize_recieved_page_art(recieved_page_art: &mut PageArt, state: &ServerSta
         })
         .collect();
 
+    // Swap out aliases for the real tag, and add whatever they imply.
+    recieved_page_art.tags = tags::normalize_tags(db_connection, &recieved_page_art.tags).await;
+
     // Clean up any empty artist names.
     recieved_page_art.base_art.creators = recieved_page_art
         .base_art
@@ -788,6 +1123,32 @@ fn sanitize_recieved_page_art(recieved_page_art: &mut PageArt, state: &ServerSta
         })
         .collect();
 
+    // Keep each warning once, in the usual order, so edits can compare them against what's stored.
+    recieved_page_art.base_art.content_warnings = ContentWarning::ALL
+        .into_iter()
+        .filter(|warning| {
+            recieved_page_art
+                .base_art
+                .content_warnings
+                .contains(warning)
+        })
+        .collect();
+
+    // Clean up empty sources and repeats, keeping the order they were given in.
+    let mut cleaned_sources: Vec<String> = Vec::new();
+    for source in &recieved_page_art.sources {
+        let trimmed_source = source.trim();
+
+        if !trimmed_source.is_empty()
+            && !cleaned_sources
+                .iter()
+                .any(|kept_source| kept_source == trimmed_source)
+        {
+            cleaned_sources.push(trimmed_source.to_string());
+        }
+    }
+    recieved_page_art.sources = cleaned_sources;
+
     // Get only the keys from the URLs the user gave us.
     // We don't need to raise an error if the host is wrong bc if the host is wrong, the key _has_ got to be wrong too.
     // If the host is wrong but the key is correct I legitimately have no idea what the fuck the user is doing.
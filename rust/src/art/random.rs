@@ -1,15 +1,55 @@
-use crate::art::structs::BaseArt;
-use crate::{RootErrors, ServerState};
-use axum::extract::State;
+use crate::art::get_total_amount_of_art;
+use crate::art::structs::{ArtSearchParameters, BaseArt};
+use crate::{nsfw_splash, user::User, RootErrors, ServerState};
+use axum::extract::{OriginalUri, Query, State};
 use axum::response::{IntoResponse, Redirect, Response};
+use tower_cookies::Cookies;
 
-pub async fn random_art_redirect(State(state): State<ServerState>) -> Result<Response, RootErrors> {
+/// Sends the user to a random piece of art. Takes the same search as the art index, so the random button on a search stays in it.
+pub async fn random_art_redirect(
+    State(state): State<ServerState>,
+    Query(mut query_params): Query<ArtSearchParameters>,
+    OriginalUri(original_uri): OriginalUri,
+    cookie_jar: Cookies,
+) -> Result<Response, RootErrors> {
     let db_connection = state.db_pool.get().await.map_err(|err| {
         eprintln!("[RANDOM ART REDIRECT] Failed getting the DB connection! Error: {err:?}");
         RootErrors::InternalServerError
     })?;
 
-    let random_art = BaseArt::get_random_art(&db_connection).await;
+    query_params.resolve_tag_aliases(&db_connection).await;
 
-    Ok(Redirect::to(&format!("/art/{}", random_art.slug)).into_response())
+    let user = User::get_from_cookie_jar(&db_connection, &cookie_jar).await;
+
+    // Landing on something they blacklisted is exactly what they're trying to avoid, blurred or not.
+    query_params.exclude_blacklisted_art(&user);
+
+    let amount_matching = get_total_amount_of_art(&state, &db_connection, &query_params)
+        .await
+        .map_err(|err| {
+            eprintln!(
+                "[RANDOM ART REDIRECT] Failed counting art for {query_params:?}! Error: {err:?}"
+            );
+            RootErrors::InternalServerError
+        })?;
+
+    // The search itself is kept in the URL, so the arrows on the art page keep going through it.
+    let search_uri_params = query_params.to_uri_parameters(false);
+
+    let Some(random_art) =
+        BaseArt::get_random_art(&db_connection, &query_params, amount_matching).await
+    else {
+        // Nothing matches, so send them to the (empty) search instead of a 404.
+        return Ok(Redirect::to(&format!("/art{search_uri_params}")).into_response());
+    };
+
+    if random_art.is_nsfw {
+        if let Some(nsfw_splash) =
+            nsfw_splash::get_if_user_hasnt_enabled_nsfw(&user, &original_uri, &cookie_jar)
+        {
+            return Ok(nsfw_splash);
+        }
+    }
+
+    Ok(Redirect::to(&format!("/art/{}{search_uri_params}", random_art.slug)).into_response())
 }
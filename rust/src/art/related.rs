@@ -0,0 +1,185 @@
+use super::structs::BaseArt;
+use crate::ServerState;
+use deadpool::managed::Object;
+use deadpool_postgres::Manager;
+
+/// How many pieces of art the "More like this" strip shows.
+const AMOUNT_OF_RELATED_ART: i64 = 8;
+
+/// How much sharing an artist counts for, next to the tag overlap (which is between 0 and 1).
+const SHARED_CREATOR_WEIGHT: f64 = 0.5;
+/// How much each shared character counts for. Same character is usually what people are actually after.
+const SHARED_CHARACTER_WEIGHT: f64 = 0.75;
+
+/// Returns the public art most like the given one, best match first. NSFW art is only included if asked for.
+/// Cached per art, since it's the same for everyone and the query isn't cheap.
+pub async fn get_related_art(
+    state: &ServerState,
+    db_connection: &Object<Manager>,
+    art_id: i32,
+    include_nsfw: bool,
+) -> Vec<BaseArt> {
+    let cache_key = (art_id, include_nsfw);
+
+    if let Some(related_art) = state.related_art_cache.get(&cache_key) {
+        return related_art;
+    }
+
+    let related_art: Vec<BaseArt> = match db_connection
+        .query(
+            &get_related_art_query(),
+            &[&art_id, &include_nsfw, &AMOUNT_OF_RELATED_ART],
+        )
+        .await
+    {
+        Ok(rows) => rows.iter().map(BaseArt::from_db_row).collect(),
+        Err(err) => {
+            eprintln!("[RELATED ART] Failed getting art related to art ID {art_id}! {err:?}");
+            // Don't cache failures, maybe it'll work next time.
+            return Vec::new();
+        }
+    };
+
+    state
+        .related_art_cache
+        .insert(cache_key, related_art.clone());
+
+    related_art
+}
+
+/// The query for the art most like $1. $2 is whether NSFW art counts, $3 is how many to get.
+fn get_related_art_query() -> String {
+    // Similarity is the Jaccard index of the tags (shared tags / all tags between the two),
+    // plus a bonus for sharing a creator, plus a bonus for every character they share.
+    // Only art that shares at least a tag or a creator is even considered, so the GIN index on tags does most of the work.
+    format!(
+        "WITH source_art AS (SELECT tags, creators FROM art WHERE id = $1),
+        scored_art AS (
+            SELECT art.id, art.title, art.creators, art.thumbnail, art.page_slug, art.is_nsfw, art.content_warnings, art.creation_date,
+                COALESCE(
+                    cardinality(ARRAY(SELECT unnest(art.tags) INTERSECT SELECT unnest(source_art.tags)))::float8
+                    / NULLIF(cardinality(ARRAY(SELECT unnest(art.tags) UNION SELECT unnest(source_art.tags))), 0),
+                    0
+                )
+                + CASE WHEN art.creators && source_art.creators THEN {SHARED_CREATOR_WEIGHT} ELSE 0 END
+                + {SHARED_CHARACTER_WEIGHT} * cardinality(ARRAY(
+                    SELECT unnest(art.tags) INTERSECT SELECT unnest(source_art.tags)
+                    INTERSECT SELECT relevant_tag FROM character WHERE relevant_tag IS NOT NULL
+                )) AS similarity
+            FROM art, source_art
+            WHERE art.id != $1
+                AND art.post_state = 'public'
+                AND (NOT art.is_nsfw OR $2)
+                AND (art.tags && source_art.tags OR art.creators && source_art.creators)
+        )
+        SELECT id, title, creators, thumbnail, page_slug, is_nsfw, content_warnings FROM scored_art
+        ORDER BY similarity DESC, creation_date DESC, page_slug
+        LIMIT $3"
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+
+    /// An art piece's (tags, creators).
+    type TestArt<'a> = (&'a [&'a str], &'a [&'a str]);
+
+    /// Same scoring as the query, for checking what the weights add up to.
+    fn similarity(
+        (tags, creators): TestArt,
+        (other_tags, other_creators): TestArt,
+        character_tags: &[&str],
+    ) -> f64 {
+        let tags: HashSet<_> = tags.iter().collect();
+        let other_tags: HashSet<_> = other_tags.iter().collect();
+
+        let shared_tags = tags.intersection(&other_tags).count();
+        let all_tags = tags.union(&other_tags).count();
+        let shared_characters = tags
+            .intersection(&other_tags)
+            .filter(|tag| character_tags.contains(tag))
+            .count();
+
+        let tag_overlap = if all_tags == 0 {
+            0.0
+        } else {
+            shared_tags as f64 / all_tags as f64
+        };
+        let creator_bonus = if creators
+            .iter()
+            .any(|creator| other_creators.contains(creator))
+        {
+            SHARED_CREATOR_WEIGHT
+        } else {
+            0.0
+        };
+
+        tag_overlap + creator_bonus + SHARED_CHARACTER_WEIGHT * shared_characters as f64
+    }
+
+    #[test]
+    fn similarity_adds_up_like_the_query() {
+        // Expected values are what Postgres gave for the same arrays.
+        let cases: [(TestArt, TestArt, f64); 4] = [
+            (
+                (&["kate", "forest", "night"], &["a"]),
+                (&["kate", "forest", "day"], &["b"]),
+                1.25,
+            ),
+            ((&["forest"], &["a"]), (&["forest", "day"], &["a"]), 1.0),
+            ((&[], &["a"]), (&[], &["a"]), 0.5),
+            ((&["forest"], &["a"]), (&["night"], &["b"]), 0.0),
+        ];
+
+        for (art, other_art, expected) in cases {
+            assert_eq!(
+                similarity(art, other_art, &["kate", "ucas"]),
+                expected,
+                "{art:?} vs {other_art:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn shared_characters_beat_shared_creators() {
+        let source_art: TestArt = (&["kate", "forest", "night"], &["a"]);
+        let same_character = (&["kate", "beach"][..], &["b"][..]);
+        let same_creator = (&["city", "rain"][..], &["a"][..]);
+
+        assert!(
+            similarity(source_art, same_character, &["kate"])
+                > similarity(source_art, same_creator, &["kate"])
+        );
+    }
+
+    #[test]
+    fn same_tags_beat_a_shared_creator() {
+        let source_art: TestArt = (&["forest", "night", "rain"], &["a"]);
+        let same_tags = (&["forest", "night", "rain"][..], &["b"][..]);
+        let same_creator = (&["forest", "beach", "day", "city"][..], &["a"][..]);
+
+        assert!(similarity(source_art, same_tags, &[]) > similarity(source_art, same_creator, &[]));
+    }
+
+    #[test]
+    fn query_has_the_weights_in_it() {
+        let query = get_related_art_query();
+
+        // Formatted as plain decimals, since Postgres doesn't take stuff like 5e-1 everywhere.
+        assert!(query.contains(&format!("THEN {SHARED_CREATOR_WEIGHT} ELSE 0")));
+        assert!(query.contains(&format!("{SHARED_CHARACTER_WEIGHT} * cardinality")));
+        assert!(!query.contains('{'));
+    }
+
+    #[test]
+    fn query_only_takes_three_parameters() {
+        let query = get_related_art_query();
+
+        for param in ["$1", "$2", "$3"] {
+            assert!(query.contains(param), "{param}");
+        }
+        assert!(!query.contains("$4"));
+    }
+}
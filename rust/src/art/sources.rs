@@ -0,0 +1,337 @@
+use ammonia::Url;
+use serde::{Deserialize, Deserializer};
+
+/// Where a source link points. Only used for labelling links, what's stored is just the URL.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SourcePlatform {
+    Discord,
+    Twitter,
+    Tumblr,
+    Bluesky,
+    DeviantArt,
+    Reddit,
+    Pixiv,
+    YouTube,
+    /// Some site we don't know about. Labelled with its domain instead.
+    Other,
+}
+
+impl SourcePlatform {
+    /// Figures out which platform the given URL is from, by its domain.
+    pub fn detect(source_url: &str) -> Self {
+        let Some(host) = Url::parse(source_url)
+            .ok()
+            .and_then(|url| url.host_str().map(|host| host.to_lowercase()))
+        else {
+            return SourcePlatform::Other;
+        };
+
+        // Subdomains count too, so www.reddit.com or someone.tumblr.com still get recognized.
+        let is_on = |domain: &str| host == domain || host.ends_with(&format!(".{domain}"));
+
+        if is_on("discord.com") || is_on("discordapp.com") {
+            SourcePlatform::Discord
+        } else if is_on("twitter.com") || is_on("x.com") {
+            SourcePlatform::Twitter
+        } else if is_on("tumblr.com") {
+            SourcePlatform::Tumblr
+        } else if is_on("bsky.app") {
+            SourcePlatform::Bluesky
+        } else if is_on("deviantart.com") {
+            SourcePlatform::DeviantArt
+        } else if is_on("reddit.com") || is_on("redd.it") {
+            SourcePlatform::Reddit
+        } else if is_on("pixiv.net") {
+            SourcePlatform::Pixiv
+        } else if is_on("youtube.com") || is_on("youtu.be") {
+            SourcePlatform::YouTube
+        } else {
+            SourcePlatform::Other
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            SourcePlatform::Discord => "Discord",
+            SourcePlatform::Twitter => "Twitter",
+            SourcePlatform::Tumblr => "Tumblr",
+            SourcePlatform::Bluesky => "Bluesky",
+            SourcePlatform::DeviantArt => "DeviantArt",
+            SourcePlatform::Reddit => "Reddit",
+            SourcePlatform::Pixiv => "Pixiv",
+            SourcePlatform::YouTube => "YouTube",
+            SourcePlatform::Other => "Elsewhere",
+        }
+    }
+}
+
+/// A source link of an art post, as shown on its page.
+#[derive(Debug, Clone)]
+pub struct ArtSource {
+    pub url: String,
+    pub platform: SourcePlatform,
+}
+
+impl ArtSource {
+    pub fn new(url: &str) -> Self {
+        ArtSource {
+            url: url.to_string(),
+            platform: SourcePlatform::detect(url),
+        }
+    }
+
+    /// What to show for the link. The platform's name, or the domain if it's not one we know.
+    pub fn get_label(&self) -> String {
+        if self.platform != SourcePlatform::Other {
+            return self.platform.label().to_string();
+        }
+
+        Url::parse(&self.url)
+            .ok()
+            .and_then(|url| {
+                url.host_str()
+                    .map(|host| host.trim_start_matches("www.").to_string())
+            })
+            .unwrap_or_else(|| self.platform.label().to_string())
+    }
+
+    /// The source search that finds everything else posted in the same place, if there's such a thing.
+    /// Right now only Discord, where it's the channel. Most of the archive came out of pins.
+    /// Written without a trailing slash, so links to the channel itself match too. See [`get_search_condition`].
+    pub fn get_search_term(&self) -> Option<String> {
+        if self.platform != SourcePlatform::Discord {
+            return None;
+        }
+
+        // Message links go /channels/[server]/[channel]/[message].
+        let url = Url::parse(&self.url).ok()?;
+        let mut path_segments = url.path_segments()?;
+
+        if path_segments.next()? != "channels" {
+            return None;
+        }
+
+        let server_id = path_segments.next()?;
+        let channel_id = path_segments.next()?;
+
+        Some(format!("channels/{server_id}/{channel_id}"))
+    }
+}
+
+/// The SQL condition for art with a source matching the term in the given parameter.
+/// The term has to be followed by a slash or the end of the link, so "channels/1/2" doesn't also find "channels/1/23".
+/// The tests mirror this in `matches_search_term`, keep them in sync.
+pub fn get_search_condition(parameter_index: usize) -> String {
+    format!(
+        "EXISTS (SELECT 1 FROM unnest(sources) AS source
+            WHERE strpos(lower(source), lower(${parameter_index}) || '/') > 0
+                OR right(lower(source), length(${parameter_index})) = lower(${parameter_index}))"
+    )
+}
+
+/// Deserializes a source search from a URL. Trailing slashes are dropped, since the search adds its own.
+pub fn deserialize_source_search<'de, D>(deserializer: D) -> Result<Option<String>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let s = String::deserialize(deserializer)?;
+    let trimmed = s.trim().trim_end_matches('/');
+
+    if trimmed.is_empty() {
+        Ok(None)
+    } else {
+        Ok(Some(trimmed.to_string()))
+    }
+}
+
+/// Checks whether the given source is a link we'd put on an art page.
+pub fn is_valid_source_url(source_url: &str) -> bool {
+    Url::parse(source_url)
+        .is_ok_and(|url| matches!(url.scheme(), "http" | "https") && url.host_str().is_some())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Whether a source search for the given term finds this source. The same check [`get_search_condition`] does in SQL.
+    fn matches_search_term(source: &ArtSource, search_term: &str) -> bool {
+        let url = source.url.to_lowercase();
+        let search_term = search_term.to_lowercase();
+
+        url.contains(&format!("{search_term}/")) || url.ends_with(&search_term)
+    }
+
+    #[test]
+    fn detects_platforms() {
+        let cases = [
+            (
+                "https://discord.com/channels/1/2/3",
+                SourcePlatform::Discord,
+            ),
+            (
+                "https://discordapp.com/channels/1/2/3",
+                SourcePlatform::Discord,
+            ),
+            (
+                "https://ptb.discord.com/channels/1/2/3",
+                SourcePlatform::Discord,
+            ),
+            (
+                "https://cdn.discordapp.com/attachments/1/2/art.png",
+                SourcePlatform::Discord,
+            ),
+            (
+                "https://twitter.com/someone/status/123",
+                SourcePlatform::Twitter,
+            ),
+            ("https://x.com/someone/status/123", SourcePlatform::Twitter),
+            (
+                "https://mobile.twitter.com/someone/status/123",
+                SourcePlatform::Twitter,
+            ),
+            (
+                "https://someone.tumblr.com/post/123",
+                SourcePlatform::Tumblr,
+            ),
+            ("https://www.tumblr.com/someone/123", SourcePlatform::Tumblr),
+            (
+                "https://bsky.app/profile/someone/post/abc",
+                SourcePlatform::Bluesky,
+            ),
+            (
+                "https://www.deviantart.com/someone/art/thing-123",
+                SourcePlatform::DeviantArt,
+            ),
+            (
+                "https://old.reddit.com/r/something/comments/abc",
+                SourcePlatform::Reddit,
+            ),
+            (
+                "https://www.pixiv.net/en/artworks/123",
+                SourcePlatform::Pixiv,
+            ),
+            ("https://youtu.be/abc", SourcePlatform::YouTube),
+            (
+                "HTTPS://DISCORD.COM/channels/1/2/3",
+                SourcePlatform::Discord,
+            ),
+            // Lookalikes don't count.
+            (
+                "https://notdiscord.com/channels/1/2/3",
+                SourcePlatform::Other,
+            ),
+            ("https://x.com.evil.example/someone", SourcePlatform::Other),
+            ("https://example.com/discord.com", SourcePlatform::Other),
+            ("https://someones-site.net/gallery", SourcePlatform::Other),
+            ("not a url", SourcePlatform::Other),
+            ("", SourcePlatform::Other),
+        ];
+
+        for (url, platform) in cases {
+            assert_eq!(SourcePlatform::detect(url), platform, "url {url:?}");
+        }
+    }
+
+    #[test]
+    fn labels() {
+        let cases = [
+            ("https://discord.com/channels/1/2/3", "Discord"),
+            ("https://x.com/someone/status/123", "Twitter"),
+            ("https://someone.tumblr.com/post/123", "Tumblr"),
+            ("https://www.someones-site.net/gallery", "someones-site.net"),
+            ("https://art.example.org/1", "art.example.org"),
+        ];
+
+        for (url, label) in cases {
+            assert_eq!(ArtSource::new(url).get_label(), label, "url {url:?}");
+        }
+    }
+
+    #[test]
+    fn search_terms() {
+        let cases = [
+            (
+                "https://discord.com/channels/111/222/333",
+                Some("channels/111/222"),
+            ),
+            // Same channel from the old domain searches the same.
+            (
+                "https://discordapp.com/channels/111/222/333",
+                Some("channels/111/222"),
+            ),
+            // A link to the channel itself.
+            (
+                "https://discord.com/channels/111/222",
+                Some("channels/111/222"),
+            ),
+            ("https://discord.com/channels/111", None),
+            ("https://discord.com/invite/abc", None),
+            ("https://cdn.discordapp.com/attachments/1/2/art.png", None),
+            ("https://x.com/someone/status/123", None),
+            ("https://someone.tumblr.com/post/123", None),
+            ("https://someones-site.net/channels/1/2/3", None),
+        ];
+
+        for (url, search_term) in cases {
+            assert_eq!(
+                ArtSource::new(url).get_search_term().as_deref(),
+                search_term,
+                "url {url:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn sources_are_found_by_their_own_search_term() {
+        for url in [
+            "https://discord.com/channels/111/222/333",
+            "https://discord.com/channels/111/222",
+            "https://discord.com/channels/111/222/",
+            "https://discordapp.com/channels/111/222/333",
+        ] {
+            let source = ArtSource::new(url);
+            let search_term = source
+                .get_search_term()
+                .expect("discord links have a search term");
+
+            assert!(matches_search_term(&source, &search_term), "url {url:?}");
+        }
+    }
+
+    #[test]
+    fn search_terms_dont_match_other_channels() {
+        let search_term = ArtSource::new("https://discord.com/channels/111/222")
+            .get_search_term()
+            .unwrap();
+
+        for url in [
+            "https://discord.com/channels/111/2223/333",
+            "https://discord.com/channels/111/2223",
+            "https://discord.com/channels/111/333/222",
+        ] {
+            assert!(
+                !matches_search_term(&ArtSource::new(url), &search_term),
+                "url {url:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn valid_source_urls() {
+        let cases = [
+            ("https://discord.com/channels/1/2/3", true),
+            ("http://someones-site.net", true),
+            ("ftp://someones-site.net/art.png", false),
+            ("javascript:alert(1)", false),
+            ("data:text/html,hi", false),
+            ("/art/some-slug", false),
+            ("someones-site.net", false),
+            ("", false),
+        ];
+
+        for (url, is_valid) in cases {
+            assert_eq!(is_valid_source_url(url), is_valid, "url {url:?}");
+        }
+    }
+}
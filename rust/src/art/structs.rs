@@ -1,13 +1,26 @@
-use super::SQL_ORDER_BY_STATEMENT;
+use super::{
+    content_warnings::{self, ContentWarning},
+    cursor::{self, ArtCursor, CursorDirection},
+    sources,
+    tag_query::{self, TagQueryTerm},
+    tags,
+    variants::{self, ArtFileVariant},
+    SQL_ORDER_BY_STATEMENT,
+};
 use crate::{
-    user::{User, UsermadePost},
-    utils::sql::PostState,
+    user::{ContentFilters, User, UsermadePost},
+    utils::{file_metadata::FileMetadata, sql::PostState},
 };
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, NaiveDate, Utc};
 use deadpool::managed::Object;
 use deadpool_postgres::Manager;
 use postgres::Row;
 use serde::{Deserialize, Deserializer};
+use std::collections::{HashMap, HashSet};
+
+/// The columns BaseArt::from_db_row needs. No reason to drag descriptions and search vectors along for a thumbnail.
+const BASE_ART_COLUMNS: &str =
+    "id, title, creators, thumbnail, page_slug, is_nsfw, content_warnings";
 
 #[derive(Debug, Clone, Deserialize)]
 pub struct BaseArt {
@@ -19,6 +32,8 @@ pub struct BaseArt {
     pub slug: String,
     #[serde(default)]
     pub is_nsfw: bool,
+    #[serde(default)]
+    pub content_warnings: Vec<ContentWarning>,
     /*#[serde(default = "default_art_state")]
     pub art_state: PostState,*/
 }
@@ -33,62 +48,237 @@ pub struct PageArt {
     pub tags: Vec<String>,
     #[serde(default)]
     pub art_keys: Vec<String>,
+    /// Links to wherever the art was originally posted, in the order they were given. Discord pins, tweets and such.
+    #[serde(default)]
+    pub sources: Vec<String>,
+    /// What we know about each of the files in art_keys, in the same order.
+    #[serde(skip)]
+    pub art_file_metadata: Vec<FileMetadata>,
+    /// The smaller copies of each of the files in art_keys, in the same order.
+    #[serde(skip)]
+    pub art_file_variants: Vec<Vec<ArtFileVariant>>,
     pub creation_date: chrono::NaiveDate,
     #[serde(skip)]
     pub uploading_user: Option<User>,
     #[serde(skip)]
     pub comments: Vec<Comment>,
+    #[serde(skip)]
+    pub favorite_count: i64,
+    #[serde(skip)]
+    pub post_state: PostState,
+    #[serde(skip)]
+    pub rejection_reason: Option<String>, // Why an admin rejected it, if they did.
+}
+
+/// Picks how many matches to skip for random art, or None if nothing matches. Every match is equally likely.
+fn get_random_offset(amount_matching: i64) -> Option<i64> {
+    (amount_matching > 0).then(|| rand::random_range(0..amount_matching))
+}
+
+/// The query for a single piece of art matching the given search, some amount of matches along the ID index.
+/// The amount to skip is the parameter after the search's, so push it right after calling this.
+fn get_random_art_query<'a>(
+    search_params: &'a ArtSearchParameters,
+    query_params: &mut Vec<&'a (dyn tokio_postgres::types::ToSql + Sync)>,
+) -> String {
+    let query_where = search_params.get_postgres_where(query_params);
+
+    // This is safe bc query_where is entirely made within our code, and all the user-given info is in query_params.
+    format!(
+        "SELECT {BASE_ART_COLUMNS} FROM art {query_where} ORDER BY id OFFSET ${} LIMIT 1",
+        query_params.len() + 1
+    )
 }
 
 impl BaseArt {
     pub async fn get_by_slug(db_connection: &Object<Manager>, page_slug: &str) -> Option<Self> {
         let requested_art = db_connection
-            .query_one("SELECT * FROM art WHERE page_slug=$1", &[&page_slug])
+            .query_one(
+                &format!("SELECT {BASE_ART_COLUMNS} FROM art WHERE page_slug=$1"),
+                &[&page_slug],
+            )
             .await
             .ok()?;
 
         Some(Self::from_db_row(&requested_art))
     }
 
-    pub async fn get_random_art(db_connection: &Object<Manager>) -> Self {
-        let random_art = db_connection
-            .query_one(
-                "SELECT * FROM art WHERE post_state='public' ORDER BY RANDOM() LIMIT 1",
-                &[],
+    /// Picks a random piece of art out of the ones matching the given search, or None if nothing matches.
+    /// Needs to be told how many pieces match (the cached count is fine), so every match is equally likely.
+    /// It skips that many matches along the ID index instead of shuffling all of them with ORDER BY RANDOM(),
+    /// which still walks through the matches before it, but never more than the amount matching.
+    pub async fn get_random_art(
+        db_connection: &Object<Manager>,
+        search_params: &ArtSearchParameters,
+        amount_matching: i64,
+    ) -> Option<Self> {
+        let random_offset = get_random_offset(amount_matching)?;
+        let first_offset: i64 = 0;
+
+        let mut query_params: Vec<&(dyn tokio_postgres::types::ToSql + Sync)> = Vec::new();
+
+        let query = get_random_art_query(search_params, &mut query_params);
+        query_params.push(&random_offset);
+
+        let mut random_art = db_connection.query_opt(&query, &query_params).await;
+
+        // The cached count can be a bit stale, so the offset might be past the end now. Rare enough that the first one is fine.
+        if matches!(random_art, Ok(None)) && random_offset > 0 {
+            query_params.pop();
+            query_params.push(&first_offset);
+
+            random_art = db_connection.query_opt(&query, &query_params).await;
+        }
+
+        match random_art {
+            Ok(random_art) => random_art.map(|row| Self::from_db_row(&row)),
+            Err(err) => {
+                eprintln!("[RANDOM ART] Failed picking random art! Query={query} Err={err:?}");
+                None
+            }
+        }
+    }
+
+    /// Returns the public art in the given collection, in the collection's order.
+    pub async fn get_in_collection(
+        db_connection: &Object<Manager>,
+        collection_id: i32,
+    ) -> Vec<Self> {
+        db_connection
+            .query(
+                &format!(
+                    "SELECT {BASE_ART_COLUMNS} FROM art
+                    JOIN art_collection_member ON art_collection_member.art_id = art.id
+                    WHERE art_collection_member.collection_id=$1 AND post_state='public'
+                    ORDER BY art_collection_member.internal_order"
+                ),
+                &[&collection_id],
             )
             .await
-            .unwrap();
-
-        Self::from_db_row(&random_art)
+            .unwrap_or_default()
+            .iter()
+            .map(Self::from_db_row)
+            .collect()
     }
 
-    /// Gets [amount_to_return] amount of art pieces, starting from the [index] newest piece.
-    pub async fn get_art_from_index(
+    /// Gets a page's worth of art for the given search.
+    /// If the search has a cursor, and the search's ordering allows it, gets the art next to the cursor.
+    /// Otherwise falls back to skipping to [page_number] with OFFSET.
+    pub async fn get_art_page(
         db_connection: &Object<Manager>,
-        index: i64,
-        amount_to_return: i64,
+        amount_per_page: i64,
+        page_number: i64,
         search_parameters: &ArtSearchParameters,
-    ) -> Vec<Self> {
-        // TODO: Narrow down select so it runs faster.
+    ) -> ArtIndexSlice {
+        // One extra, so we know whether there's anything past this page.
+        let amount_to_fetch = amount_per_page + 1;
+        let offset = page_number
+            .saturating_sub(1)
+            .saturating_mul(amount_per_page);
+
         let mut query_parameters: Vec<&(dyn tokio_postgres::types::ToSql + Sync)> =
-            vec![&amount_to_return, &index];
+            vec![&amount_to_fetch];
 
         let query_where = search_parameters.get_postgres_where(&mut query_parameters);
 
-        // This is safe bc query_where is entirely made within our code, and all the user-given info is in query_params.
-        let query =
-            format!("SELECT * FROM art {query_where} {SQL_ORDER_BY_STATEMENT} LIMIT $1 OFFSET $2");
+        let keyset_cursor = search_parameters
+            .cursor
+            .as_ref()
+            .filter(|_| search_parameters.supports_keyset_pagination());
+
+        // This is safe bc everything but the parameters is entirely made within our code, and all the user-given info is in query_params.
+        let query = match keyset_cursor {
+            Some(cursor) => {
+                query_parameters.push(&cursor.creation_date);
+                let date_param = query_parameters.len();
+                query_parameters.push(&cursor.page_slug);
+                let slug_param = query_parameters.len();
+
+                let (keyset_condition, order_by) = get_keyset_condition_and_order(
+                    search_parameters.get_sort(),
+                    cursor.direction,
+                    date_param,
+                    slug_param,
+                );
+
+                let query_where = if query_where.is_empty() {
+                    format!("WHERE {keyset_condition}")
+                } else {
+                    format!("{query_where} AND {keyset_condition}")
+                };
+
+                format!("SELECT {BASE_ART_COLUMNS}, creation_date FROM art {query_where} {order_by} LIMIT $1")
+            }
+            None => {
+                let query_order_by = search_parameters.get_postgres_order_by(&mut query_parameters);
+                query_parameters.push(&offset);
+
+                format!(
+                    "SELECT {BASE_ART_COLUMNS}, creation_date FROM art {query_where} {query_order_by} LIMIT $1 OFFSET ${}",
+                    query_parameters.len()
+                )
+            }
+        };
 
-        let requested_art_rows = db_connection
+        let mut requested_art_rows = db_connection
             .query(&query, &query_parameters)
             .await
             .unwrap();
 
-        requested_art_rows.iter().map(Self::from_db_row).collect()
+        let has_more_rows = requested_art_rows.len() as i64 > amount_per_page;
+        requested_art_rows.truncate(amount_per_page as usize);
+
+        let (page_number, has_previous_page, has_next_page) = match keyset_cursor {
+            Some(cursor) if cursor.direction == CursorDirection::Before => {
+                requested_art_rows.reverse();
+                // If there's nothing before this, we're on the first page no matter what the cursor claims.
+                let page_number = if has_more_rows { cursor.page_number } else { 1 };
+                (page_number, has_more_rows, true)
+            }
+            Some(cursor) => (cursor.page_number, true, has_more_rows),
+            None => (page_number, page_number > 1, has_more_rows),
+        };
+
+        // Cursors only make sense when the order is one the keyset conditions above understand.
+        let (previous_page_cursor, next_page_cursor) =
+            if search_parameters.supports_keyset_pagination() {
+                let cursor_at = |row: &Row, direction, page_number| ArtCursor {
+                    direction,
+                    creation_date: row.get::<_, NaiveDate>("creation_date"),
+                    page_slug: row.get("page_slug"),
+                    page_number,
+                };
+
+                (
+                    requested_art_rows
+                        .first()
+                        .filter(|_| has_previous_page)
+                        .map(|row| {
+                            cursor_at(row, CursorDirection::Before, page_number.saturating_sub(1))
+                        }),
+                    requested_art_rows
+                        .last()
+                        .filter(|_| has_next_page)
+                        .map(|row| {
+                            cursor_at(row, CursorDirection::After, page_number.saturating_add(1))
+                        }),
+                )
+            } else {
+                (None, None)
+            };
+
+        ArtIndexSlice {
+            art_pieces: requested_art_rows.iter().map(Self::from_db_row).collect(),
+            page_number,
+            has_previous_page,
+            has_next_page,
+            previous_page_cursor,
+            next_page_cursor,
+        }
     }
 
     /// Converts a DB row with the relevant info to a BaseArt struct.
-    fn from_db_row(row: &Row) -> Self {
+    pub(super) fn from_db_row(row: &Row) -> Self {
         BaseArt {
             id: row.get("id"),
             title: row.get("title"),
@@ -96,13 +286,104 @@ impl BaseArt {
             thumbnail_key: row.get("thumbnail"),
             slug: row.get("page_slug"),
             is_nsfw: row.get("is_nsfw"),
+            content_warnings: row.get("content_warnings"),
+        }
+    }
+
+    /// Returns which of the given art pieces are caught by the given filters.
+    pub async fn get_ids_caught_by_filters(
+        db_connection: &Object<Manager>,
+        art_ids: &[i32],
+        content_filters: &ContentFilters,
+    ) -> HashSet<i32> {
+        if art_ids.is_empty() || content_filters.is_empty() {
+            return HashSet::new();
         }
+
+        const FILTERED_ART_QUERY: &str = "SELECT id FROM art WHERE id = ANY($1) AND (tags && $2
+            OR EXISTS (SELECT 1 FROM unnest(creators) AS creator WHERE lower(creator) = ANY($3)))";
+
+        match db_connection
+            .query(
+                FILTERED_ART_QUERY,
+                &[
+                    &art_ids,
+                    &content_filters.blacklisted_tags,
+                    &content_filters.get_lowercase_blacklisted_artists(),
+                ],
+            )
+            .await
+        {
+            Ok(rows) => rows.iter().map(|row| row.get("id")).collect(),
+            Err(err) => {
+                eprintln!(
+                    "[CONTENT FILTERS] Failed checking art {art_ids:?} against filters! {err:?}"
+                );
+                HashSet::new()
+            }
+        }
+    }
+
+    /// Returns how many users have this art in their favorites.
+    pub async fn get_favorite_count(db_connection: &Object<Manager>, art_id: i32) -> i64 {
+        db_connection
+            .query_one(
+                "SELECT COUNT(*) FROM art_favorite WHERE art_id=$1",
+                &[&art_id],
+            )
+            .await
+            .map(|row| row.get(0))
+            .unwrap_or(0)
+    }
+
+    /// Returns whether the given user has this art in their favorites.
+    pub async fn is_favorited_by(
+        db_connection: &Object<Manager>,
+        art_id: i32,
+        user_id: i32,
+    ) -> bool {
+        db_connection
+            .query_one(
+                "SELECT EXISTS (SELECT 1 FROM art_favorite WHERE art_id=$1 AND user_id=$2)",
+                &[&art_id, &user_id],
+            )
+            .await
+            .map(|row| row.get(0))
+            .unwrap_or(false)
     }
 
     /// Returns the proper URL for the thumbnail.
     pub fn get_thumbnail_url(&self) -> String {
         crate::utils::get_s3_public_object_url(&self.thumbnail_key)
     }
+
+    /// Returns the warnings on this art that the given user hasn't said they're fine with. These cover it up until they click through.
+    /// People who aren't logged in get every warning.
+    pub fn get_unaccepted_warnings(&self, user: &Option<User>) -> Vec<ContentWarning> {
+        self.content_warnings
+            .iter()
+            .filter(|warning| {
+                !user.as_ref().is_some_and(|user| {
+                    user.content_filters
+                        .shown_content_warnings
+                        .contains(warning)
+                })
+            })
+            .copied()
+            .collect()
+    }
+}
+
+/// A page's worth of art from the index, along with what's needed to link to the pages around it.
+#[derive(Debug, Clone)]
+pub struct ArtIndexSlice {
+    pub art_pieces: Vec<BaseArt>,
+    pub page_number: i64,
+    pub has_previous_page: bool,
+    pub has_next_page: bool,
+    /// Points at the page before/after this one. None if there isn't one, or if the search can't be paged by cursor.
+    pub previous_page_cursor: Option<ArtCursor>,
+    pub next_page_cursor: Option<ArtCursor>,
 }
 
 impl PageArt {
@@ -129,16 +410,22 @@ impl PageArt {
                 let index: i32 = row.get("internal_order");
                 let key: String = row.get("s3_key");
 
-                (index, key)
+                (index, key, FileMetadata::from_db_row(row))
             })
             .collect::<Vec<_>>();
 
-        art_files.sort();
+        art_files.sort_by_key(|(index, _, _)| *index);
+
+        let (art_keys, art_file_metadata): (Vec<String>, _) = art_files
+            .into_iter()
+            .map(|(_, key, metadata)| (key, metadata))
+            .unzip();
 
-        let art_keys = art_files
+        let mut variants_by_key = variants::get_art_file_variants(db_connection, art_id).await;
+        let art_file_variants = art_keys
             .iter()
-            .map(|(_, x)| x.to_owned())
-            .collect::<Vec<_>>();
+            .map(|key| variants_by_key.remove(key).unwrap_or_default())
+            .collect();
 
         let uploading_user_id: Option<i32> = row.get("uploading_user_id");
         let uploading_user = if let Some(user_id) = uploading_user_id {
@@ -150,15 +437,22 @@ impl PageArt {
         let base_art = BaseArt::from_db_row(row);
 
         let comments = Self::get_comments(&base_art.id, db_connection).await;
+        let favorite_count = BaseArt::get_favorite_count(db_connection, base_art.id).await;
 
         PageArt {
             base_art,
             description: row.get("description"),
             tags: row.try_get("tags").unwrap_or_default(),
             art_keys,
+            sources: row.get("sources"),
+            art_file_metadata,
+            art_file_variants,
             creation_date: row.get("creation_date"),
             uploading_user,
             comments,
+            favorite_count,
+            post_state: row.get("post_state"),
+            rejection_reason: row.get("rejection_reason"),
         }
     }
 
@@ -170,27 +464,102 @@ impl PageArt {
             .collect()
     }
 
-    /// Returns the comments underneath a given art post, sorted by posting time.
+    /// Returns the srcset for the art file at the given index: its smaller copies, then the original.
+    /// None if it has no smaller copies (or we don't know how wide the original is), in which case src is enough.
+    pub fn get_srcset(&self, index: usize) -> Option<String> {
+        let variants = self
+            .art_file_variants
+            .get(index)
+            .filter(|variants| !variants.is_empty())?;
+        let original_width = self.art_file_metadata.get(index)?.width?;
+        let original_url = crate::utils::get_s3_public_object_url(self.art_keys.get(index)?);
+
+        Some(
+            variants
+                .iter()
+                .map(|variant| format!("{} {}w", variant.get_url(), variant.width))
+                .chain(std::iter::once(format!("{original_url} {original_width}w")))
+                .collect::<Vec<_>>()
+                .join(", "),
+        )
+    }
+
+    /// Returns the S3 keys of every smaller copy of every file in this art.
+    pub fn get_variant_keys(&self) -> Vec<String> {
+        self.art_file_variants
+            .iter()
+            .flatten()
+            .map(|variant| variant.s3_key.clone())
+            .collect()
+    }
+
+    /// Returns the comments underneath a given art post, in thread order:
+    /// Every comment is followed by its replies (sorted by posting time), and top-level comments are sorted by posting time.
+    /// Deleted comments are only kept around if something under them wasn't deleted.
     pub async fn get_comments(post_id: &i32, db_connection: &Object<Manager>) -> Vec<Comment> {
-        // Get the relevant art URLs from the art_file table.
-        let comment_futures: Vec<_> = db_connection
+        let comment_rows: Vec<_> = db_connection
             .query("SELECT * FROM art_comment WHERE under_post=$1", &[post_id])
             .await
             .unwrap_or(Vec::new());
 
-        if comment_futures.is_empty() {
+        if comment_rows.is_empty() {
             return Vec::new();
         }
 
         let mut comments = Vec::new();
 
-        for comment in comment_futures {
+        for comment in comment_rows {
             comments.push(Comment::from_db_row(&comment, db_connection).await);
         }
 
+        Self::thread_comments(comments)
+    }
+
+    /// Puts the given comments in thread order, and sets how deep each one is. See [Self::get_comments].
+    fn thread_comments(mut comments: Vec<Comment>) -> Vec<Comment> {
         comments.sort_by_key(|comment| comment.posting_time);
 
-        comments
+        let mut replies: HashMap<Option<i32>, Vec<Comment>> = HashMap::new();
+        for comment in comments {
+            replies.entry(comment.reply_to).or_default().push(comment);
+        }
+
+        let mut threaded_comments = Vec::new();
+        Self::flatten_comment_thread(None, 0, &mut replies, &mut threaded_comments);
+
+        threaded_comments
+    }
+
+    /// Appends the replies to [parent_id] (and their replies, and so on) into [output], in thread order.
+    /// Returns whether anything that was added isn't deleted.
+    fn flatten_comment_thread(
+        parent_id: Option<i32>,
+        depth: usize,
+        replies: &mut HashMap<Option<i32>, Vec<Comment>>,
+        output: &mut Vec<Comment>,
+    ) -> bool {
+        let mut added_visible_comment = false;
+
+        for mut comment in replies.remove(&parent_id).unwrap_or_default() {
+            comment.depth = depth;
+            let comment_id = comment.id;
+            let comment_is_deleted = comment.is_deleted();
+
+            let position_in_output = output.len();
+            output.push(comment);
+
+            let has_visible_replies =
+                Self::flatten_comment_thread(Some(comment_id), depth + 1, replies, output);
+
+            // A deleted comment with nothing worth reading under it is just noise.
+            if comment_is_deleted && !has_visible_replies {
+                output.truncate(position_in_output);
+            } else {
+                added_visible_comment = true;
+            }
+        }
+
+        added_visible_comment
     }
 }
 
@@ -214,16 +583,145 @@ pub struct ArtSearchParameters {
     pub page: i64,
 
     #[serde(default, deserialize_with = "deserialize_tags")]
-    pub tags: Vec<String>,
+    pub tags: Vec<TagQueryTerm>,
 
     #[serde(default)]
     pub is_nsfw: bool,
 
-    #[serde(default)]
+    /// Always public when it comes from a URL, so nobody can go digging through the approval queue.
+    #[serde(skip)]
     pub art_state: PostState,
 
     #[serde(default)]
     pub artist: Option<String>,
+
+    /// Free text search over the title, description, tags and creators.
+    #[serde(default, deserialize_with = "deserialize_search_query")]
+    pub q: Option<String>,
+
+    /// Only art with a source link containing this, like a Discord channel's "channels/[server]/[channel]".
+    #[serde(default, deserialize_with = "sources::deserialize_source_search")]
+    pub source: Option<String>,
+
+    /// Where in the results we are, for keyset pagination. Takes priority over `page` when it can be used.
+    #[serde(default, deserialize_with = "cursor::deserialize_cursor")]
+    pub cursor: Option<ArtCursor>,
+
+    /// How to order the results. If None, see [ArtSearchParameters::get_sort].
+    #[serde(default)]
+    pub sort: Option<ArtSort>,
+
+    /// Seed for ArtSort::Random, so the shuffle stays the same between pages.
+    #[serde(default)]
+    pub seed: Option<i64>,
+
+    /// Only art in this user's favorites. Not taken from the URL, it's set by the favorites gallery based on its path.
+    #[serde(skip)]
+    pub favorited_by: Option<i32>,
+
+    /// Only art with a file of this shape.
+    #[serde(default)]
+    pub orientation: Option<ArtOrientation>,
+
+    /// Only art with a file whose shorter side is at least this many pixels. So 1080 means "1080p or better" in either orientation.
+    #[serde(default)]
+    pub min_resolution: Option<i32>,
+
+    /// Only art with every one of these content warnings.
+    #[serde(
+        default,
+        deserialize_with = "content_warnings::deserialize_content_warnings"
+    )]
+    pub with_warnings: Vec<ContentWarning>,
+    /// Only art with none of these content warnings.
+    #[serde(
+        default,
+        deserialize_with = "content_warnings::deserialize_content_warnings"
+    )]
+    pub without_warnings: Vec<ContentWarning>,
+
+    /// Art with any of these tags is left out. Not taken from the URL, it's the logged in user's blacklist.
+    #[serde(skip)]
+    pub excluded_tags: Vec<String>,
+    /// Art by any of these creators is left out. Not taken from the URL, it's the logged in user's blacklist.
+    /// Expected to be lowercase, since creators are matched case-insensitively.
+    #[serde(skip)]
+    pub excluded_artists: Vec<String>,
+}
+
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ArtOrientation {
+    Landscape,
+    Portrait,
+    Square,
+}
+
+impl ArtOrientation {
+    /// The orientations a user can pick from in the art index, alongside how they're shown.
+    const SELECTABLE: [(ArtOrientation, &'static str); 3] = [
+        (ArtOrientation::Landscape, "Landscape"),
+        (ArtOrientation::Portrait, "Portrait"),
+        (ArtOrientation::Square, "Square"),
+    ];
+
+    /// How it's written in the URL.
+    fn to_uri_value(self) -> &'static str {
+        match self {
+            ArtOrientation::Landscape => "landscape",
+            ArtOrientation::Portrait => "portrait",
+            ArtOrientation::Square => "square",
+        }
+    }
+
+    /// The condition on art_file's width and height for a file to be of this shape.
+    fn to_postgres_condition(self) -> &'static str {
+        match self {
+            ArtOrientation::Landscape => "art_file.width > art_file.height",
+            ArtOrientation::Portrait => "art_file.width < art_file.height",
+            ArtOrientation::Square => "art_file.width = art_file.height",
+        }
+    }
+}
+
+/// The minimum resolutions a user can pick from in the art index, alongside how they're shown.
+const SELECTABLE_MIN_RESOLUTIONS: [(i32, &str); 3] =
+    [(720, "720p+"), (1080, "1080p+"), (2160, "4K+")];
+
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ArtSort {
+    Newest,
+    Oldest,
+    MostCommented,
+    RecentlyModified,
+    Random,
+    /// Best match for the text search first. Only means anything if there's a text search.
+    Relevance,
+}
+
+impl ArtSort {
+    /// The sorts a user can pick from in the art index, alongside how they're shown.
+    const SELECTABLE: [(ArtSort, &'static str); 6] = [
+        (ArtSort::Relevance, "Best Match"),
+        (ArtSort::Newest, "Newest"),
+        (ArtSort::Oldest, "Oldest"),
+        (ArtSort::MostCommented, "Most Commented"),
+        (ArtSort::RecentlyModified, "Recently Modified"),
+        (ArtSort::Random, "Random"),
+    ];
+
+    /// How it's written in the URL.
+    fn to_uri_value(self) -> &'static str {
+        match self {
+            ArtSort::Newest => "newest",
+            ArtSort::Oldest => "oldest",
+            ArtSort::MostCommented => "most_commented",
+            ArtSort::RecentlyModified => "recently_modified",
+            ArtSort::Random => "random",
+            ArtSort::Relevance => "relevance",
+        }
+    }
 }
 
 fn default_page_number() -> i64 {
@@ -231,6 +729,45 @@ fn default_page_number() -> i64 {
 }
 
 impl ArtSearchParameters {
+    /// Swaps out any tag aliases the user searched for with the tags they point to.
+    pub async fn resolve_tag_aliases(&mut self, db_connection: &Object<Manager>) {
+        let searched_tags: Vec<String> = self
+            .tags
+            .iter()
+            .flat_map(|tag_term| tag_term.tag_names())
+            .cloned()
+            .collect();
+
+        let alias_map = tags::get_alias_map(db_connection, &searched_tags).await;
+
+        if alias_map.is_empty() {
+            return;
+        }
+
+        for tag in self
+            .tags
+            .iter_mut()
+            .flat_map(|tag_term| tag_term.tag_names_mut())
+        {
+            if let Some(canonical_tag) = alias_map.get(tag) {
+                *tag = canonical_tag.clone();
+            }
+        }
+    }
+
+    /// Leaves out any art the given user blacklisted, whether they'd rather have it hidden or blurred.
+    pub fn exclude_blacklisted_art(&mut self, user: &Option<User>) {
+        if let Some(user) = user {
+            self.excluded_tags = user.content_filters.blacklisted_tags.clone();
+            self.excluded_artists = user.content_filters.get_lowercase_blacklisted_artists();
+        }
+    }
+
+    /// Whether any art is being left out because of someone's blacklist.
+    pub fn has_exclusions(&self) -> bool {
+        !self.excluded_tags.is_empty() || !self.excluded_artists.is_empty()
+    }
+
     /// Creates the WHERE section of a postgresql statement for these parameters. Modifies a given set of function parameters.
     /// Lifetime of parameter modifications tied to lifetime of struct.
     pub fn get_postgres_where<'a>(
@@ -248,14 +785,83 @@ impl ArtSearchParameters {
             query_conditions.push("NOT is_nsfw".to_string());
         }
 
-        if !self.tags.is_empty() {
-            params.push(&self.tags);
-            query_conditions.push(format!("tags @> ${}", params.len()));
+        for tag_term in &self.tags {
+            query_conditions.push(tag_term.to_postgres_condition(params));
         }
 
         if let Some(artist_name) = &self.artist {
             params.push(artist_name);
-            query_conditions.push(format!("${} ILIKE ANY(creators)", params.len()))
+            query_conditions.push(format!(
+                "lower(${}) = ANY(SELECT lower(creator) FROM unnest(creators) AS creator)",
+                params.len()
+            ))
+        }
+
+        if !self.with_warnings.is_empty() {
+            params.push(&self.with_warnings);
+            query_conditions.push(format!("content_warnings @> ${}", params.len()));
+        }
+
+        if !self.without_warnings.is_empty() {
+            params.push(&self.without_warnings);
+            query_conditions.push(format!("NOT (content_warnings && ${})", params.len()));
+        }
+
+        if !self.excluded_tags.is_empty() {
+            params.push(&self.excluded_tags);
+            query_conditions.push(format!("NOT (tags && ${})", params.len()));
+        }
+
+        if !self.excluded_artists.is_empty() {
+            params.push(&self.excluded_artists);
+            query_conditions.push(format!(
+                "NOT EXISTS (SELECT 1 FROM unnest(creators) AS creator WHERE lower(creator) = ANY(${}))",
+                params.len()
+            ));
+        }
+
+        if let Some(source) = &self.source {
+            params.push(source);
+            query_conditions.push(sources::get_search_condition(params.len()));
+        }
+
+        if let Some(user_id) = &self.favorited_by {
+            params.push(user_id);
+            query_conditions.push(format!(
+                "EXISTS (SELECT 1 FROM art_favorite WHERE art_favorite.art_id = art.id AND art_favorite.user_id = ${})",
+                params.len()
+            ));
+        }
+
+        if let Some(search_query) = &self.q {
+            params.push(search_query);
+            query_conditions.push(format!(
+                "search_vector @@ {}",
+                text_search_tsquery(params.len())
+            ));
+        }
+
+        // A post counts if any of its files fits, since multi-file posts are usually variations of the same thing anyways.
+        // Files we don't know the size of never fit.
+        let mut file_conditions: Vec<String> = Vec::new();
+
+        if let Some(orientation) = &self.orientation {
+            file_conditions.push(orientation.to_postgres_condition().to_string());
+        }
+
+        if let Some(min_resolution) = &self.min_resolution {
+            params.push(min_resolution);
+            file_conditions.push(format!(
+                "LEAST(art_file.width, art_file.height) >= ${}",
+                params.len()
+            ));
+        }
+
+        if !file_conditions.is_empty() {
+            query_conditions.push(format!(
+                "EXISTS (SELECT 1 FROM art_file WHERE art_file.belongs_to = art.id AND {})",
+                file_conditions.join(" AND ")
+            ));
         }
 
         // --- Return ---
@@ -266,12 +872,156 @@ impl ArtSearchParameters {
         }
     }
 
+    /// Creates the ORDER BY section of a postgresql statement for these parameters.
+    /// Every order ends with page_slug, so ties are broken the same way every time and pagination stays stable.
+    pub fn get_postgres_order_by<'a>(
+        &'a self,
+        params: &mut Vec<&'a (dyn tokio_postgres::types::ToSql + Sync)>,
+    ) -> String {
+        match (self.get_sort(), &self.q, &self.seed) {
+            (ArtSort::Relevance, Some(search_query), _) => {
+                params.push(search_query);
+
+                format!(
+                    "ORDER BY ts_rank(search_vector, {}) DESC, creation_date DESC, page_slug",
+                    text_search_tsquery(params.len())
+                )
+            }
+            (ArtSort::Oldest, _, _) => "ORDER BY creation_date ASC, page_slug DESC".to_string(),
+            (ArtSort::MostCommented, _, _) => "ORDER BY (SELECT COUNT(*) FROM art_comment WHERE art_comment.under_post = art.id AND art_comment.deleted_time IS NULL) DESC, creation_date DESC, page_slug".to_string(),
+            (ArtSort::RecentlyModified, _, _) => {
+                "ORDER BY last_modified_date DESC, page_slug".to_string()
+            }
+            // Hashing the slug with the seed shuffles everything, but the same seed always shuffles it the same way.
+            (ArtSort::Random, _, Some(seed)) => {
+                params.push(seed);
+                format!(
+                    "ORDER BY md5(page_slug || ${}::bigint::text), page_slug",
+                    params.len()
+                )
+            }
+            // No seed means nothing to keep it stable with, so just go with RANDOM().
+            (ArtSort::Random, _, None) => "ORDER BY RANDOM()".to_string(),
+            (ArtSort::Newest, _, _) | (ArtSort::Relevance, None, _) => {
+                SQL_ORDER_BY_STATEMENT.to_string()
+            }
+        }
+    }
+
+    /// Which order the results should be in. If the user didn't pick one, text searches go by relevance, everything else by newest.
+    pub fn get_sort(&self) -> ArtSort {
+        match (self.sort, &self.q) {
+            (Some(sort), _) => sort,
+            (None, Some(_)) => ArtSort::Relevance,
+            (None, None) => ArtSort::Newest,
+        }
+    }
+
+    /// If we're shuffling and there's no seed yet, comes up with one, so every link from here on shuffles the same way.
+    pub fn ensure_random_seed(&mut self) {
+        if self.get_sort() == ArtSort::Random && self.seed.is_none() {
+            self.seed = Some(rand::random_range(1..i64::from(u32::MAX)));
+        }
+    }
+
+    /// The sort options for the art index's dropdown, as (URL value, label, whether it's the current one).
+    pub fn get_sort_options(&self) -> Vec<(&'static str, &'static str, bool)> {
+        let current_sort = self.get_sort();
+
+        ArtSort::SELECTABLE
+            .iter()
+            // Best match without anything to match against is just newest.
+            .filter(|(sort, _)| *sort != ArtSort::Relevance || self.q.is_some())
+            .map(|(sort, label)| (sort.to_uri_value(), *label, *sort == current_sort))
+            .collect()
+    }
+
+    /// The orientation options for the art index's dropdown, as (URL value, label, whether it's the current one).
+    pub fn get_orientation_options(&self) -> Vec<(&'static str, &'static str, bool)> {
+        ArtOrientation::SELECTABLE
+            .iter()
+            .map(|(orientation, label)| {
+                (
+                    orientation.to_uri_value(),
+                    *label,
+                    self.orientation == Some(*orientation),
+                )
+            })
+            .collect()
+    }
+
+    /// The minimum resolution options for the art index's dropdown, as (URL value, label, whether it's the current one).
+    pub fn get_min_resolution_options(&self) -> Vec<(i32, &'static str, bool)> {
+        SELECTABLE_MIN_RESOLUTIONS
+            .iter()
+            .map(|(resolution, label)| {
+                (
+                    *resolution,
+                    *label,
+                    self.min_resolution == Some(*resolution),
+                )
+            })
+            .collect()
+    }
+
+    /// The content warning options for the art index's dropdown, as (URL value, label, whether it's the current one).
+    pub fn get_without_warnings_options(&self) -> Vec<(String, String, bool)> {
+        let all_warnings = ContentWarning::ALL.to_vec();
+
+        std::iter::once((
+            content_warnings::to_uri_list(&all_warnings),
+            "No Content Warnings".to_string(),
+            self.without_warnings.len() == all_warnings.len(),
+        ))
+        .chain(ContentWarning::ALL.iter().map(|warning| {
+            (
+                warning.to_uri_value().to_string(),
+                format!("No {}", warning.label()),
+                self.without_warnings == [*warning],
+            )
+        }))
+        .collect()
+    }
+
+    /// Whether the results are in an order that cursors can page through.
+    /// That's only the creation date orders, anything else goes by page number.
+    pub fn supports_keyset_pagination(&self) -> bool {
+        match self.get_sort() {
+            ArtSort::Newest | ArtSort::Oldest => true,
+            // Best match with nothing to match against falls back to newest.
+            ArtSort::Relevance => self.q.is_none(),
+            ArtSort::MostCommented | ArtSort::RecentlyModified | ArtSort::Random => false,
+        }
+    }
+
+    /// A key that's the same for any two searches that'd return the same set of art, regardless of page or order.
+    pub fn get_count_cache_key(&self) -> String {
+        let unordered_search = Self {
+            sort: None,
+            seed: None,
+            ..self.clone()
+        };
+
+        // Blacklists aren't in the URL, but they sure change the count.
+        format!(
+            "{:?}{}{:?}{:?}",
+            self.art_state,
+            unordered_search.to_uri_parameters(false),
+            self.excluded_tags,
+            self.excluded_artists
+        )
+    }
+
     /// Returns how the parameter section of a URL with these parameters should look like.
     pub fn to_uri_parameters(&self, include_page_number: bool) -> String {
         let mut parameters: Vec<String> = Vec::new();
 
-        if include_page_number && self.page > 1 {
-            parameters.push(format!("page={}", self.page));
+        if include_page_number {
+            if let Some(cursor) = &self.cursor {
+                parameters.push(format!("cursor={}", cursor.to_token()));
+            } else if self.page > 1 {
+                parameters.push(format!("page={}", self.page));
+            }
         }
 
         if self.is_nsfw {
@@ -279,11 +1029,57 @@ impl ArtSearchParameters {
         }
 
         if !self.tags.is_empty() {
-            parameters.push(format!("tags={}", self.tags.join(",")));
+            parameters.push(format!(
+                "tags={}",
+                self.tags
+                    .iter()
+                    // Encoded once so commas in names don't split terms, and again for the URL. The commas between them aren't.
+                    .map(|tag_term| urlencoding::encode(&tag_term.to_query_string()).into_owned())
+                    .collect::<Vec<_>>()
+                    .join(",")
+            ));
         }
 
         if let Some(artist_name) = &self.artist {
-            parameters.push(format!("artist={}", artist_name));
+            parameters.push(format!("artist={}", urlencoding::encode(artist_name)));
+        }
+
+        if let Some(search_query) = &self.q {
+            parameters.push(format!("q={}", urlencoding::encode(search_query)));
+        }
+
+        if let Some(source) = &self.source {
+            parameters.push(format!("source={}", urlencoding::encode(source)));
+        }
+
+        if let Some(sort) = self.sort {
+            parameters.push(format!("sort={}", sort.to_uri_value()));
+        }
+
+        if let Some(seed) = self.seed {
+            parameters.push(format!("seed={seed}"));
+        }
+
+        if let Some(orientation) = self.orientation {
+            parameters.push(format!("orientation={}", orientation.to_uri_value()));
+        }
+
+        if let Some(min_resolution) = self.min_resolution {
+            parameters.push(format!("min_resolution={min_resolution}"));
+        }
+
+        if !self.with_warnings.is_empty() {
+            parameters.push(format!(
+                "with_warnings={}",
+                content_warnings::to_uri_list(&self.with_warnings)
+            ));
+        }
+
+        if !self.without_warnings.is_empty() {
+            parameters.push(format!(
+                "without_warnings={}",
+                content_warnings::to_uri_list(&self.without_warnings)
+            ));
         }
 
         // -- Return --
@@ -295,7 +1091,7 @@ impl ArtSearchParameters {
         }
     }
 
-    /// Returns the URI of said parameters, except the NSFW value is flipped, and page count is dropped.
+    /// Returns the URI of said parameters, except the NSFW value is flipped, and page count (and cursor) is dropped.
     /// Primarily for the "nsfw" toggle on the art index.
     pub fn flipped_nsfw_uri_params(&self) -> String {
         Self {
@@ -317,17 +1113,75 @@ impl ArtSearchParameters {
             human_readable_string.push_str(&format!(" by {artist_name}"));
         }
 
-        if !self.tags.is_empty() {
+        let excluded_artists: Vec<_> = self
+            .tags
+            .iter()
+            .filter(|tag_term| matches!(tag_term, TagQueryTerm::ExcludeArtist(_)))
+            .map(TagQueryTerm::to_human_readable)
+            .collect();
+
+        if !excluded_artists.is_empty() {
+            human_readable_string.push_str(&format!(" not by {}", excluded_artists.join(" or ")));
+        }
+
+        let (included_tags, excluded_tags): (Vec<_>, Vec<_>) = self
+            .tags
+            .iter()
+            .filter(|tag_term| !matches!(tag_term, TagQueryTerm::ExcludeArtist(_)))
+            .partition(|tag_term| tag_term.is_positive());
+
+        if !included_tags.is_empty() {
             human_readable_string.push_str(&format!(
                 " tagged with {}",
-                self.tags
+                included_tags
                     .iter()
-                    .map(|tag| format!("\"{tag}\""))
+                    .map(|tag_term| tag_term.to_human_readable())
                     .collect::<Vec<_>>()
                     .join(", ")
             ));
         }
 
+        if !excluded_tags.is_empty() {
+            human_readable_string.push_str(&format!(
+                " without {}",
+                excluded_tags
+                    .iter()
+                    .map(|tag_term| tag_term.to_human_readable())
+                    .collect::<Vec<_>>()
+                    .join(" or ")
+            ));
+        }
+
+        if let Some(search_query) = &self.q {
+            human_readable_string.push_str(&format!(" matching \"{search_query}\""));
+        }
+
+        if let Some(source) = &self.source {
+            human_readable_string.push_str(&format!(" from sources like \"{source}\""));
+        }
+
+        if let Some(orientation) = self.orientation {
+            human_readable_string.push_str(&format!(" in {}", orientation.to_uri_value()));
+        }
+
+        if let Some(min_resolution) = self.min_resolution {
+            human_readable_string.push_str(&format!(" at {min_resolution}p or better"));
+        }
+
+        if !self.with_warnings.is_empty() {
+            human_readable_string.push_str(&format!(
+                " warned for {}",
+                content_warnings::get_warning_labels(&self.with_warnings)
+            ));
+        }
+
+        if !self.without_warnings.is_empty() {
+            human_readable_string.push_str(&format!(
+                " with no warnings for {}",
+                content_warnings::get_warning_labels(&self.without_warnings)
+            ));
+        }
+
         human_readable_string.push('.');
 
         human_readable_string
@@ -342,42 +1196,725 @@ impl Default for ArtSearchParameters {
             is_nsfw: false,
             art_state: PostState::Public,
             artist: None,
+            q: None,
+            source: None,
+            cursor: None,
+            sort: None,
+            seed: None,
+            favorited_by: None,
+            orientation: None,
+            min_resolution: None,
+            with_warnings: Vec::new(),
+            without_warnings: Vec::new(),
+            excluded_tags: Vec::new(),
+            excluded_artists: Vec::new(),
         }
     }
 }
 
-/// Deserializes tags from a single string to vec<string>.
-fn deserialize_tags<'de, D>(deserializer: D) -> Result<Vec<String>, D::Error>
+/// The condition for art on the given side of a cursor, and the order to fetch it in.
+/// The cursor's creation date and page slug are expected in the given parameters.
+/// Only makes sense for sorts that support keyset pagination, see [ArtSearchParameters::supports_keyset_pagination].
+fn get_keyset_condition_and_order(
+    sort: ArtSort,
+    direction: CursorDirection,
+    date_param: usize,
+    slug_param: usize,
+) -> (String, &'static str) {
+    // Oldest-first is just newest-first backwards, so going "after" in it is going "before" in newest-first.
+    let newest_first_direction = match (sort, direction) {
+        (ArtSort::Oldest, CursorDirection::After) => CursorDirection::Before,
+        (ArtSort::Oldest, CursorDirection::Before) => CursorDirection::After,
+        (_, direction) => direction,
+    };
+
+    // Mirrors SQL_ORDER_BY_STATEMENT. Going backwards, we flip the order and flip the results back afterwards.
+    match newest_first_direction {
+        CursorDirection::After => (
+            format!("(creation_date < ${date_param} OR (creation_date = ${date_param} AND page_slug > ${slug_param}))"),
+            SQL_ORDER_BY_STATEMENT,
+        ),
+        CursorDirection::Before => (
+            format!("(creation_date > ${date_param} OR (creation_date = ${date_param} AND page_slug < ${slug_param}))"),
+            "ORDER BY creation_date ASC, page_slug DESC",
+        ),
+    }
+}
+
+/// Deserializes tags from a single string to a list of query terms. See [TagQueryTerm] for the syntax.
+fn deserialize_tags<'de, D>(deserializer: D) -> Result<Vec<TagQueryTerm>, D::Error>
 where
     D: Deserializer<'de>,
 {
     // Expected format is a list of lowercase, numbers, and dashes, with a comma delimiter.
     let s = String::deserialize(deserializer)?;
 
-    if s.is_empty() {
-        return Ok(Vec::new());
-    }
+    Ok(tag_query::parse_query(&s))
+}
 
-    Ok(s.split(',')
-        .map(|item| item.trim().to_lowercase())
-        .filter(|item| !item.is_empty())
-        .collect())
+/// How the user's text search, given as the [param_number]th parameter, gets turned into a tsquery.
+/// websearch_to_tsquery never errors on user input, and supports "quotes", OR and -exclusions, which is nice.
+fn text_search_tsquery(param_number: usize) -> String {
+    format!("websearch_to_tsquery('english', ${param_number})")
+}
+
+/// Deserializes the text search query, treating an empty search as no search at all.
+fn deserialize_search_query<'de, D>(deserializer: D) -> Result<Option<String>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let s = String::deserialize(deserializer)?;
+    let trimmed = s.trim();
+
+    if trimmed.is_empty() {
+        Ok(None)
+    } else {
+        Ok(Some(trimmed.to_string()))
+    }
 }
 
 #[derive(Debug, Clone)]
 pub struct Comment {
+    pub id: i32,
+    pub under_post: i32,
+    pub reply_to: Option<i32>,
     pub posting_user: Option<User>, // None means a deleted user.
-    pub contents: String,
+    pub contents: Option<String>,   // None means the comment itself was deleted.
+    /// The contents as HTML, already sanitized. Safe to put on the page as-is.
+    pub rendered_contents: Option<String>,
     pub posting_time: DateTime<Utc>,
+    pub edited_time: Option<DateTime<Utc>>,
+    /// How many replies deep this comment is, 0 being directly under the post. Only set by PageArt::get_comments.
+    pub depth: usize,
 }
 
 impl Comment {
+    pub async fn get_by_id(db_connection: &Object<Manager>, comment_id: i32) -> Option<Self> {
+        let requested_comment = db_connection
+            .query_opt("SELECT * FROM art_comment WHERE id=$1", &[&comment_id])
+            .await
+            .ok()??;
+
+        Some(Self::from_db_row(&requested_comment, db_connection).await)
+    }
+
     /// Converts a DB row with the relevant info to a Comment struct.
     async fn from_db_row(row: &Row, db_connection: &Object<Manager>) -> Self {
+        let poster_id: Option<i32> = row.get("poster_id");
+        let posting_user = match poster_id {
+            Some(poster_id) => User::get_by_id(db_connection, &poster_id).await,
+            None => None,
+        };
+
+        let contents: Option<String> = row.get("contents");
+
+        // Comments posted before we stored the HTML need to be rendered here. Slower, but only until they're edited.
+        let rendered_contents = row
+            .get::<_, Option<String>>("rendered_contents")
+            .or_else(|| {
+                contents
+                    .as_deref()
+                    .map(super::comment::render_comment_contents)
+            });
+
         Self {
-            posting_user: User::get_by_id(db_connection, &row.get("poster_id")).await,
+            id: row.get("id"),
+            under_post: row.get("under_post"),
+            reply_to: row.get("reply_to"),
+            posting_user,
             posting_time: row.get("posting_time"),
-            contents: row.get("contents"),
+            edited_time: row.get("edited_time"),
+            contents,
+            rendered_contents,
+            depth: 0,
+        }
+    }
+
+    pub fn is_deleted(&self) -> bool {
+        self.contents.is_none()
+    }
+}
+
+impl UsermadePost for Comment {
+    fn can_be_modified_by(&self, user: &User) -> bool {
+        user.user_type.permissions().can_modify_others_content
+            || self
+                .posting_user
+                .as_ref()
+                .is_some_and(|posting_user| posting_user == user)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::user::UserType;
+    use axum::extract::Query;
+    use http::Uri;
+
+    /// Parses the search out of an art index URL, the same way the handlers get it.
+    fn search_from_uri(uri: &str) -> ArtSearchParameters {
+        let uri: Uri = uri.parse().expect("test URI should be valid");
+
+        Query::<ArtSearchParameters>::try_from_uri(&uri)
+            .expect("test URI should deserialize")
+            .0
+    }
+
+    /// The WHERE section for the given search, and how many parameters it took.
+    fn where_of(search_params: &ArtSearchParameters) -> (String, usize) {
+        let mut params: Vec<&(dyn tokio_postgres::types::ToSql + Sync)> = Vec::new();
+        let query_where = search_params.get_postgres_where(&mut params);
+
+        (query_where, params.len())
+    }
+
+    /// The ORDER BY section for the given search, and how many parameters it took.
+    fn order_by_of(search_params: &ArtSearchParameters) -> (String, usize) {
+        let mut params: Vec<&(dyn tokio_postgres::types::ToSql + Sync)> = Vec::new();
+        let order_by = search_params.get_postgres_order_by(&mut params);
+
+        (order_by, params.len())
+    }
+
+    fn sorted_by(sort: Option<ArtSort>, q: Option<&str>) -> ArtSearchParameters {
+        ArtSearchParameters {
+            sort,
+            q: q.map(str::to_string),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn default_sort_depends_on_the_text_search() {
+        assert_eq!(sorted_by(None, None).get_sort(), ArtSort::Newest);
+        assert_eq!(sorted_by(None, Some("kate")).get_sort(), ArtSort::Relevance);
+        assert_eq!(
+            sorted_by(Some(ArtSort::Oldest), Some("kate")).get_sort(),
+            ArtSort::Oldest
+        );
+    }
+
+    #[test]
+    fn sorts_round_trip_through_the_url() {
+        for (sort, _) in ArtSort::SELECTABLE {
+            let search_params = sorted_by(Some(sort), None);
+
+            assert_eq!(
+                search_from_uri(&format!("/art{}", search_params.to_uri_parameters(false))).sort,
+                Some(sort)
+            );
+        }
+
+        assert!(Query::<ArtSearchParameters>::try_from_uri(
+            &"/art?sort=sideways".parse::<Uri>().unwrap()
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn sorts_map_to_their_order_by() {
+        let cases = [
+            (sorted_by(None, None), SQL_ORDER_BY_STATEMENT),
+            (
+                sorted_by(Some(ArtSort::Newest), None),
+                SQL_ORDER_BY_STATEMENT,
+            ),
+            // Best match with nothing to match against is just newest.
+            (
+                sorted_by(Some(ArtSort::Relevance), None),
+                SQL_ORDER_BY_STATEMENT,
+            ),
+            (
+                sorted_by(Some(ArtSort::Oldest), None),
+                "ORDER BY creation_date ASC, page_slug DESC",
+            ),
+            (
+                sorted_by(Some(ArtSort::RecentlyModified), None),
+                "ORDER BY last_modified_date DESC, page_slug",
+            ),
+            (sorted_by(Some(ArtSort::Random), None), "ORDER BY RANDOM()"),
+        ];
+
+        for (search_params, expected_order_by) in cases {
+            assert_eq!(
+                order_by_of(&search_params),
+                (expected_order_by.to_string(), 0),
+                "{:?}",
+                search_params.sort
+            );
+        }
+
+        let (order_by, _) = order_by_of(&sorted_by(Some(ArtSort::MostCommented), None));
+        assert!(order_by.starts_with("ORDER BY (SELECT COUNT(*) FROM art_comment"));
+        assert!(order_by.ends_with("creation_date DESC, page_slug"));
+
+        let (order_by, param_count) = order_by_of(&sorted_by(None, Some("kate")));
+        assert_eq!(param_count, 1);
+        assert!(order_by.starts_with("ORDER BY ts_rank(search_vector, websearch_to_tsquery("));
+    }
+
+    #[test]
+    fn seeded_random_stays_put() {
+        let mut search_params = sorted_by(Some(ArtSort::Random), None);
+        search_params.ensure_random_seed();
+        let seed = search_params.seed.expect("random sorts should get a seed");
+
+        // Once there's a seed, it's kept.
+        search_params.ensure_random_seed();
+        assert_eq!(search_params.seed, Some(seed));
+
+        let (order_by, param_count) = order_by_of(&search_params);
+        assert_eq!(param_count, 1);
+        assert!(order_by.starts_with("ORDER BY md5(page_slug || $1"));
+
+        // Other sorts don't need one.
+        let mut search_params = sorted_by(Some(ArtSort::Newest), None);
+        search_params.ensure_random_seed();
+        assert_eq!(search_params.seed, None);
+    }
+
+    #[test]
+    fn only_date_orders_use_keyset_pagination() {
+        let cases = [
+            (sorted_by(None, None), true),
+            (sorted_by(Some(ArtSort::Oldest), None), true),
+            (sorted_by(Some(ArtSort::Relevance), None), true),
+            (sorted_by(None, Some("kate")), false),
+            (sorted_by(Some(ArtSort::Newest), Some("kate")), true),
+            (sorted_by(Some(ArtSort::MostCommented), None), false),
+            (sorted_by(Some(ArtSort::RecentlyModified), None), false),
+            (sorted_by(Some(ArtSort::Random), None), false),
+        ];
+
+        for (search_params, supports_keyset) in cases {
+            assert_eq!(
+                search_params.supports_keyset_pagination(),
+                supports_keyset,
+                "{:?} {:?}",
+                search_params.sort,
+                search_params.q
+            );
+        }
+    }
+
+    #[test]
+    fn keyset_direction_follows_the_sort() {
+        let older_than_cursor =
+            "(creation_date < $2 OR (creation_date = $2 AND page_slug > $3))".to_string();
+        let newer_than_cursor =
+            "(creation_date > $2 OR (creation_date = $2 AND page_slug < $3))".to_string();
+        let oldest_first = "ORDER BY creation_date ASC, page_slug DESC";
+
+        let cases = [
+            (
+                ArtSort::Newest,
+                CursorDirection::After,
+                (older_than_cursor.clone(), SQL_ORDER_BY_STATEMENT),
+            ),
+            (
+                ArtSort::Newest,
+                CursorDirection::Before,
+                (newer_than_cursor.clone(), oldest_first),
+            ),
+            // Oldest first is newest first flipped around.
+            (
+                ArtSort::Oldest,
+                CursorDirection::After,
+                (newer_than_cursor.clone(), oldest_first),
+            ),
+            (
+                ArtSort::Oldest,
+                CursorDirection::Before,
+                (older_than_cursor.clone(), SQL_ORDER_BY_STATEMENT),
+            ),
+            (
+                ArtSort::Relevance,
+                CursorDirection::After,
+                (older_than_cursor, SQL_ORDER_BY_STATEMENT),
+            ),
+        ];
+
+        for (sort, direction, expected) in cases {
+            assert_eq!(
+                get_keyset_condition_and_order(sort, direction, 2, 3),
+                expected,
+                "{sort:?} {direction:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn sort_options_mark_the_current_sort() {
+        let options = sorted_by(None, None).get_sort_options();
+        assert!(!options.iter().any(|(value, _, _)| *value == "relevance"));
+        assert_eq!(
+            options
+                .iter()
+                .filter(|(_, _, is_current)| *is_current)
+                .map(|(value, _, _)| *value)
+                .collect::<Vec<_>>(),
+            vec!["newest"]
+        );
+
+        let options = sorted_by(None, Some("kate")).get_sort_options();
+        assert_eq!(options.len(), ArtSort::SELECTABLE.len());
+        assert!(options.contains(&("relevance", "Best Match", true)));
+    }
+
+    #[test]
+    fn count_cache_ignores_the_order() {
+        let newest = sorted_by(Some(ArtSort::Newest), Some("kate"));
+        let random = ArtSearchParameters {
+            seed: Some(1234),
+            ..sorted_by(Some(ArtSort::Random), Some("kate"))
+        };
+
+        assert_eq!(newest.get_count_cache_key(), random.get_count_cache_key());
+        assert_ne!(
+            newest.get_count_cache_key(),
+            sorted_by(Some(ArtSort::Newest), Some("fynn")).get_count_cache_key()
+        );
+    }
+
+    #[test]
+    fn favorites_only_come_from_the_gallery() {
+        // The gallery sets it from its own path, a query parameter on the regular index does nothing.
+        assert_eq!(search_from_uri("/art?favorited_by=3").favorited_by, None);
+
+        let search_params = ArtSearchParameters {
+            favorited_by: Some(3),
+            ..Default::default()
+        };
+        assert!(!search_params
+            .to_uri_parameters(true)
+            .contains("favorited_by"));
+
+        let (query_where, param_count) = where_of(&search_params);
+        assert!(query_where.contains(&format!(
+            "art_favorite.art_id = art.id AND art_favorite.user_id = ${param_count}"
+        )));
+    }
+
+    #[test]
+    fn text_search_parses_from_the_url() {
+        assert_eq!(
+            search_from_uri("/art?q=%20kate%20fighting%20").q.as_deref(),
+            Some("kate fighting")
+        );
+        assert_eq!(search_from_uri("/art?q=%20%20").q, None);
+        assert_eq!(search_from_uri("/art").q, None);
+    }
+
+    #[test]
+    fn text_search_round_trips_through_the_url() {
+        for q in ["kate", "\"kate fynn\" -casti", "50% & more?=yes#no"] {
+            let search_params = ArtSearchParameters {
+                q: Some(q.to_string()),
+                ..Default::default()
+            };
+
+            assert_eq!(
+                search_from_uri(&format!("/art{}", search_params.to_uri_parameters(false)))
+                    .q
+                    .as_deref(),
+                Some(q)
+            );
         }
     }
+
+    #[test]
+    fn text_search_goes_through_websearch_to_tsquery() {
+        let (query_where, param_count) = where_of(&ArtSearchParameters {
+            q: Some("kate".to_string()),
+            ..Default::default()
+        });
+
+        assert!(query_where.contains(&format!(
+            "search_vector @@ websearch_to_tsquery('english', ${param_count})"
+        )));
+
+        let (query_where, _) = where_of(&ArtSearchParameters::default());
+        assert!(!query_where.contains("search_vector"));
+    }
+
+    fn art_with_warnings(content_warnings: Vec<ContentWarning>) -> BaseArt {
+        BaseArt {
+            id: 1,
+            title: "Test".to_string(),
+            creators: vec!["someone".to_string()],
+            thumbnail_key: "art/1/thumbnail".to_string(),
+            slug: "test".to_string(),
+            is_nsfw: false,
+            content_warnings,
+        }
+    }
+
+    fn user_fine_with(shown_content_warnings: Vec<ContentWarning>) -> Option<User> {
+        Some(User {
+            id: 1,
+            user_type: UserType::Normal,
+            display_name: "Tester".to_string(),
+            profile_pic_s3_key: None,
+            last_modified: Utc::now(),
+            creator_name: None,
+            creation_time: Utc::now(),
+            content_filters: ContentFilters {
+                shown_content_warnings,
+                ..Default::default()
+            },
+        })
+    }
+
+    /// A comment posted [minute] minutes into the day. None contents means it was deleted.
+    fn comment(id: i32, reply_to: Option<i32>, minute: u32, contents: Option<&str>) -> Comment {
+        Comment {
+            id,
+            under_post: 1,
+            reply_to,
+            posting_user: None,
+            contents: contents.map(str::to_string),
+            rendered_contents: contents.map(str::to_string),
+            posting_time: NaiveDate::from_ymd_opt(2025, 1, 1)
+                .unwrap()
+                .and_hms_opt(0, minute, 0)
+                .unwrap()
+                .and_utc(),
+            edited_time: None,
+            depth: 0,
+        }
+    }
+
+    /// The (id, depth) of each comment, in order.
+    fn thread_shape(comments: Vec<Comment>) -> Vec<(i32, usize)> {
+        PageArt::thread_comments(comments)
+            .iter()
+            .map(|comment| (comment.id, comment.depth))
+            .collect()
+    }
+
+    #[test]
+    fn replies_follow_their_parents_in_posting_order() {
+        // Given out of order, like the DB might.
+        let comments = vec![
+            comment(4, Some(1), 4, Some("second reply to 1")),
+            comment(2, None, 2, Some("second top level")),
+            comment(5, Some(3), 5, Some("reply to the reply")),
+            comment(1, None, 1, Some("first top level")),
+            comment(3, Some(1), 3, Some("first reply to 1")),
+            comment(6, Some(2), 6, Some("reply to 2")),
+        ];
+
+        assert_eq!(
+            thread_shape(comments),
+            vec![(1, 0), (3, 1), (5, 2), (4, 1), (2, 0), (6, 1)]
+        );
+    }
+
+    #[test]
+    fn deleted_comments_only_stay_if_someone_replied() {
+        let comments = vec![
+            // Deleted, but has a reply, so it stays as a placeholder.
+            comment(1, None, 1, None),
+            comment(2, Some(1), 2, Some("reply")),
+            // Deleted with nothing under it, gone.
+            comment(3, None, 3, None),
+            // Deleted, and everything under it is deleted too, so the whole chain goes.
+            comment(4, None, 4, None),
+            comment(5, Some(4), 5, None),
+            comment(6, Some(5), 6, None),
+            // Deleted in the middle of a chain that's still alive.
+            comment(7, None, 7, Some("top")),
+            comment(8, Some(7), 8, None),
+            comment(9, Some(8), 9, Some("still here")),
+        ];
+
+        assert_eq!(
+            thread_shape(comments),
+            vec![(1, 0), (2, 1), (7, 0), (8, 1), (9, 2)]
+        );
+    }
+
+    #[test]
+    fn no_comments_no_thread() {
+        assert!(PageArt::thread_comments(Vec::new()).is_empty());
+        assert!(PageArt::thread_comments(vec![comment(1, None, 1, None)]).is_empty());
+    }
+
+    #[test]
+    fn logged_out_users_get_every_warning() {
+        let art = art_with_warnings(vec![ContentWarning::Gore, ContentWarning::LoreSpoilers]);
+
+        assert_eq!(
+            art.get_unaccepted_warnings(&None),
+            vec![ContentWarning::Gore, ContentWarning::LoreSpoilers]
+        );
+    }
+
+    #[test]
+    fn accepted_warnings_are_left_out() {
+        let art = art_with_warnings(vec![
+            ContentWarning::Gore,
+            ContentWarning::FlashingImagery,
+            ContentWarning::LoreSpoilers,
+        ]);
+
+        assert_eq!(
+            art.get_unaccepted_warnings(&user_fine_with(vec![ContentWarning::FlashingImagery])),
+            vec![ContentWarning::Gore, ContentWarning::LoreSpoilers]
+        );
+        assert!(art
+            .get_unaccepted_warnings(&user_fine_with(ContentWarning::ALL.to_vec()))
+            .is_empty());
+    }
+
+    #[test]
+    fn art_without_warnings_never_gets_covered() {
+        let art = art_with_warnings(Vec::new());
+
+        assert!(art.get_unaccepted_warnings(&None).is_empty());
+        assert!(art
+            .get_unaccepted_warnings(&user_fine_with(Vec::new()))
+            .is_empty());
+    }
+
+    #[test]
+    fn artist_search_ignores_case() {
+        let search_params = search_from_uri("/art?artist=Kate");
+        let (query_where, param_count) = where_of(&search_params);
+
+        assert_eq!(search_params.artist.as_deref(), Some("Kate"));
+        assert!(query_where.contains(&format!(
+            "lower(${param_count}) = ANY(SELECT lower(creator) FROM unnest(creators) AS creator)"
+        )));
+
+        let (query_where, _) = where_of(&ArtSearchParameters::default());
+        assert!(!query_where.contains("creator"));
+    }
+
+    #[test]
+    fn artists_round_trip_through_the_url() {
+        for artist_name in ["kate", "Some Artist", "a&b=c", "50% off"] {
+            let search_params = ArtSearchParameters {
+                artist: Some(artist_name.to_string()),
+                ..Default::default()
+            };
+
+            assert_eq!(
+                search_from_uri(&format!("/art{}", search_params.to_uri_parameters(false)))
+                    .artist
+                    .as_deref(),
+                Some(artist_name)
+            );
+        }
+    }
+
+    #[test]
+    fn artists_get_their_own_count_cache_key() {
+        let by_kate = ArtSearchParameters {
+            artist: Some("kate".to_string()),
+            ..Default::default()
+        };
+
+        assert_ne!(
+            by_kate.get_count_cache_key(),
+            ArtSearchParameters::default().get_count_cache_key()
+        );
+    }
+
+    #[test]
+    fn random_offsets_cover_every_match() {
+        assert_eq!(get_random_offset(0), None);
+        assert_eq!(get_random_offset(-1), None);
+        assert_eq!(get_random_offset(1), Some(0));
+
+        let mut seen = [false; 5];
+        for _ in 0..1000 {
+            let offset = get_random_offset(5).unwrap();
+            assert!((0..5).contains(&offset), "{offset}");
+            seen[offset as usize] = true;
+        }
+        assert_eq!(seen, [true; 5]);
+    }
+
+    #[test]
+    fn random_art_stays_in_the_search() {
+        let search_params = search_from_uri("/art/random?tags=kate&is_nsfw=true");
+        let mut params: Vec<&(dyn tokio_postgres::types::ToSql + Sync)> = Vec::new();
+        let query = get_random_art_query(&search_params, &mut params);
+
+        assert!(query.contains("tags @> ARRAY[$"));
+        assert!(query.contains("is_nsfw") && !query.contains("NOT is_nsfw"));
+        assert!(query.contains("post_state = $"));
+        // The offset goes right after the search's own parameters.
+        assert!(query.ends_with(&format!("ORDER BY id OFFSET ${} LIMIT 1", params.len() + 1)));
+    }
+
+    #[test]
+    fn random_art_is_sfw_by_default() {
+        let search_params = search_from_uri("/art/random");
+        let mut params: Vec<&(dyn tokio_postgres::types::ToSql + Sync)> = Vec::new();
+        let query = get_random_art_query(&search_params, &mut params);
+
+        assert!(query.contains("NOT is_nsfw"));
+        assert!(query.ends_with(&format!("ORDER BY id OFFSET ${} LIMIT 1", params.len() + 1)));
+    }
+
+    fn user_blacklisting(tags: &[&str], artists: &[&str]) -> Option<User> {
+        let mut user = user_fine_with(Vec::new())?;
+        user.content_filters.blacklisted_tags = tags.iter().map(|tag| tag.to_string()).collect();
+        user.content_filters.blacklisted_artists =
+            artists.iter().map(|artist| artist.to_string()).collect();
+
+        Some(user)
+    }
+
+    #[test]
+    fn blacklists_only_come_from_the_user() {
+        let mut search_params = search_from_uri("/art?tags=kate");
+        search_params.exclude_blacklisted_art(&None);
+        assert!(!search_params.has_exclusions());
+
+        search_params.exclude_blacklisted_art(&user_blacklisting(&[], &[]));
+        assert!(!search_params.has_exclusions());
+
+        search_params.exclude_blacklisted_art(&user_blacklisting(&["gore"], &["Kate", "KATE"]));
+        assert!(search_params.has_exclusions());
+        assert_eq!(search_params.excluded_tags, vec!["gore".to_string()]);
+        assert_eq!(search_params.excluded_artists, vec!["kate".to_string()]);
+    }
+
+    #[test]
+    fn blacklisted_art_is_left_out_of_the_search() {
+        let mut search_params = ArtSearchParameters::default();
+        search_params.exclude_blacklisted_art(&user_blacklisting(&["gore"], &["Kate"]));
+        let (query_where, _) = where_of(&search_params);
+
+        assert!(query_where.contains("NOT (tags && $"));
+        assert!(query_where.contains(
+            "NOT EXISTS (SELECT 1 FROM unnest(creators) AS creator WHERE lower(creator) = ANY($"
+        ));
+
+        let (query_where, _) = where_of(&ArtSearchParameters::default());
+        assert!(!query_where.contains("NOT (tags &&"));
+        assert!(!query_where.contains("NOT EXISTS"));
+    }
+
+    #[test]
+    fn blacklists_stay_out_of_the_url_but_not_the_count() {
+        let mut search_params = ArtSearchParameters::default();
+        search_params.exclude_blacklisted_art(&user_blacklisting(&["gore"], &["Kate"]));
+
+        let uri_parameters = search_params.to_uri_parameters(false);
+        assert!(!uri_parameters.contains("gore"), "{uri_parameters}");
+        assert!(
+            !uri_parameters.to_lowercase().contains("kate"),
+            "{uri_parameters}"
+        );
+
+        assert_ne!(
+            search_params.get_count_cache_key(),
+            ArtSearchParameters::default().get_count_cache_key()
+        );
+    }
 }
@@ -0,0 +1,315 @@
+//! The little query language used in the art search's `tags` parameter.
+//! Terms are comma-separated and each percent-encoded, so names with commas in them survive. Each one is one of:
+//! - `tag`, the art must have this tag.
+//! - `-tag`, the art must NOT have this tag.
+//! - `tag1|tag2|tag3`, the art must have at least one of these tags.
+//! - `-artist:name`, the art must not be made by this artist.
+
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TagQueryTerm {
+    Include(String),
+    Exclude(String),
+    AnyOf(Vec<String>),
+    ExcludeArtist(String),
+}
+
+const TERM_DELIMITER: char = ',';
+const EXCLUSION_PREFIX: char = '-';
+const OR_DELIMITER: char = '|';
+const ARTIST_PREFIX: &str = "artist:";
+
+impl TagQueryTerm {
+    /// Parses a single term of the query. Returns None if the term is meaningless (e.g. just "-" or "|").
+    /// Assumes the term was already trimmed and lowercased.
+    pub fn parse(term: &str) -> Option<Self> {
+        if let Some(excluded) = term.strip_prefix(EXCLUSION_PREFIX) {
+            let excluded = excluded.trim();
+
+            if let Some(artist_name) = excluded.strip_prefix(ARTIST_PREFIX) {
+                let artist_name = artist_name.trim();
+                return (!artist_name.is_empty())
+                    .then(|| Self::ExcludeArtist(artist_name.to_string()));
+            }
+
+            return (!excluded.is_empty()).then(|| Self::Exclude(excluded.to_string()));
+        }
+
+        if term.contains(OR_DELIMITER) {
+            let mut options: Vec<String> = Vec::new();
+
+            for option in term.split(OR_DELIMITER).map(str::trim) {
+                if !option.is_empty() && !options.iter().any(|existing| existing == option) {
+                    options.push(option.to_string());
+                }
+            }
+
+            // "a|" is just "a", no need to make it an OR group.
+            return match options.len() {
+                0 => None,
+                1 => Some(Self::Include(options.remove(0))),
+                _ => Some(Self::AnyOf(options)),
+            };
+        }
+
+        (!term.is_empty()).then(|| Self::Include(term.to_string()))
+    }
+
+    /// Creates the postgres condition for this term, pushing whatever it needs into the given params.
+    /// Lifetime of parameter modifications tied to lifetime of the term.
+    pub fn to_postgres_condition<'a>(
+        &'a self,
+        params: &mut Vec<&'a (dyn tokio_postgres::types::ToSql + Sync)>,
+    ) -> String {
+        match self {
+            Self::Include(tag) => {
+                params.push(tag);
+                format!("tags @> ARRAY[${}::text]", params.len())
+            }
+            Self::Exclude(tag) => {
+                params.push(tag);
+                format!("NOT (tags @> ARRAY[${}::text])", params.len())
+            }
+            Self::AnyOf(tags) => {
+                params.push(tags);
+                format!("tags && ${}", params.len())
+            }
+            Self::ExcludeArtist(artist_name) => {
+                params.push(artist_name);
+                format!(
+                    "NOT (lower(${}) = ANY(SELECT lower(creator) FROM unnest(creators) AS creator))",
+                    params.len()
+                )
+            }
+        }
+    }
+
+    /// Returns all the tag names in this term.
+    pub fn tag_names(&self) -> Vec<&String> {
+        match self {
+            Self::Include(tag) | Self::Exclude(tag) => vec![tag],
+            Self::AnyOf(tags) => tags.iter().collect(),
+            Self::ExcludeArtist(_) => Vec::new(),
+        }
+    }
+
+    /// Same as [Self::tag_names], but mutable, so they can be swapped for their canonical names.
+    pub fn tag_names_mut(&mut self) -> Vec<&mut String> {
+        match self {
+            Self::Include(tag) | Self::Exclude(tag) => vec![tag],
+            Self::AnyOf(tags) => tags.iter_mut().collect(),
+            Self::ExcludeArtist(_) => Vec::new(),
+        }
+    }
+
+    /// Writes the term the way [parse_query] reads it back, percent-encoded so it can be joined with other terms.
+    pub fn to_query_string(&self) -> String {
+        urlencoding::encode(&self.to_string()).into_owned()
+    }
+
+    /// Whether this term narrows down by tags the art has, as opposed to ones it doesn't.
+    pub fn is_positive(&self) -> bool {
+        matches!(self, Self::Include(_) | Self::AnyOf(_))
+    }
+
+    /// How this term would be described in a sentence, e.g. `"kate" or "fynn"`.
+    pub fn to_human_readable(&self) -> String {
+        match self {
+            Self::Include(tag) | Self::Exclude(tag) => format!("\"{tag}\""),
+            Self::AnyOf(tags) => tags
+                .iter()
+                .map(|tag| format!("\"{tag}\""))
+                .collect::<Vec<_>>()
+                .join(" or "),
+            Self::ExcludeArtist(artist_name) => artist_name.clone(),
+        }
+    }
+}
+
+/// Parses a whole query, in the format [TagQueryTerm::to_query_string] writes. Meaningless and repeated terms are dropped.
+/// Terms without any percent-encoding read the same either way, so hand-written queries like "kate,-gore" still work.
+pub fn parse_query(query: &str) -> Vec<TagQueryTerm> {
+    let mut tag_terms: Vec<TagQueryTerm> = Vec::new();
+
+    for term in query.split(TERM_DELIMITER) {
+        // Anything that doesn't decode to valid UTF-8 was never encoded by us, so take it as it is.
+        let term = urlencoding::decode(term).unwrap_or(std::borrow::Cow::Borrowed(term));
+
+        if let Some(tag_term) = TagQueryTerm::parse(&term.trim().to_lowercase()) {
+            if !tag_terms.contains(&tag_term) {
+                tag_terms.push(tag_term);
+            }
+        }
+    }
+
+    tag_terms
+}
+
+/// Writes the term the same way the user would. See [TagQueryTerm::to_query_string] for putting it in a query.
+impl fmt::Display for TagQueryTerm {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Include(tag) => write!(f, "{tag}"),
+            Self::Exclude(tag) => write!(f, "{EXCLUSION_PREFIX}{tag}"),
+            Self::AnyOf(tags) => write!(f, "{}", tags.join(&OR_DELIMITER.to_string())),
+            Self::ExcludeArtist(artist_name) => {
+                write!(f, "{EXCLUSION_PREFIX}{ARTIST_PREFIX}{artist_name}")
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::TagQueryTerm::{self, *};
+    use crate::art::structs::ArtSearchParameters;
+    use axum::extract::Query;
+    use http::Uri;
+
+    fn strings(tags: &[&str]) -> Vec<String> {
+        tags.iter().map(|tag| tag.to_string()).collect()
+    }
+
+    /// Parses the tags out of an art index URL, the same way the handlers get them.
+    fn tags_from_uri(uri: &str) -> Vec<TagQueryTerm> {
+        let uri: Uri = uri.parse().expect("test URI should be valid");
+
+        Query::<ArtSearchParameters>::try_from_uri(&uri)
+            .expect("test URI should deserialize")
+            .0
+            .tags
+    }
+
+    #[test]
+    fn parses_every_kind_of_term() {
+        assert_eq!(TagQueryTerm::parse("kate"), Some(Include("kate".into())));
+        assert_eq!(TagQueryTerm::parse("-gore"), Some(Exclude("gore".into())));
+        assert_eq!(
+            TagQueryTerm::parse("kate|fynn|casti"),
+            Some(AnyOf(strings(&["kate", "fynn", "casti"])))
+        );
+        assert_eq!(
+            TagQueryTerm::parse("-artist:some_artist"),
+            Some(ExcludeArtist("some_artist".into()))
+        );
+    }
+
+    #[test]
+    fn meaningless_terms_are_dropped() {
+        for term in ["", "-", "|", "||", "|||", "-artist:", "-artist: ", "- "] {
+            assert_eq!(TagQueryTerm::parse(term), None, "term {term:?}");
+        }
+    }
+
+    #[test]
+    fn or_groups_are_cleaned_up() {
+        // Empty options and repeats go away, and a group of one is just a tag.
+        assert_eq!(
+            TagQueryTerm::parse("kate||fynn"),
+            Some(AnyOf(strings(&["kate", "fynn"])))
+        );
+        assert_eq!(
+            TagQueryTerm::parse("kate | fynn | kate"),
+            Some(AnyOf(strings(&["kate", "fynn"])))
+        );
+        assert_eq!(TagQueryTerm::parse("kate|"), Some(Include("kate".into())));
+        assert_eq!(
+            TagQueryTerm::parse("|kate|kate"),
+            Some(Include("kate".into()))
+        );
+    }
+
+    #[test]
+    fn exclusions_are_trimmed() {
+        assert_eq!(TagQueryTerm::parse("- gore"), Some(Exclude("gore".into())));
+        assert_eq!(
+            TagQueryTerm::parse("-artist: some artist "),
+            Some(ExcludeArtist("some artist".into()))
+        );
+        // Only the first dash is the exclusion, the rest is the tag.
+        assert_eq!(TagQueryTerm::parse("--gore"), Some(Exclude("-gore".into())));
+    }
+
+    #[test]
+    fn terms_survive_being_written_back_out() {
+        let terms = [
+            Include("kate".into()),
+            Exclude("gore".into()),
+            AnyOf(strings(&["kate", "fynn"])),
+            ExcludeArtist("some artist".into()),
+        ];
+
+        for term in terms {
+            assert_eq!(TagQueryTerm::parse(&term.to_string()), Some(term));
+        }
+    }
+
+    #[test]
+    fn query_parses_from_the_url() {
+        assert_eq!(
+            tags_from_uri("/art?tags=Kate,-gore,kate%7Cfynn,,-artist:Some_Artist,kate"),
+            vec![
+                Include("kate".into()),
+                Exclude("gore".into()),
+                AnyOf(strings(&["kate", "fynn"])),
+                ExcludeArtist("some_artist".into()),
+            ]
+        );
+
+        assert!(tags_from_uri("/art?tags=").is_empty());
+        assert!(tags_from_uri("/art?tags=-,|,-artist:").is_empty());
+    }
+
+    #[test]
+    fn names_with_commas_round_trip() {
+        let terms = vec![
+            ExcludeArtist("smith, john".into()),
+            AnyOf(strings(&["a,b", "c"])),
+            Include("100%".into()),
+            Exclude("kate".into()),
+        ];
+
+        let query = terms
+            .iter()
+            .map(TagQueryTerm::to_query_string)
+            .collect::<Vec<_>>()
+            .join(",");
+        assert_eq!(super::parse_query(&query), terms);
+
+        let search_params = ArtSearchParameters {
+            tags: terms.clone(),
+            ..Default::default()
+        };
+        assert_eq!(
+            tags_from_uri(&format!("/art{}", search_params.to_uri_parameters(false))),
+            terms
+        );
+    }
+
+    #[test]
+    fn query_round_trips_through_the_url() {
+        for tags_param in [
+            "kate",
+            "kate,-gore",
+            "kate|fynn,-gore",
+            "-artist:some_artist",
+            // Stuff that means something in a URL has to come back out the same.
+            "-artist:a%26b%20%2B%20c%3Dd",
+            "-artist:50%25%20off,kate%7Cfynn%7Ccasti",
+        ] {
+            let parsed_tags = tags_from_uri(&format!("/art?tags={tags_param}"));
+            assert!(!parsed_tags.is_empty(), "tags {tags_param:?}");
+
+            let search_params = ArtSearchParameters {
+                tags: parsed_tags.clone(),
+                ..Default::default()
+            };
+
+            let reparsed_tags =
+                tags_from_uri(&format!("/art{}", search_params.to_uri_parameters(false)));
+
+            assert_eq!(reparsed_tags, parsed_tags, "tags {tags_param:?}");
+        }
+    }
+}
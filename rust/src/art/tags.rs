@@ -0,0 +1,219 @@
+use deadpool::managed::Object;
+use deadpool_postgres::Manager;
+use postgres_types::{FromSql, ToSql};
+use serde::Deserialize;
+use std::collections::HashMap;
+
+/// What kind of thing a tag describes. Mostly used for grouping tags in the picker.
+/// Content warnings aren't a category, they're set on the art itself. See [`super::content_warnings::ContentWarning`].
+#[derive(Clone, Copy, FromSql, ToSql, Deserialize, Debug, Default, PartialEq, Eq)]
+#[postgres(name = "tag_category", rename_all = "snake_case")]
+#[serde(rename_all = "snake_case")]
+pub enum TagCategory {
+    #[default]
+    General,
+    Character,
+    Artist,
+    Meta,
+}
+
+impl std::fmt::Display for TagCategory {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let human_readable = match self {
+            Self::General => "General",
+            Self::Character => "Character",
+            Self::Artist => "Artist",
+            Self::Meta => "Meta",
+        };
+
+        write!(f, "{human_readable}")
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Tag {
+    pub name: String,
+    pub category: TagCategory,
+    pub description: Option<String>,
+    pub usage_count: i64,
+}
+
+/// Given some tags, replaces aliases with the canonical tag they point to, and adds any tags implied by them.
+/// Keeps the order it was given in, implied tags are put at the end.
+pub async fn normalize_tags(db_connection: &Object<Manager>, tags: &[String]) -> Vec<String> {
+    let mut normalized_tags: Vec<String> = Vec::new();
+    push_new_tags(
+        &mut normalized_tags,
+        resolve_aliases(db_connection, tags).await,
+    );
+
+    // UNION (rather than UNION ALL) drops rows we've already seen, so a loop of implications doesn't go on forever.
+    const IMPLIED_TAGS_QUERY: &str = "WITH RECURSIVE implied(id) AS (
+            SELECT id FROM tag WHERE name = ANY($1)
+            UNION
+            SELECT tag_implication.implied_tag_id FROM tag_implication
+                JOIN implied ON tag_implication.tag_id = implied.id
+        )
+        SELECT tag.name FROM tag JOIN implied ON tag.id = implied.id ORDER BY tag.name";
+
+    match db_connection
+        .query(IMPLIED_TAGS_QUERY, &[&normalized_tags])
+        .await
+    {
+        Ok(rows) => push_new_tags(
+            &mut normalized_tags,
+            rows.iter().map(|row| row.get::<_, String>("name")),
+        ),
+        Err(err) => {
+            eprintln!("[TAG NORMALIZATION] Failed getting implied tags, skipping them. {err:?}")
+        }
+    }
+
+    normalized_tags
+}
+
+/// Given some tags, replaces any alias in them with the canonical tag it points to.
+/// Does NOT add implied tags, for that see [normalize_tags].
+pub async fn resolve_aliases(db_connection: &Object<Manager>, tags: &[String]) -> Vec<String> {
+    let alias_map = get_alias_map(db_connection, tags).await;
+
+    replace_aliases(tags, &alias_map)
+}
+
+/// Swaps out whichever of the given tags are in the alias map (see [get_alias_map]) for the tag they point to. Keeps the order.
+fn replace_aliases(tags: &[String], alias_map: &HashMap<String, String>) -> Vec<String> {
+    tags.iter()
+        .map(|tag| alias_map.get(tag).unwrap_or(tag).to_owned())
+        .collect()
+}
+
+/// Adds the given tags to the end of the list, skipping any that are already in it.
+fn push_new_tags(tags: &mut Vec<String>, new_tags: impl IntoIterator<Item = String>) {
+    for tag in new_tags {
+        if !tags.contains(&tag) {
+            tags.push(tag);
+        }
+    }
+}
+
+/// Returns a map of alias -> canonical tag name, for whichever of the given tags are aliases.
+pub async fn get_alias_map(
+    db_connection: &Object<Manager>,
+    tags: &[String],
+) -> HashMap<String, String> {
+    if tags.is_empty() {
+        return HashMap::new();
+    }
+
+    const ALIAS_QUERY: &str = "SELECT tag_alias.alias, tag.name FROM tag_alias
+        JOIN tag ON tag.id = tag_alias.tag_id
+        WHERE tag_alias.alias = ANY($1)";
+
+    match db_connection.query(ALIAS_QUERY, &[&tags]).await {
+        Ok(rows) => rows
+            .iter()
+            .map(|row| (row.get("alias"), row.get("name")))
+            .collect(),
+        Err(err) => {
+            eprintln!(
+                "[TAG NORMALIZATION] Failed getting tag aliases, leaving tags as-is. {err:?}"
+            );
+            HashMap::new()
+        }
+    }
+}
+
+/// Makes sure all the given tags exist in the tag table. New ones are put in as general tags.
+pub async fn register_tags(
+    db_connection: &Object<Manager>,
+    tags: &[String],
+) -> Result<(), tokio_postgres::Error> {
+    if tags.is_empty() {
+        return Ok(());
+    }
+
+    db_connection
+        .execute(
+            "INSERT INTO tag (name) SELECT unnest($1::text[]) ON CONFLICT DO NOTHING",
+            &[&tags],
+        )
+        .await
+        .map(|_| ())
+}
+
+/// Makes sure the given character tag exists in the tag table as a character tag.
+/// If it was already registered as a general tag, it's moved to the character category. Other categories are left alone.
+pub async fn register_character_tag(
+    db_connection: &Object<Manager>,
+    tag: &str,
+) -> Result<(), tokio_postgres::Error> {
+    db_connection
+        .execute(
+            "INSERT INTO tag (name, category) VALUES ($1, 'character')
+            ON CONFLICT (name) DO UPDATE SET category = 'character' WHERE tag.category = 'general'",
+            &[&tag],
+        )
+        .await
+        .map(|_| ())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn strings(tags: &[&str]) -> Vec<String> {
+        tags.iter().map(|tag| tag.to_string()).collect()
+    }
+
+    fn alias_map(aliases: &[(&str, &str)]) -> HashMap<String, String> {
+        aliases
+            .iter()
+            .map(|(alias, tag)| (alias.to_string(), tag.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn aliases_are_replaced_in_place() {
+        let aliases = alias_map(&[("kat", "kate"), ("fyn", "fynn")]);
+
+        assert_eq!(
+            replace_aliases(&strings(&["fyn", "casti", "kat"]), &aliases),
+            strings(&["fynn", "casti", "kate"])
+        );
+        assert_eq!(
+            replace_aliases(&strings(&["casti"]), &aliases),
+            strings(&["casti"])
+        );
+        assert!(replace_aliases(&[], &aliases).is_empty());
+    }
+
+    #[test]
+    fn new_tags_go_at_the_end_without_repeats() {
+        // Two aliases of the same tag only leave the tag once.
+        let mut tags = Vec::new();
+        push_new_tags(&mut tags, strings(&["kate", "casti", "kate"]));
+        assert_eq!(tags, strings(&["kate", "casti"]));
+
+        // Implied tags come after, and ones the art already had don't move.
+        push_new_tags(&mut tags, strings(&["casti", "sweater", "kate"]));
+        assert_eq!(tags, strings(&["kate", "casti", "sweater"]));
+    }
+
+    #[test]
+    fn categories_read_and_display() {
+        let cases = [
+            ("\"general\"", TagCategory::General, "General"),
+            ("\"character\"", TagCategory::Character, "Character"),
+            ("\"artist\"", TagCategory::Artist, "Artist"),
+            ("\"meta\"", TagCategory::Meta, "Meta"),
+        ];
+
+        for (json, category, label) in cases {
+            assert_eq!(serde_json::from_str::<TagCategory>(json).unwrap(), category);
+            assert_eq!(category.to_string(), label);
+        }
+
+        assert!(serde_json::from_str::<TagCategory>("\"content_warning\"").is_err());
+        assert_eq!(TagCategory::default(), TagCategory::General);
+    }
+}
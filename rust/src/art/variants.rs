@@ -0,0 +1,493 @@
+use crate::server_state::config::Config;
+use crate::utils::{self, file_compression, file_metadata::FileMetadata};
+use crate::ServerState;
+use axum::body::Bytes;
+use deadpool::managed::Object;
+use deadpool_postgres::Manager;
+use std::collections::HashMap;
+
+/// The quality of the smaller copies. They're only shown on small screens, so they can afford to be a bit worse than the original.
+const ART_VARIANT_QUALITY: u8 = 80;
+
+/// How many files the scheduled backfill goes over each time it runs, so it doesn't hog the server.
+const BACKFILL_BATCH_SIZE: i64 = 20;
+
+// A file that gets reordered loses its variants_checked mark, so it might get its copies made again. Same keys, same rows, no harm.
+const INSERT_INTO_ART_FILE_VARIANT_DB_QUERY: &str =
+    "INSERT INTO art_file_variant (belongs_to,source_s3_key,s3_key,width,height,byte_size) VALUES ($1,$2,$3,$4,$5,$6)
+        ON CONFLICT (source_s3_key, width) DO NOTHING";
+
+/// A smaller WebP copy of an art file.
+#[derive(Debug, Clone)]
+pub struct ArtFileVariant {
+    pub s3_key: String,
+    pub width: i32,
+}
+
+impl ArtFileVariant {
+    pub fn get_url(&self) -> String {
+        utils::get_s3_public_object_url(&self.s3_key)
+    }
+}
+
+/// Makes the smaller copies of a freshly moved art file in the background, so whoever uploaded it doesn't have to wait.
+/// The file's record should already be in the DB, so it can be marked as checked once it's done.
+pub fn spawn_art_file_variants(state: &ServerState, art_id: i32, moved_file: &utils::MovedFile) {
+    let state = state.clone();
+    let source_key = moved_file.key.clone();
+    let source_metadata = moved_file.metadata.clone();
+    let source_bytes = moved_file.file_bytes.clone();
+
+    tokio::spawn(async move {
+        let db_connection = match state.db_pool.get().await {
+            Ok(db_connection) => db_connection,
+            Err(err) => {
+                // The backfill will get to it later.
+                eprintln!(
+                    "[ART VARIANTS] Failed getting a DB connection for {source_key}: {err:?}"
+                );
+                return;
+            }
+        };
+
+        create_art_file_variants(
+            &state.s3_client,
+            &state.config,
+            &db_connection,
+            art_id,
+            &source_key,
+            &source_metadata,
+            source_bytes,
+        )
+        .await;
+    });
+}
+
+/// Makes the smaller copies of an art file that was already moved into place, uploads them next to it, and saves them in the DB.
+/// Takes the file's bytes, since whoever calls this just had them anyways. Returns the keys of the copies that got uploaded.
+/// If this fails the art page just falls back to the original, so errors are only logged.
+pub async fn create_art_file_variants(
+    s3_client: &aws_sdk_s3::Client,
+    config: &Config,
+    db_connection: &Object<Manager>,
+    art_id: i32,
+    source_key: &str,
+    source_metadata: &FileMetadata,
+    source_bytes: Bytes,
+) -> Vec<String> {
+    let uploaded_keys = upload_art_file_variants(
+        s3_client,
+        config,
+        db_connection,
+        art_id,
+        source_key,
+        source_metadata,
+        source_bytes,
+    )
+    .await;
+
+    // Marked even if nothing came out of it, so the backfill doesn't keep retrying files that never get copies.
+    mark_variants_checked(db_connection, art_id, source_key).await;
+
+    uploaded_keys
+}
+
+async fn mark_variants_checked(db_connection: &Object<Manager>, art_id: i32, source_key: &str) {
+    if let Err(err) = db_connection
+        .execute(
+            "UPDATE art_file SET variants_checked=true WHERE belongs_to=$1 AND s3_key=$2",
+            &[&art_id, &source_key],
+        )
+        .await
+    {
+        eprintln!("[ART VARIANTS] Failed marking {source_key} as checked: {err:?}");
+    }
+}
+
+async fn upload_art_file_variants(
+    s3_client: &aws_sdk_s3::Client,
+    config: &Config,
+    db_connection: &Object<Manager>,
+    art_id: i32,
+    source_key: &str,
+    source_metadata: &FileMetadata,
+    source_bytes: Bytes,
+) -> Vec<String> {
+    let Some(mime_type) = source_metadata.mime_type.clone() else {
+        return Vec::new();
+    };
+
+    let widths = get_variant_widths(source_metadata, &config.art_variant_widths);
+
+    if widths.is_empty() {
+        return Vec::new();
+    }
+
+    // Resizing is CPU-bound, keep it off the async threads.
+    let compressed_variants = tokio::task::spawn_blocking(move || {
+        widths
+            .into_iter()
+            .filter_map(|width| compress_art_file_variant(&source_bytes, &mime_type, width))
+            .collect::<Vec<_>>()
+    })
+    .await
+    .unwrap_or_default();
+
+    let mut uploaded_keys = Vec::new();
+
+    for (file_bytes, metadata) in compressed_variants {
+        let (Some(width), Some(height), Some(byte_size)) =
+            (metadata.width, metadata.height, metadata.byte_size)
+        else {
+            continue;
+        };
+
+        let variant_key = get_variant_key(source_key, width);
+
+        if let Err(err) = s3_client
+            .put_object()
+            .bucket(&config.s3_public_bucket)
+            .key(&variant_key)
+            .body(file_bytes.into())
+            .content_type("image/webp")
+            .content_disposition("inline")
+            .send()
+            .await
+        {
+            eprintln!("[ART VARIANTS] Failed uploading {variant_key}: {err:?}");
+            continue;
+        }
+
+        if let Err(err) = db_connection
+            .execute(
+                INSERT_INTO_ART_FILE_VARIANT_DB_QUERY,
+                &[
+                    &art_id,
+                    &source_key,
+                    &variant_key,
+                    &width,
+                    &height,
+                    &byte_size,
+                ],
+            )
+            .await
+        {
+            eprintln!("[ART VARIANTS] Failed saving {variant_key} to the DB: {err:?}");
+
+            let _ = utils::delete_keys_from_s3(
+                s3_client,
+                &config.s3_public_bucket,
+                std::slice::from_ref(&variant_key),
+            )
+            .await;
+            continue;
+        }
+
+        uploaded_keys.push(variant_key);
+    }
+
+    uploaded_keys
+}
+
+/// Returns which of the configured widths the given file should get a smaller copy at. Empty if it shouldn't get any.
+fn get_variant_widths(source_metadata: &FileMetadata, art_variant_widths: &[u32]) -> Vec<u32> {
+    let Some(mime_type) = &source_metadata.mime_type else {
+        return Vec::new();
+    };
+
+    // Videos aren't getting re-encoded on-server, and SVGs are already every size.
+    if !mime_type.starts_with("image/") || mime_type == "image/svg+xml" {
+        return Vec::new();
+    }
+
+    // Rotated phone pictures have their width and height swapped, so the longer side is the only safe bet for what's too wide.
+    let longest_side = source_metadata
+        .width
+        .max(source_metadata.height)
+        .and_then(|side| u32::try_from(side).ok())
+        .unwrap_or(u32::MAX);
+
+    art_variant_widths
+        .iter()
+        .copied()
+        .filter(|width| *width < longest_side)
+        .collect()
+}
+
+/// Shrinks the given file down to a WebP of the given width. None if that fails, or if it doesn't come out that wide.
+fn compress_art_file_variant(
+    source_bytes: &[u8],
+    mime_type: &str,
+    width: u32,
+) -> Option<(Vec<u8>, FileMetadata)> {
+    let settings = file_compression::LossyCompressionSettings {
+        max_width: Some(width),
+        max_height: None,
+        quality: ART_VARIANT_QUALITY,
+        keep_animation: true,
+    };
+
+    let compressed =
+        file_compression::compress_image_lossy(source_bytes.to_vec(), mime_type, Some(settings))
+            .ok()?;
+
+    let metadata = FileMetadata::from_file_bytes(&compressed.file_bytes, "image/webp");
+
+    // If it came out narrower than asked, the original was narrower than it looked, so it's not worth keeping.
+    if metadata.width != i32::try_from(width).ok() {
+        return None;
+    }
+
+    Some((compressed.file_bytes, metadata))
+}
+
+/// The copies sit next to the original, as in art/1/file_480w.webp for art/1/file.png.
+fn get_variant_key(source_key: &str, width: i32) -> String {
+    let source_key_without_extension = source_key
+        .rsplit_once('.')
+        .map_or(source_key, |(key, _)| key);
+
+    format!("{source_key_without_extension}_{width}w.webp")
+}
+
+/// Makes the smaller copies of art files that never got any, like ones uploaded before variants were a thing.
+/// Meant to run as a scheduled task. Goes over a few files at a time, oldest first, so it catches up over a few runs.
+pub async fn backfill_art_file_variants(state: &ServerState) {
+    let db_connection = match state.db_pool.get().await {
+        Ok(db_connection) => db_connection,
+        Err(err) => {
+            eprintln!("[ART VARIANTS BACKFILL] Failed to get sql connection! {err:?}");
+            return;
+        }
+    };
+
+    let unchecked_files = match db_connection
+        .query(
+            "SELECT * FROM art_file WHERE NOT variants_checked ORDER BY id LIMIT $1",
+            &[&BACKFILL_BATCH_SIZE],
+        )
+        .await
+    {
+        Ok(rows) => rows,
+        Err(err) => {
+            eprintln!("[ART VARIANTS BACKFILL] Failed getting unchecked art files! {err:?}");
+            return;
+        }
+    };
+
+    if unchecked_files.is_empty() {
+        return;
+    }
+
+    println!(
+        "[ART VARIANTS BACKFILL] Making variants for {} art files.",
+        unchecked_files.len()
+    );
+
+    for row in unchecked_files {
+        let art_id: i32 = row.get("belongs_to");
+        let source_key: String = row.get("s3_key");
+
+        let source_bytes = match state
+            .s3_client
+            .get_object()
+            .bucket(&state.config.s3_public_bucket)
+            .key(&source_key)
+            .send()
+            .await
+        {
+            Ok(downloaded_file) => match downloaded_file.body.collect().await {
+                Ok(file_bytes) => file_bytes.into_bytes(),
+                Err(err) => {
+                    eprintln!("[ART VARIANTS BACKFILL] Failed reading {source_key}: {err:?}");
+                    continue;
+                }
+            },
+            Err(err) => {
+                // If it's not on the bucket at all there's nothing to copy, so don't try again. Anything else might pass next time.
+                if err
+                    .as_service_error()
+                    .is_some_and(|service_err| service_err.is_no_such_key())
+                {
+                    mark_variants_checked(&db_connection, art_id, &source_key).await;
+                }
+
+                eprintln!("[ART VARIANTS BACKFILL] Failed downloading {source_key}: {err:?}");
+                continue;
+            }
+        };
+
+        // Files from before we kept metadata don't know what they are, so look at the file itself.
+        let mut source_metadata = FileMetadata::from_db_row(&row);
+        if source_metadata.mime_type.is_none() {
+            if let Some(file_type) = infer::get(&source_bytes) {
+                source_metadata =
+                    FileMetadata::from_file_bytes(&source_bytes, file_type.mime_type());
+            }
+        }
+
+        create_art_file_variants(
+            &state.s3_client,
+            &state.config,
+            &db_connection,
+            art_id,
+            &source_key,
+            &source_metadata,
+            source_bytes,
+        )
+        .await;
+    }
+}
+
+/// Returns the smaller copies of each of the given art's files, keyed by the original file's key. Narrowest first.
+pub async fn get_art_file_variants(
+    db_connection: &Object<Manager>,
+    art_id: i32,
+) -> HashMap<String, Vec<ArtFileVariant>> {
+    let mut variants: HashMap<String, Vec<ArtFileVariant>> = HashMap::new();
+
+    let rows = db_connection
+        .query(
+            "SELECT source_s3_key, s3_key, width FROM art_file_variant WHERE belongs_to=$1 ORDER BY width",
+            &[&art_id],
+        )
+        .await
+        .unwrap_or_default();
+
+    for row in rows {
+        variants
+            .entry(row.get("source_s3_key"))
+            .or_default()
+            .push(ArtFileVariant {
+                s3_key: row.get("s3_key"),
+                width: row.get("width"),
+            });
+    }
+
+    variants
+}
+
+/// Forgets the smaller copies of the given art files, and returns their keys so they can be deleted from S3.
+pub async fn remove_art_file_variants(
+    db_connection: &Object<Manager>,
+    art_id: i32,
+    source_keys: &[String],
+) -> Result<Vec<String>, tokio_postgres::Error> {
+    if source_keys.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    Ok(db_connection
+        .query(
+            "DELETE FROM art_file_variant WHERE belongs_to=$1 AND source_s3_key = ANY($2) RETURNING s3_key",
+            &[&art_id, &source_keys],
+        )
+        .await?
+        .iter()
+        .map(|row| row.get("s3_key"))
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{DynamicImage, ImageFormat, RgbImage};
+    use std::io::Cursor;
+
+    const WIDTHS: [u32; 3] = [480, 960, 1600];
+
+    fn metadata(mime_type: &str, width: i32, height: i32) -> FileMetadata {
+        FileMetadata {
+            mime_type: Some(mime_type.to_string()),
+            byte_size: Some(1000),
+            width: Some(width),
+            height: Some(height),
+            duration_seconds: None,
+        }
+    }
+
+    fn png(width: u32, height: u32) -> Vec<u8> {
+        let img = DynamicImage::ImageRgb8(RgbImage::from_fn(width, height, |x, y| {
+            image::Rgb([(x % 256) as u8, (y % 256) as u8, 128])
+        }));
+        let mut bytes = Cursor::new(Vec::new());
+        img.write_to(&mut bytes, ImageFormat::Png).unwrap();
+        bytes.into_inner()
+    }
+
+    #[test]
+    fn only_widths_below_the_longest_side_get_copies() {
+        let cases = [
+            (metadata("image/png", 2000, 1000), vec![480, 960, 1600]),
+            (metadata("image/png", 1000, 500), vec![480, 960]),
+            // Sideways phone pictures.
+            (metadata("image/jpeg", 1000, 3000), vec![480, 960, 1600]),
+            (metadata("image/png", 480, 300), vec![]),
+            (metadata("image/png", 100, 100), vec![]),
+        ];
+
+        for (metadata, expected) in cases {
+            assert_eq!(
+                get_variant_widths(&metadata, &WIDTHS),
+                expected,
+                "{metadata:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn unknown_sizes_get_every_copy() {
+        let metadata = FileMetadata {
+            width: None,
+            height: None,
+            ..metadata("image/png", 0, 0)
+        };
+
+        assert_eq!(get_variant_widths(&metadata, &WIDTHS), WIDTHS.to_vec());
+    }
+
+    #[test]
+    fn non_images_and_svgs_get_no_copies() {
+        for mime_type in ["video/mp4", "image/svg+xml", "application/zip"] {
+            assert!(
+                get_variant_widths(&metadata(mime_type, 4000, 4000), &WIDTHS).is_empty(),
+                "{mime_type}"
+            );
+        }
+
+        let metadata = FileMetadata {
+            mime_type: None,
+            ..metadata("image/png", 4000, 4000)
+        };
+        assert!(get_variant_widths(&metadata, &WIDTHS).is_empty());
+    }
+
+    #[test]
+    fn copies_sit_next_to_the_original() {
+        let cases = [
+            ("art/1/file.png", 480, "art/1/file_480w.webp"),
+            ("art/1/file.name.jpg", 960, "art/1/file.name_960w.webp"),
+            ("art/1/no_extension", 480, "art/1/no_extension_480w.webp"),
+        ];
+
+        for (source_key, width, expected) in cases {
+            assert_eq!(get_variant_key(source_key, width), expected, "{source_key}");
+        }
+    }
+
+    #[test]
+    fn copies_are_webps_of_the_asked_width() {
+        let (file_bytes, metadata) = compress_art_file_variant(&png(600, 300), "image/png", 480)
+            .expect("Should've made a copy");
+
+        assert_eq!(metadata.width, Some(480));
+        assert_eq!(metadata.height, Some(240));
+        assert_eq!(image::guess_format(&file_bytes).unwrap(), ImageFormat::WebP);
+    }
+
+    #[test]
+    fn copies_wider_than_the_original_are_dropped() {
+        assert!(compress_art_file_variant(&png(300, 600), "image/png", 480).is_none());
+        assert!(compress_art_file_variant(b"not an image", "image/png", 480).is_none());
+    }
+}
@@ -0,0 +1,224 @@
+use crate::art::{self, BaseArt};
+use crate::characters::BaseCharacter;
+use crate::stories::BaseStory;
+use crate::utils::template_to_response;
+use crate::{RootErrors, ServerState, User};
+use askama::Template;
+use axum::extract::{OriginalUri, Path, Query, State};
+use axum::{response::Response, routing::get, Router};
+use axum_extra::routing::RouterExt;
+use deadpool::managed::Object;
+use deadpool_postgres::Manager;
+use http::Uri;
+use serde::Deserialize;
+
+/// How many pieces of art the artist page's gallery shows at once.
+const AMOUNT_OF_ART_PER_GALLERY_PAGE: i64 = 12;
+
+pub fn router() -> Router<ServerState> {
+    Router::new()
+        .route("/", get(artist_index))
+        .route_with_tsr("/{artist_name}", get(artist_page))
+}
+
+/// Returns the URL of the given artist's page.
+pub fn get_artist_page_url(artist_name: &str) -> String {
+    format!("/artists/{}", urlencoding::encode(artist_name))
+}
+
+#[derive(Debug, Template)]
+#[template(path = "artists/index.html")]
+struct ArtistIndex {
+    user: Option<User>,
+    original_uri: Uri,
+
+    artist_names: Vec<String>,
+}
+
+async fn artist_index(
+    State(state): State<ServerState>,
+    OriginalUri(original_uri): OriginalUri,
+    cookie_jar: tower_cookies::Cookies,
+) -> Result<Response, RootErrors> {
+    let db_connection = state
+        .db_pool
+        .get()
+        .await
+        .map_err(|_| RootErrors::InternalServerError)?;
+
+    let user = User::get_from_cookie_jar(&db_connection, &cookie_jar).await;
+
+    let mut artist_names = art::get_all_artists(&db_connection).await;
+    artist_names.sort_by_key(|artist_name| artist_name.to_lowercase());
+
+    Ok(template_to_response(ArtistIndex {
+        user,
+        original_uri,
+
+        artist_names,
+    }))
+}
+
+#[derive(Debug, Template)]
+#[template(path = "artists/page.html")]
+struct ArtistPage {
+    user: Option<User>,
+    original_uri: Uri,
+
+    artist_name: String,
+
+    /// The site user who goes by this name, if they have an account.
+    linked_user: Option<User>,
+
+    art_pieces: Vec<BaseArt>,
+    stories: Vec<BaseStory>,
+    characters: Vec<BaseCharacter>,
+
+    /// Everyone else this artist has made art or stories with.
+    co_creators: Vec<String>,
+
+    current_page_number: i64,
+    total_page_number: i64,
+
+    first_page_url: Option<String>,
+    prev_page_url: Option<String>,
+    next_page_url: Option<String>,
+    last_page_url: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ArtistPageParameters {
+    /// Which page of the artist's art gallery to show.
+    #[serde(default)]
+    art_page: Option<i64>,
+}
+
+async fn artist_page(
+    Path(artist_name): Path<String>,
+    State(state): State<ServerState>,
+    Query(query_params): Query<ArtistPageParameters>,
+    OriginalUri(original_uri): OriginalUri,
+    cookie_jar: tower_cookies::Cookies,
+) -> Result<Response, RootErrors> {
+    let db_connection = state
+        .db_pool
+        .get()
+        .await
+        .map_err(|_| RootErrors::InternalServerError)?;
+
+    let user = User::get_from_cookie_jar(&db_connection, &cookie_jar).await;
+
+    let art_gallery = art::get_artist_art_page(
+        &state,
+        &db_connection,
+        &artist_name,
+        query_params.art_page.unwrap_or(1),
+        AMOUNT_OF_ART_PER_GALLERY_PAGE,
+    )
+    .await;
+
+    let stories = BaseStory::get_by_creator(&db_connection, &artist_name).await;
+    let characters = BaseCharacter::get_by_creator(&db_connection, &artist_name).await;
+    let linked_user = User::get_by_creator_name(&db_connection, &artist_name).await;
+
+    // Creator names are just strings, so "an artist" is anyone who shows up somewhere. If they show up nowhere, there's no page.
+    if art_gallery.art_pieces.is_empty()
+        && stories.is_empty()
+        && characters.is_empty()
+        && linked_user.is_none()
+        && !has_any_public_art(&db_connection, &artist_name).await
+    {
+        return Err(RootErrors::NotFound(original_uri, cookie_jar, user));
+    }
+
+    let co_creators = get_co_creators(&db_connection, &artist_name).await;
+
+    let page_urls = art_gallery.get_page_urls(original_uri.path());
+
+    Ok(template_to_response(ArtistPage {
+        user,
+        original_uri,
+
+        artist_name,
+        linked_user,
+
+        art_pieces: art_gallery.art_pieces,
+        stories,
+        characters,
+        co_creators,
+
+        first_page_url: page_urls.first,
+        prev_page_url: page_urls.prev,
+        next_page_url: page_urls.next,
+        last_page_url: page_urls.last,
+
+        current_page_number: art_gallery.page_number,
+        total_page_number: art_gallery.total_pages,
+    }))
+}
+
+/// Whether the given artist has any public art at all, NSFW included. The gallery only shows SFW art,
+/// so an artist who only draws NSFW stuff would look like they don't exist without this.
+async fn has_any_public_art(db_connection: &Object<Manager>, artist_name: &str) -> bool {
+    db_connection
+        .query_one(
+            "SELECT EXISTS (SELECT 1 FROM art WHERE post_state = 'public' AND lower($1) = ANY(SELECT lower(creator) FROM unnest(creators) AS creator))",
+            &[&artist_name],
+        )
+        .await
+        .is_ok_and(|row| row.get(0))
+}
+
+/// Returns everyone credited alongside the given artist on public art or visible stories, alphabetically.
+async fn get_co_creators(db_connection: &Object<Manager>, artist_name: &str) -> Vec<String> {
+    const CO_CREATORS_QUERY: &str = "
+        SELECT DISTINCT co_creator FROM (
+            SELECT unnest(creators) AS co_creator FROM art
+                WHERE post_state = 'public' AND lower($1) = ANY(SELECT lower(creator) FROM unnest(creators) AS creator)
+            UNION ALL
+            SELECT unnest(creators) AS co_creator FROM story
+                WHERE NOT is_hidden AND lower($1) = ANY(SELECT lower(creator) FROM unnest(creators) AS creator)
+        ) AS shared_works
+        WHERE lower(co_creator) != lower($1)
+        ORDER BY co_creator";
+
+    match db_connection
+        .query(CO_CREATORS_QUERY, &[&artist_name])
+        .await
+    {
+        Ok(rows) => rows.iter().map(|row| row.get("co_creator")).collect(),
+        Err(err) => {
+            eprintln!("[ARTIST PAGE] Failed getting the co-creators of {artist_name}! {err:?}");
+            Vec::new()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn artist_page_urls_are_encoded() {
+        let cases = [
+            ("kate", "/artists/kate"),
+            ("Some Artist", "/artists/Some%20Artist"),
+            ("a/b & c?", "/artists/a%2Fb%20%26%20c%3F"),
+        ];
+
+        for (artist_name, expected) in cases {
+            assert_eq!(get_artist_page_url(artist_name), expected, "{artist_name}");
+        }
+    }
+
+    #[test]
+    fn artist_page_urls_route_back_to_the_name() {
+        for artist_name in ["kate", "Some Artist", "a/b & c?", "ßíñ"] {
+            let url = get_artist_page_url(artist_name);
+            let encoded_name = url.strip_prefix("/artists/").unwrap();
+
+            assert!(!encoded_name.contains('/'), "{url}");
+            assert_eq!(urlencoding::decode(encoded_name).unwrap(), artist_name);
+        }
+    }
+}
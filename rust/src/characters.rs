@@ -8,6 +8,7 @@ use axum::{
 };
 use axum_extra::routing::RouterExt;
 use http::Uri;
+use std::collections::HashMap;
 
 mod edit;
 mod page;
@@ -49,7 +50,21 @@ struct CharacterIndex<'a> {
     birthday_character_names: &'a str,
     date_today_readable: &'a str,
 
+    /// How much art each character has, by ID. Characters without art aren't in here.
+    art_counts: HashMap<i32, i64>,
+
     show_upload_button: bool,
+    /// Whether the user's uploads go straight to public. If not, they're submitted for approval instead.
+    user_can_post_directly: bool,
+}
+
+impl<'a> CharacterIndex<'a> {
+    fn get_art_count(&self, character: &BaseCharacter) -> i64 {
+        self.art_counts
+            .get(&character.db_id)
+            .copied()
+            .unwrap_or_default()
+    }
 }
 
 async fn character_index(
@@ -80,6 +95,8 @@ async fn character_index(
         .collect();
     retired_characters.sort();
 
+    let art_counts = BaseCharacter::get_art_counts(&db_connection).await;
+
     let current_time = chrono::Utc::now();
     let date_today_readable = utils::format_date_to_human_readable(current_time);
 
@@ -110,7 +127,9 @@ async fn character_index(
         }
     };
 
-    let show_upload_button = user
+    // Anyone logged in can upload, but only some get to skip the approval queue.
+    let show_upload_button = user.is_some();
+    let user_can_post_directly = user
         .as_ref()
         .is_some_and(|user| user.user_type.permissions().can_post_characters);
 
@@ -123,6 +142,8 @@ async fn character_index(
         birthday_characters: &birthday_characters,
         date_today_readable: &date_today_readable,
         birthday_character_names: &birthday_character_names,
+        art_counts,
         show_upload_button,
+        user_can_post_directly,
     }))
 }
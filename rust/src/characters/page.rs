@@ -1,15 +1,20 @@
+use crate::art::{self, ArtGalleryPage, BaseArt};
 use crate::user::UsermadePost;
-use crate::utils::template_to_response;
+use crate::utils::{sql::PostState, template_to_response};
 use crate::{characters::structs::PageCharacter, errs::RootErrors, user::User, ServerState};
 use askama::Template;
 use axum::response::IntoResponse;
 use axum::{
-    extract::{OriginalUri, Path, State},
+    extract::{OriginalUri, Path, Query, State},
     response::Response,
 };
 use comrak::markdown_to_html;
 use http::Uri;
 use rand::seq::IndexedRandom;
+use serde::Deserialize;
+
+/// How many pieces of art the character page's gallery shows at once.
+const AMOUNT_OF_ART_PER_GALLERY_PAGE: i64 = 12;
 
 #[derive(Debug, Template)]
 #[template(path = "characters/page.html")]
@@ -23,21 +28,50 @@ struct CharacterPage<'a> {
 
     retirement_reason: Option<&'a str>,
 
+    /// If the character isn't public, why. Only the submitter and admins get this far with non-public characters.
+    review_notice: Option<String>,
+
     subtitle: &'a str,
 
     content: Option<&'a str>,
+
+    /// The character's art, if they have a tag. Empty if they have a tag but no art yet.
+    art_pieces: Vec<BaseArt>,
+
+    current_page_number: i64,
+    total_page_number: i64,
+
+    first_page_url: Option<String>,
+    prev_page_url: Option<String>,
+    next_page_url: Option<String>,
+    last_page_url: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CharacterPageParameters {
+    /// Which page of the character's art gallery to show.
+    #[serde(default)]
+    art_page: Option<i64>,
 }
 
 pub async fn character_page(
     Path(character_slug): Path<String>,
     State(state): State<ServerState>,
+    Query(query_params): Query<CharacterPageParameters>,
     OriginalUri(original_uri): OriginalUri,
     cookie_jar: tower_cookies::Cookies,
 ) -> Result<Response, RootErrors> {
     let db_connection = state.db_pool.get().await.unwrap();
     let requesting_user = User::get_from_cookie_jar(&db_connection, &cookie_jar).await;
 
-    if let Some(chosen_char) = PageCharacter::get_by_slug(&db_connection, &character_slug).await {
+    if let Some(chosen_char) = PageCharacter::get_by_slug(&db_connection, &character_slug)
+        .await
+        // Characters that aren't public yet (or won't ever be) are only for the people who can do something about them.
+        .filter(|character| {
+            character.post_state == PostState::Public
+                || character.can_optionally_be_modified_by(&requesting_user)
+        })
+    {
         let parsed_content = chosen_char.page_contents.as_ref().map(|contents| {
             parse_character_page_contents(contents).unwrap_or("PARSING FAILED!".to_owned())
         });
@@ -47,17 +81,43 @@ pub async fn character_page(
             .as_ref()
             .map(|f| markdown_to_html(f, &comrak::Options::default()));
 
+        let review_notice = chosen_char
+            .post_state
+            .get_review_notice(&chosen_char.rejection_reason);
+
         let random_subtitle = chosen_char
             .subtitles
             .choose(&mut rand::rng())
             .unwrap()
             .clone();
 
+        // Characters without a tag have no gallery, but the template still wants page numbers.
+        let art_gallery = match &chosen_char.tag {
+            Some(tag) => {
+                art::get_tagged_art_page(
+                    &state,
+                    &db_connection,
+                    tag,
+                    query_params.art_page.unwrap_or(1),
+                    AMOUNT_OF_ART_PER_GALLERY_PAGE,
+                )
+                .await
+            }
+            None => ArtGalleryPage {
+                art_pieces: Vec::new(),
+                page_number: 1,
+                total_pages: 1,
+            },
+        };
+
+        let page_urls = art_gallery.get_page_urls(original_uri.path());
+
         Ok(template_to_response(CharacterPage {
             user: requesting_user,
             original_uri,
 
             retirement_reason: retirement_reason.as_deref(),
+            review_notice,
 
             name: chosen_char
                 .long_name
@@ -69,6 +129,16 @@ pub async fn character_page(
             content: parsed_content.as_deref(),
 
             character: chosen_char,
+
+            art_pieces: art_gallery.art_pieces,
+
+            first_page_url: page_urls.first,
+            prev_page_url: page_urls.prev,
+            next_page_url: page_urls.next,
+            last_page_url: page_urls.last,
+
+            current_page_number: art_gallery.page_number,
+            total_page_number: art_gallery.total_pages,
         }))
     } else {
         Err(RootErrors::NotFound(
@@ -1,3 +1,4 @@
+use crate::art::tags;
 use crate::characters::BaseCharacter;
 use crate::user::{User, UsermadePost};
 use crate::utils::sql::PostState;
@@ -9,6 +10,8 @@ use askama::Template;
 use axum::extract::{OriginalUri, Path, State};
 use axum::response::{IntoResponse, Redirect, Response};
 use axum::{http, Json};
+use deadpool::managed::Object;
+use deadpool_postgres::Manager;
 use http::Uri;
 
 const CHARACTER_THUMBNAIL_COMPRESSION_SETTINGS: utils::file_compression::LossyCompressionSettings =
@@ -16,6 +19,7 @@ const CHARACTER_THUMBNAIL_COMPRESSION_SETTINGS: utils::file_compression::LossyCo
         max_width: Some(100),
         max_height: Some(100),
         quality: 85,
+        keep_animation: false,
     };
 
 const CHARACTER_IMAGE_COMPRESSION_SETTINGS: utils::file_compression::LossyCompressionSettings =
@@ -23,6 +27,7 @@ const CHARACTER_IMAGE_COMPRESSION_SETTINGS: utils::file_compression::LossyCompre
         max_width: Some(500),
         max_height: Some(500),
         quality: 90,
+        keep_animation: false,
     };
 
 const CHARACTER_LOGO_COMPRESSION_SETTINGS: utils::file_compression::LossyCompressionSettings =
@@ -30,6 +35,7 @@ const CHARACTER_LOGO_COMPRESSION_SETTINGS: utils::file_compression::LossyCompres
         max_width: Some(100),
         max_height: Some(100),
         quality: 90,
+        keep_animation: false,
     };
 
 #[axum::debug_handler]
@@ -50,9 +56,9 @@ pub async fn add_character(
         None => return Err(RootErrors::Unauthorized),
     };
 
-    if !requesting_user.user_type.permissions().can_post_characters {
-        return Err(RootErrors::Forbidden);
-    }
+    // Anyone logged in can submit a character, but only some people's go up without an admin looking at them first.
+    let final_post_state =
+        PostState::after_upload(requesting_user.user_type.permissions().can_post_characters);
 
     match posting_step {
         PostingSteps::RequestPresignedURLs { file_amount } => {
@@ -94,6 +100,9 @@ pub async fn add_character(
                 )));
             }
 
+            // Only once we know the character's going in, so rejected submissions don't leave their tag in the registry.
+            register_recieved_character_tag(&mut recieved_page_character, &db_connection).await?;
+
             // Let's build our query.
             let mut columns: Vec<String> = Vec::new();
             let mut values: Vec<&(dyn tokio_postgres::types::ToSql + Sync)> = Vec::new();
@@ -162,6 +171,9 @@ pub async fn add_character(
             columns.push("post_state".into());
             values.push(&PostState::Processing);
 
+            columns.push("uploading_user_id".into());
+            values.push(&requesting_user.id);
+
             // Now we have the character! Well, most pieces of the character. Let's get their ID.
             // SAFETY: we're not inserting anything the user sent into the query. Everything user-inputted is passed as values later.
             let creation_query = format!(
@@ -289,7 +301,7 @@ pub async fn add_character(
 
             // This is the final push!
             columns.push("post_state".into());
-            values.push(&PostState::Public);
+            values.push(&final_post_state);
 
             // Update the image values of the character
             // SAFETY: No user-passed values are in the query, they're all in `values`
@@ -320,10 +332,11 @@ pub async fn add_character(
             };
 
             println!(
-                "[CHARACTER POSTING] User {} (ID:{}) uploaded character named {}",
+                "[CHARACTER POSTING] User {} (ID:{}) uploaded character named {} as {:?}",
                 requesting_user.display_name,
                 requesting_user.id,
-                recieved_page_character.base_character.name
+                recieved_page_character.base_character.name,
+                final_post_state
             );
 
             Ok(Redirect::to(&format!(
@@ -399,6 +412,8 @@ pub async fn modify_character(
                 return Err(RootErrors::BadRequest(err_string));
             }
 
+            register_recieved_character_tag(&mut recieved_page_character, &db_connection).await?;
+
             // Let's build our update query.
             let mut columns: Vec<String> = Vec::new();
             let mut values: Vec<&(dyn tokio_postgres::types::ToSql + Sync)> = Vec::new();
@@ -573,6 +588,32 @@ pub async fn modify_character(
                 }
             }
 
+            // Edits by people who can't post directly need to be approved again.
+            let can_post_characters = requesting_user.user_type.permissions().can_post_characters;
+            let final_post_state = modified_character
+                .post_state
+                .after_edit(can_post_characters);
+
+            if final_post_state != modified_character.post_state && !values.is_empty() {
+                columns.push("post_state".into());
+                values.push(&final_post_state);
+
+                // Going back into the queue means the old rejection doesn't apply anymore.
+                if final_post_state == PostState::PendingApproval {
+                    columns.push("rejection_reason".into());
+                    values.push(&None::<String>);
+                }
+            }
+
+            // Public characters stay up, but the edit still shows up in the queue for an admin to look over.
+            if modified_character
+                .post_state
+                .edit_needs_review(can_post_characters)
+            {
+                columns.push("edited_since_review".into());
+                values.push(&true);
+            }
+
             // If anything was changed, let's modify it.
             if !values.is_empty() {
                 // Update the image values of the character
@@ -683,6 +724,30 @@ fn validate_recieved_page_character(recieved_page_character: &PageCharacter) ->
     Ok(())
 }
 
+/// The character's tag is what links them to their art, so swap an alias for the tag art actually uses,
+/// and make sure it's in the tag registry as a character tag.
+async fn register_recieved_character_tag(
+    recieved_page_character: &mut PageCharacter,
+    db_connection: &Object<Manager>,
+) -> Result<(), RootErrors> {
+    let Some(tag) = recieved_page_character.tag.take() else {
+        return Ok(());
+    };
+
+    let tag = tags::resolve_aliases(db_connection, &[tag]).await.remove(0);
+
+    tags::register_character_tag(db_connection, &tag)
+        .await
+        .map_err(|err| {
+            eprintln!("[TAG REGISTRATION] Failed registering character tag {tag}! {err:?}");
+            RootErrors::InternalServerError
+        })?;
+
+    recieved_page_character.tag = Some(tag);
+
+    Ok(())
+}
+
 /// Given a Page Character, cleans up any invalid or nonsensical values, such as empty strings in lists.
 /// NOTE: Does not make sure the values make _logical_ sense, only that we don't deal with trivially incorrect data.
 fn sanitize_recieved_page_character(
@@ -4,8 +4,10 @@ use deadpool_postgres::Manager;
 use postgres::Row;
 use postgres_types::{FromSql, ToSql};
 use serde::Deserialize;
+use std::collections::HashMap;
 
 use crate::user::{User, UsermadePost};
+use crate::utils::sql::PostState;
 
 #[derive(Debug, Clone, Deserialize)]
 /// Info relevant to absolute most uses of a character
@@ -51,6 +53,13 @@ pub struct PageCharacter {
     pub custom_css: Option<String>,
     #[serde(default)]
     pub page_contents: Option<String>,
+
+    #[serde(skip)]
+    pub post_state: PostState,
+    #[serde(skip)]
+    pub rejection_reason: Option<String>, // Why an admin rejected it, if they did.
+    #[serde(skip)]
+    pub uploading_user_id: Option<i32>, // None for deleted users and characters from before we tracked this.
 }
 
 #[derive(Debug, FromSql, ToSql, Clone, Deserialize, PartialEq)]
@@ -108,6 +117,60 @@ impl BaseCharacter {
         Self::from_db_row(&character_row)
     }
 
+    /// Gets the public characters whose tag is one of the given tags, like the tags of an art piece.
+    /// Hidden characters are left out, same as on the index.
+    pub async fn get_by_tags(db_connection: &Object<Manager>, tags: &[String]) -> Vec<Self> {
+        if tags.is_empty() {
+            return Vec::new();
+        }
+
+        db_connection
+            .query(
+                "SELECT * FROM character WHERE post_state='public' AND NOT is_hidden AND relevant_tag = ANY($1) ORDER BY short_name",
+                &[&tags],
+            )
+            .await
+            .unwrap_or_default()
+            .iter()
+            .map(Self::from_db_row)
+            .collect()
+    }
+
+    /// Returns the public, non-hidden characters made by the given creator. The name isn't case sensitive.
+    pub async fn get_by_creator(db_connection: &Object<Manager>, creator_name: &str) -> Vec<Self> {
+        db_connection
+            .query(
+                "SELECT * FROM character WHERE post_state='public' AND NOT is_hidden AND lower(creator) = lower($1) ORDER BY short_name",
+                &[&creator_name],
+            )
+            .await
+            .unwrap_or_default()
+            .iter()
+            .map(Self::from_db_row)
+            .collect()
+    }
+
+    /// Returns how much public, SFW art each character has, by character ID. Characters without any art are left out.
+    pub async fn get_art_counts(db_connection: &Object<Manager>) -> HashMap<i32, i64> {
+        // Written with @> so each character is a lookup in the GIN index on art tags. "= ANY(tags)" can't use it.
+        const ART_COUNT_QUERY: &str = "SELECT character.id, COUNT(art.id) AS art_count
+            FROM character
+            JOIN art ON art.tags @> ARRAY[character.relevant_tag]
+            WHERE art.post_state = 'public' AND NOT art.is_nsfw
+            GROUP BY character.id";
+
+        match db_connection.query(ART_COUNT_QUERY, &[]).await {
+            Ok(rows) => rows
+                .iter()
+                .map(|row| (row.get("id"), row.get("art_count")))
+                .collect(),
+            Err(err) => {
+                eprintln!("[CHARACTER INDEX] Failed counting character art! {err:?}");
+                HashMap::new()
+            }
+        }
+    }
+
     /// Converts a DB row with the relevant info to a BaseCharacter struct.
     fn from_db_row(row: &Row) -> Self {
         let archival_reason: Option<String> = row.get("retirement_reason");
@@ -182,6 +245,9 @@ impl PageCharacter {
             custom_css: row.get("custom_css"),
             page_contents: row.get("page_text"),
             tag: row.get("relevant_tag"),
+            post_state: row.get("post_state"),
+            rejection_reason: row.get("rejection_reason"),
+            uploading_user_id: row.get("uploading_user_id"),
         }
     }
 }
@@ -209,6 +275,9 @@ impl Ord for PageCharacter {
 impl UsermadePost for PageCharacter {
     fn can_be_modified_by(&self, user: &User) -> bool {
         user.user_type.permissions().can_modify_others_content
+            || self
+                .uploading_user_id
+                .is_some_and(|uploading_user_id| uploading_user_id == user.id)
             || user
                 .creator_name
                 .as_ref()
@@ -1,14 +1,16 @@
 use crate::user::User;
 use askama::Template;
 use axum::{
-    http::StatusCode,
+    http::{header, StatusCode},
     response::{Html, IntoResponse, Response},
 };
 use http::Uri;
 use lazy_static::lazy_static;
-use std::fs;
+use std::{fs, time::Duration};
 use tower_cookies::Cookies;
 
+// NotFound lugs the whole user around so the 404 page's navbar is right. It only gets made on the way out, so who cares how big it is.
+#[allow(clippy::large_enum_variant)]
 #[derive(Debug)]
 pub enum RootErrors {
     /// User asked for something that the server doesn't recognize.
@@ -23,6 +25,10 @@ pub enum RootErrors {
     Unauthorized,
     /// The user is logged in, and they don't have the permissions to do what they were doing.
     Forbidden,
+    /// The user's request clashes with something already on the site, and they need to confirm they meant it. Includes an explanation.
+    Conflict(String),
+    /// The user is doing something too often, and needs to wait this long before trying again. Includes an explanation.
+    TooManyRequests(Duration, String),
 }
 
 impl IntoResponse for RootErrors {
@@ -40,6 +46,10 @@ impl IntoResponse for RootErrors {
             Self::BadRequest(elaboration) => bad_request(elaboration).into_response(),
             Self::Unauthorized => unauthorized().into_response(),
             Self::Forbidden => forbidden().into_response(),
+            Self::Conflict(elaboration) => conflict(elaboration).into_response(),
+            Self::TooManyRequests(retry_after, elaboration) => {
+                too_many_requests(retry_after, elaboration).into_response()
+            }
         }
     }
 }
@@ -96,3 +106,18 @@ fn forbidden() -> impl IntoResponse {
         "You do not have the necessary permissions to do whatever you were trying to do.",
     )
 }
+
+fn conflict(elaboration: String) -> impl IntoResponse {
+    (StatusCode::CONFLICT, elaboration)
+}
+
+fn too_many_requests(retry_after: Duration, elaboration: String) -> impl IntoResponse {
+    // Retry-After is in whole seconds, so round up. Rounding down would tell them to come back too early.
+    let retry_after_seconds = retry_after.as_secs() + u64::from(retry_after.subsec_nanos() > 0);
+
+    (
+        StatusCode::TOO_MANY_REQUESTS,
+        [(header::RETRY_AFTER, retry_after_seconds.to_string())],
+        elaboration,
+    )
+}
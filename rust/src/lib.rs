@@ -14,6 +14,7 @@ use tower_http::compression::CompressionLayer;
 
 mod admin;
 mod art;
+mod artists;
 mod askama;
 mod characters;
 mod errs;
@@ -45,6 +46,7 @@ pub fn router(state: ServerState) -> Router<()> {
         .nest("/static", static_files::router())
         .nest("/characters", characters::router())
         .nest("/art", art::router())
+        .nest("/artists", artists::router())
         .route_with_tsr(
             "/art-archive",
             get(|uri: Uri| async move { Redirect::permanent(&format!("/art{}", uri.path())) }),
@@ -17,6 +17,7 @@ const THUMBNAIL_COMPRESSION_SETTINGS: utils::file_compression::LossyCompressionS
         max_width: Some(250),
         max_height: Some(250),
         quality: 85,
+        keep_animation: false,
     };
 
 /// Post Request Handler for editing lore categories.
@@ -11,17 +11,23 @@ struct NSFWSplash<'a> {
     original_uri: &'a Uri,
 }
 
-/// Reads the user's cookies. If the user doesn't have NSFW viewing enabled, returns the NSFW splash page.
+/// Reads the user's cookies and preferences. If the user doesn't have NSFW viewing enabled, returns the NSFW splash page.
 pub fn get_if_user_hasnt_enabled_nsfw(
     user: &Option<User>,
     original_uri: &Uri,
     cookie_jar: &tower_cookies::Cookies,
 ) -> Option<Response> {
-    let user_has_enabled_nsfw = cookie_jar.get("NSFW_WARNING_SHOWN").is_some();
-
-    if user_has_enabled_nsfw {
+    if user_has_enabled_nsfw(user, cookie_jar) {
         None
     } else {
         Some(template_to_response(NSFWSplash { user, original_uri }))
     }
 }
+
+/// Whether the user is fine with seeing NSFW stuff, either by going through the NSFW splash or by saying so in their preferences.
+pub fn user_has_enabled_nsfw(user: &Option<User>, cookie_jar: &tower_cookies::Cookies) -> bool {
+    cookie_jar.get("NSFW_WARNING_SHOWN").is_some()
+        || user
+            .as_ref()
+            .is_some_and(|user| user.content_filters.show_nsfw_by_default)
+}
@@ -5,7 +5,9 @@
 //!
 //! The `initiate(ServerState)` function is the one the main function should call to initiate all the relevant tasks.
 
-use crate::{scheduled_tasks::lib::clean_temp_db_entries, ServerState};
+use crate::{
+    art::backfill_art_file_variants, scheduled_tasks::lib::clean_temp_db_entries, ServerState,
+};
 use tokio_cron_scheduler::{Job, JobScheduler};
 
 mod lib;
@@ -55,6 +57,30 @@ pub async fn initiate_scheduled_tasks(state: ServerState) {
         })
         .unwrap();
 
+    // Make smaller copies of art files that don't have them yet, a batch every hour.
+    let cloned_state = state.clone();
+
+    job_scheduler
+        .add(
+            Job::new_async("@hourly", move |_uuid, _scheduler| {
+                let state = cloned_state.clone();
+                Box::pin(async move {
+                    backfill_art_file_variants(&state).await;
+                })
+            })
+            .inspect_err(|err| {
+                eprintln!("[JOB SCHEDULER] Failed creating backfill_art_file_variants job: {err:?}")
+            })
+            .unwrap(),
+        )
+        .await
+        .inspect_err(|err| {
+            eprintln!(
+                "[JOB SCHEDULER] Failed adding backfill_art_file_variants job to list: {err:?}"
+            )
+        })
+        .unwrap();
+
     // Backup DB once a day.
     let cloned_state = state.clone();
 
@@ -1,11 +1,13 @@
-use std::env;
+use std::{env, net::IpAddr, time::Duration};
 
 use deadpool::managed::Pool;
 use deadpool_postgres::{self, Manager, ManagerConfig, RecyclingMethod};
 use postgres::NoTls;
 use tokio::join;
 
+pub mod cache;
 pub mod config;
+pub mod rate_limit;
 
 #[derive(Debug, Clone)]
 pub struct ServerState {
@@ -13,6 +15,16 @@ pub struct ServerState {
     pub s3_client: aws_sdk_s3::Client, // Apparently cloning these doesn't cause race conditions.
     // If this ^ ends up being a bottleneck, create a client pool with deadpool.
     pub config: config::Config,
+
+    /// How much art matches a given search, keyed by the search. Counting is slow, and nobody needs it to be exact.
+    pub art_count_cache: cache::TtlCache<String, i64>,
+    /// The "More like this" art for each art page, keyed by art ID and whether NSFW art was included.
+    pub related_art_cache: cache::TtlCache<(i32, bool), Vec<crate::art::BaseArt>>,
+
+    /// Recent comments per user ID, to stop people from flooding the comment sections.
+    pub comment_user_limiter: rate_limit::RateLimiter<i32>,
+    /// Recent comments per IP, so making a bunch of accounts doesn't get around the user limit.
+    pub comment_ip_limiter: rate_limit::RateLimiter<IpAddr>,
 }
 
 impl ServerState {
@@ -23,10 +35,24 @@ impl ServerState {
 
         let config = config::Config::initialize();
 
+        let comment_limits = &config.comment_limits;
+        let comment_user_limiter = rate_limit::RateLimiter::new(
+            comment_limits.rate_limit_window,
+            comment_limits.max_comments_per_user,
+        );
+        let comment_ip_limiter = rate_limit::RateLimiter::new(
+            comment_limits.rate_limit_window,
+            comment_limits.max_comments_per_ip,
+        );
+
         ServerState {
             db_pool,
             s3_client,
             config,
+            art_count_cache: cache::TtlCache::new(Duration::from_secs(60), 1000),
+            related_art_cache: cache::TtlCache::new(Duration::from_secs(10 * 60), 1000),
+            comment_user_limiter,
+            comment_ip_limiter,
         }
     }
 
@@ -0,0 +1,58 @@
+use std::{
+    collections::HashMap,
+    hash::Hash,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+/// A tiny in-memory cache where every entry expires after a set amount of time.
+/// Cloning it gives you a handle to the same cache, so it can live in the ServerState.
+#[derive(Debug, Clone)]
+pub struct TtlCache<K, V> {
+    entries: Arc<Mutex<HashMap<K, (Instant, V)>>>,
+    time_to_live: Duration,
+    max_entries: usize,
+}
+
+impl<K: Eq + Hash, V: Clone> TtlCache<K, V> {
+    pub fn new(time_to_live: Duration, max_entries: usize) -> Self {
+        Self {
+            entries: Arc::new(Mutex::new(HashMap::new())),
+            time_to_live,
+            max_entries,
+        }
+    }
+
+    /// Returns the cached value, if it exists and hasn't expired yet.
+    pub fn get(&self, key: &K) -> Option<V> {
+        let entries = self.entries.lock().unwrap();
+
+        entries
+            .get(key)
+            .filter(|(inserted_at, _)| inserted_at.elapsed() < self.time_to_live)
+            .map(|(_, value)| value.clone())
+    }
+
+    pub fn insert(&self, key: K, value: V) {
+        let mut entries = self.entries.lock().unwrap();
+
+        // If we're full, throw out anything stale. If that didn't help, just start over, it's a cache.
+        if entries.len() >= self.max_entries {
+            entries.retain(|_, (inserted_at, _)| inserted_at.elapsed() < self.time_to_live);
+
+            if entries.len() >= self.max_entries {
+                entries.clear();
+            }
+        }
+
+        entries.insert(key, (Instant::now(), value));
+    }
+
+    pub fn invalidate(&self, key: &K) {
+        self.entries.lock().unwrap().remove(key);
+    }
+
+    pub fn clear(&self) {
+        self.entries.lock().unwrap().clear();
+    }
+}
@@ -1,4 +1,4 @@
-use std::env;
+use std::{env, str::FromStr, time::Duration};
 
 #[derive(Debug, Clone)]
 pub struct Config {
@@ -6,6 +6,32 @@ pub struct Config {
     // which, frankly, I don't want to do.
     pub s3_public_bucket: String, // The name of the public bucket, passed from env.
     pub s3_sql_backup_bucket: String, // The name of the sql backup bucket, passed from env.
+
+    pub comment_limits: CommentLimits,
+
+    /// How many bits two art files' perceptual hashes can differ by and still count as the same picture.
+    pub art_duplicate_hash_distance: u32,
+
+    /// Whether to leave colour profiles in when stripping metadata from uploaded images.
+    pub keep_image_colour_profiles: bool,
+
+    /// The widths of the smaller copies made of every uploaded art image. Ones wider than the original are skipped.
+    pub art_variant_widths: Vec<u32>,
+}
+
+/// Everything that stops people from flooding the comment sections.
+#[derive(Debug, Clone)]
+pub struct CommentLimits {
+    /// The window that the per-user and per-IP limits are counted in.
+    pub rate_limit_window: Duration,
+    /// How many comments a single user can post per window.
+    pub max_comments_per_user: usize,
+    /// How many comments can come from a single IP per window. Higher than the user one, since people share IPs.
+    pub max_comments_per_ip: usize,
+    /// How long a user has to wait before posting the exact same comment again.
+    pub duplicate_comment_window: Duration,
+    /// How old an account has to be before it can post comments at all.
+    pub minimum_account_age: Duration,
 }
 
 impl Config {
@@ -14,9 +40,58 @@ impl Config {
         let s3_public_bucket = env::var("S3_PUBLIC_BUCKET_NAME").unwrap();
         let s3_sql_backup_bucket = env::var("S3_SQL_BACKUP_BUCKET_NAME").unwrap();
 
+        let comment_limits = CommentLimits {
+            rate_limit_window: Duration::from_secs(env_or_default(
+                "COMMENT_RATE_LIMIT_WINDOW_SECONDS",
+                60,
+            )),
+            max_comments_per_user: env_or_default("COMMENT_RATE_LIMIT_PER_USER", 5),
+            max_comments_per_ip: env_or_default("COMMENT_RATE_LIMIT_PER_IP", 15),
+            duplicate_comment_window: Duration::from_secs(env_or_default(
+                "COMMENT_DUPLICATE_WINDOW_SECONDS",
+                600,
+            )),
+            minimum_account_age: Duration::from_secs(env_or_default(
+                "COMMENT_MINIMUM_ACCOUNT_AGE_SECONDS",
+                600,
+            )),
+        };
+
         Self {
             s3_public_bucket,
             s3_sql_backup_bucket,
+            comment_limits,
+            art_duplicate_hash_distance: env_or_default("ART_DUPLICATE_HASH_DISTANCE", 8),
+            keep_image_colour_profiles: env_or_default("KEEP_IMAGE_COLOUR_PROFILES", true),
+            art_variant_widths: env_list_or_default("ART_VARIANT_WIDTHS", vec![320, 640, 1280]),
         }
     }
 }
+
+/// Reads an optional variable from ENV. If it's missing, uses the default.
+/// If it's there but isn't a valid value, panics, since someone clearly meant to set it to something.
+fn env_or_default<T: FromStr>(variable_name: &str, default: T) -> T {
+    match env::var(variable_name) {
+        Ok(value) => value.trim().parse().unwrap_or_else(|_| {
+            panic!("ENV variable {variable_name} has invalid value \"{value}\"")
+        }),
+        Err(_) => default,
+    }
+}
+
+/// Same as `env_or_default`, but for a comma-separated list of values.
+fn env_list_or_default<T: FromStr>(variable_name: &str, default: Vec<T>) -> Vec<T> {
+    match env::var(variable_name) {
+        Ok(value) => value
+            .split(',')
+            .map(str::trim)
+            .filter(|item| !item.is_empty())
+            .map(|item| {
+                item.parse().unwrap_or_else(|_| {
+                    panic!("ENV variable {variable_name} has invalid value \"{value}\"")
+                })
+            })
+            .collect(),
+        Err(_) => default,
+    }
+}
@@ -0,0 +1,146 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    hash::Hash,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+/// Counts how many times something happened per key in a sliding window, and says no once it's too many.
+/// Cloning it gives you a handle to the same limiter, so it can live in the ServerState.
+#[derive(Debug, Clone)]
+pub struct RateLimiter<K> {
+    hits: Arc<Mutex<HashMap<K, VecDeque<Instant>>>>,
+    window: Duration,
+    max_hits: usize,
+}
+
+impl<K: Eq + Hash + Clone> RateLimiter<K> {
+    pub fn new(window: Duration, max_hits: usize) -> Self {
+        Self {
+            hits: Arc::new(Mutex::new(HashMap::new())),
+            window,
+            max_hits,
+        }
+    }
+
+    /// Returns how long the key has to wait until it's allowed again, or None if it's allowed right now.
+    /// Doesn't count as a hit, for that see [RateLimiter::record].
+    pub fn time_until_allowed(&self, key: &K) -> Option<Duration> {
+        let mut all_hits = self.hits.lock().unwrap();
+
+        let key_hits = all_hits.get_mut(key)?;
+        Self::forget_old_hits(key_hits, self.window);
+
+        if key_hits.len() < self.max_hits {
+            return None;
+        }
+
+        // The oldest hit leaving the window is what frees up a spot.
+        key_hits
+            .front()
+            .map(|oldest_hit| self.window.saturating_sub(oldest_hit.elapsed()))
+    }
+
+    /// Counts a hit for the given key.
+    pub fn record(&self, key: K) {
+        let mut all_hits = self.hits.lock().unwrap();
+
+        // Every so often, throw out keys that haven't done anything in a while so this doesn't grow forever.
+        if all_hits.len() >= 1000 {
+            all_hits.retain(|_, key_hits| {
+                Self::forget_old_hits(key_hits, self.window);
+                !key_hits.is_empty()
+            });
+        }
+
+        all_hits.entry(key).or_default().push_back(Instant::now());
+    }
+
+    fn forget_old_hits(key_hits: &mut VecDeque<Instant>, window: Duration) {
+        while key_hits
+            .front()
+            .is_some_and(|hit_time| hit_time.elapsed() >= window)
+        {
+            key_hits.pop_front();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread::sleep;
+
+    #[test]
+    fn allows_up_to_the_limit() {
+        let limiter = RateLimiter::new(Duration::from_secs(60), 3);
+
+        for _ in 0..3 {
+            assert_eq!(limiter.time_until_allowed(&1), None);
+            limiter.record(1);
+        }
+
+        let wait = limiter
+            .time_until_allowed(&1)
+            .expect("fourth hit should be limited");
+        assert!(wait <= Duration::from_secs(60));
+        assert!(wait > Duration::from_secs(59));
+    }
+
+    #[test]
+    fn keys_are_counted_separately() {
+        let limiter = RateLimiter::new(Duration::from_secs(60), 1);
+
+        limiter.record("someone");
+        assert!(limiter.time_until_allowed(&"someone").is_some());
+        assert_eq!(limiter.time_until_allowed(&"someone else"), None);
+    }
+
+    #[test]
+    fn hits_leave_the_window() {
+        let window = Duration::from_millis(200);
+        let limiter = RateLimiter::new(window, 2);
+
+        limiter.record(1);
+        sleep(window / 2);
+        limiter.record(1);
+        assert!(limiter.time_until_allowed(&1).is_some());
+
+        // The first hit is out of the window by now, the second one isn't.
+        sleep(window / 2 + Duration::from_millis(10));
+        assert_eq!(limiter.time_until_allowed(&1), None);
+        limiter.record(1);
+        assert!(limiter.time_until_allowed(&1).is_some());
+
+        sleep(window);
+        assert_eq!(limiter.time_until_allowed(&1), None);
+    }
+
+    #[test]
+    fn wait_shrinks_as_time_passes() {
+        let window = Duration::from_millis(200);
+        let limiter = RateLimiter::new(window, 1);
+
+        limiter.record(1);
+        let first_wait = limiter.time_until_allowed(&1).unwrap();
+        sleep(Duration::from_millis(50));
+        let second_wait = limiter.time_until_allowed(&1).unwrap();
+
+        assert!(second_wait < first_wait);
+        assert!(second_wait <= window - Duration::from_millis(50));
+    }
+
+    #[test]
+    fn idle_keys_get_thrown_out() {
+        let window = Duration::from_millis(20);
+        let limiter = RateLimiter::new(window, 1);
+
+        for key in 0..1000 {
+            limiter.record(key);
+        }
+        sleep(window);
+        limiter.record(1000);
+
+        assert_eq!(limiter.hits.lock().unwrap().len(), 1);
+    }
+}
@@ -1,4 +1,4 @@
-use crate::stories::structs::{BaseStory, StorySearchParameters};
+use crate::stories::structs::StorySearchParameters;
 use crate::utils::template_to_response;
 use crate::RootErrors;
 use crate::{user::User, ServerState};
@@ -16,6 +16,8 @@ mod page;
 mod post;
 mod structs;
 
+pub use structs::BaseStory;
+
 pub fn router() -> Router<ServerState> {
     Router::new()
         .route("/", get(story_index))
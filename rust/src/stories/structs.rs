@@ -78,6 +78,20 @@ impl BaseStory {
         requested_rows.iter().map(Self::from_db_row).collect()
     }
 
+    /// Returns every visible story the given creator worked on, newest first. The name isn't case sensitive.
+    pub async fn get_by_creator(db_connection: &Object<Manager>, creator_name: &str) -> Vec<Self> {
+        db_connection
+            .query(
+                "SELECT * FROM story WHERE NOT is_hidden AND lower($1) = ANY(SELECT lower(creator) FROM unnest(creators) AS creator) ORDER BY creation_date DESC",
+                &[&creator_name],
+            )
+            .await
+            .unwrap_or_default()
+            .iter()
+            .map(Self::from_db_row)
+            .collect()
+    }
+
     /// Returns the total amount of stories currently in the db.
     pub async fn get_total_amount(
         db_connection: Object<Manager>,
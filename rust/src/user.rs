@@ -1,6 +1,6 @@
 use crate::{
     user::structs::UserSession,
-    utils::{self, template_to_response},
+    utils::{self, sql::PostState, template_to_response},
     RootErrors, ServerState,
 };
 use askama::Template;
@@ -19,6 +19,8 @@ mod patch;
 mod structs;
 mod traits;
 
+pub use structs::ContentFilters;
+pub use structs::FilteredArtDisplay;
 pub use structs::User;
 pub use structs::UserType;
 pub use traits::UsermadePost;
@@ -33,6 +35,7 @@ pub fn router() -> Router<ServerState> {
         .nest("/oauth2", oauth::router())
         .route_with_tsr("/{user_id}", get(other_user_page).patch(patch::patch_user))
         .route_with_tsr("/{user_id}/modify", get(patch::modify_user_page))
+        .route_with_tsr("/{user_id}/favorites", get(crate::art::user_favorites_page))
 }
 
 /// Returns the user page. If the user is not logged in, redirects to login page.
@@ -56,6 +59,42 @@ struct UserPageTemplate {
     original_uri: Uri,
 
     viewed_user: User,
+    pending_submissions: Vec<PendingSubmission>,
+}
+
+/// Something the user posted that isn't public yet, shown to them on their own page.
+#[derive(Debug)]
+struct PendingSubmission {
+    title: String,
+    page_url: String,
+    post_state: PostState,
+    rejection_reason: Option<String>,
+}
+
+/// Returns everything the given user submitted that's still awaiting approval or was rejected.
+async fn get_pending_submissions(
+    db_connection: &deadpool::managed::Object<deadpool_postgres::Manager>,
+    user_id: i32,
+) -> Result<Vec<PendingSubmission>, tokio_postgres::Error> {
+    const PENDING_SUBMISSIONS_QUERY: &str = "
+        SELECT title, '/art/' || page_slug AS page_url, post_state, rejection_reason, last_modified_date
+            FROM art WHERE uploading_user_id=$1 AND post_state IN ('pending_approval', 'rejected')
+        UNION ALL
+        SELECT short_name, '/characters/' || page_slug, post_state, rejection_reason, last_modified_date
+            FROM character WHERE uploading_user_id=$1 AND post_state IN ('pending_approval', 'rejected')
+        ORDER BY last_modified_date DESC";
+
+    Ok(db_connection
+        .query(PENDING_SUBMISSIONS_QUERY, &[&user_id])
+        .await?
+        .iter()
+        .map(|row| PendingSubmission {
+            title: row.get("title"),
+            page_url: row.get("page_url"),
+            post_state: row.get("post_state"),
+            rejection_reason: row.get("rejection_reason"),
+        })
+        .collect())
 }
 
 /// Shows you the info on a given user
@@ -80,15 +119,33 @@ pub async fn other_user_page(
 
     let viewed_user = User::get_by_id(&db_connection, &parsed_user_id).await;
 
-    match viewed_user {
-        Some(viewed_user) => Ok(template_to_response(UserPageTemplate {
-            user,
-            original_uri,
+    let viewed_user = match viewed_user {
+        Some(viewed_user) => viewed_user,
+        None => return Err(RootErrors::NotFound(original_uri, cookie_jar, user)),
+    };
 
-            viewed_user,
-        })),
-        None => Err(RootErrors::NotFound(original_uri, cookie_jar, user)),
-    }
+    // Only you get to see how your own submissions are doing.
+    let pending_submissions = if user.as_ref() == Some(&viewed_user) {
+        get_pending_submissions(&db_connection, viewed_user.id)
+            .await
+            .map_err(|err| {
+                eprintln!(
+                    "[USER PAGE] Failed getting pending submissions of user ID {}! {err:?}",
+                    viewed_user.id
+                );
+                RootErrors::InternalServerError
+            })?
+    } else {
+        Vec::new()
+    };
+
+    Ok(template_to_response(UserPageTemplate {
+        user,
+        original_uri,
+
+        viewed_user,
+        pending_submissions,
+    }))
 }
 
 /// Returns page allowing user to login/create an account/connect an existing account using oauth methods.
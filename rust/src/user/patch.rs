@@ -2,7 +2,8 @@
 //!
 //! This file is for PATCH requests for a given user, to modify their values like their permission level, pfp, username, etc.
 
-use super::structs::UserType;
+use super::structs::{FilteredArtDisplay, UserType};
+use crate::art::{content_warnings::ContentWarning, tags};
 use crate::utils::file_compression::LossyCompressionSettings;
 use crate::utils::{
     get_temp_s3_presigned_urls, template_to_response, MoveTempS3FileErrs, PostingSteps,
@@ -20,6 +21,7 @@ const PROFILE_PICTURE_COMPRESSION_SETTINGS: LossyCompressionSettings = LossyComp
     max_height: Some(150),
     max_width: Some(150),
     quality: 85,
+    keep_animation: false,
 };
 
 #[axum::debug_handler]
@@ -195,6 +197,60 @@ pub async fn patch_user(
                 }
             }
 
+            // Content filters are personal. Not even admins get to decide what someone else sees.
+            let is_changing_content_filters = modified_user_info.blacklisted_tags.is_some()
+                || modified_user_info.blacklisted_artists.is_some()
+                || modified_user_info.show_nsfw_by_default.is_some()
+                || modified_user_info.filtered_art_display.is_some()
+                || modified_user_info.shown_content_warnings.is_some();
+
+            if is_changing_content_filters && modified_user != requesting_user {
+                return Err(RootErrors::Forbidden);
+            }
+
+            let sanitized_blacklisted_tags: Vec<String>;
+            if let Some(blacklisted_tags) = &modified_user_info.blacklisted_tags {
+                let cleaned_tags =
+                    sanitize_blacklist(blacklisted_tags, true).map_err(RootErrors::BadRequest)?;
+
+                // Blacklisting an alias should blacklist the tag it points to, since that's what the art actually has.
+                sanitized_blacklisted_tags =
+                    tags::resolve_aliases(&db_connection, &cleaned_tags).await;
+
+                columns.push("blacklisted_tags".to_string());
+                values.push(&sanitized_blacklisted_tags);
+            }
+
+            let sanitized_blacklisted_artists: Vec<String>;
+            if let Some(blacklisted_artists) = &modified_user_info.blacklisted_artists {
+                sanitized_blacklisted_artists = sanitize_blacklist(blacklisted_artists, false)
+                    .map_err(RootErrors::BadRequest)?;
+
+                columns.push("blacklisted_artists".to_string());
+                values.push(&sanitized_blacklisted_artists);
+            }
+
+            if let Some(show_nsfw_by_default) = &modified_user_info.show_nsfw_by_default {
+                columns.push("show_nsfw_by_default".to_string());
+                values.push(show_nsfw_by_default);
+            }
+
+            if let Some(filtered_art_display) = &modified_user_info.filtered_art_display {
+                columns.push("filtered_art_display".to_string());
+                values.push(filtered_art_display);
+            }
+
+            let deduped_shown_content_warnings: Vec<ContentWarning>;
+            if let Some(shown_content_warnings) = &modified_user_info.shown_content_warnings {
+                deduped_shown_content_warnings = ContentWarning::ALL
+                    .into_iter()
+                    .filter(|warning| shown_content_warnings.contains(warning))
+                    .collect();
+
+                columns.push("shown_content_warnings".to_string());
+                values.push(&deduped_shown_content_warnings);
+            }
+
             // Did we actually do anything?
             if columns.is_empty() {
                 return Err(RootErrors::BadRequest(
@@ -268,6 +324,56 @@ pub struct ModifiableUserInfo {
     /// If an empty string is passed, set to NULL in the DB.
     #[serde(default)]
     creator_name: Option<String>,
+    /// Tags whose art this user doesn't want to see. Only the user themselves can set this.
+    #[serde(default)]
+    blacklisted_tags: Option<Vec<String>>,
+    /// Creators whose art this user doesn't want to see. Only the user themselves can set this.
+    #[serde(default)]
+    blacklisted_artists: Option<Vec<String>>,
+    /// Whether to skip the NSFW splash for this user. Only the user themselves can set this.
+    #[serde(default)]
+    show_nsfw_by_default: Option<bool>,
+    /// Whether art caught by the blacklists is hidden or blurred. Only the user themselves can set this.
+    #[serde(default)]
+    filtered_art_display: Option<FilteredArtDisplay>,
+    /// Content warnings this user doesn't need covering up. Only the user themselves can set this.
+    #[serde(default)]
+    shown_content_warnings: Option<Vec<ContentWarning>>,
+}
+
+/// Cleans up a blacklist the user sent. Tags get lowercased like they are on art, creator names are kept as-is.
+/// Returns an explanation if the blacklist is no good.
+fn sanitize_blacklist(blacklist: &[String], lowercase: bool) -> Result<Vec<String>, String> {
+    // Every entry is another condition on every art query they make, so let's not go overboard.
+    const MAX_BLACKLIST_LENGTH: usize = 100;
+
+    let mut sanitized_blacklist: Vec<String> = Vec::new();
+
+    for entry in blacklist {
+        let trimmed_entry = entry.trim();
+
+        if trimmed_entry.is_empty() {
+            continue;
+        }
+
+        let cleaned_entry = if lowercase {
+            trimmed_entry.to_lowercase()
+        } else {
+            trimmed_entry.to_string()
+        };
+
+        if !sanitized_blacklist.contains(&cleaned_entry) {
+            sanitized_blacklist.push(cleaned_entry);
+        }
+    }
+
+    if sanitized_blacklist.len() > MAX_BLACKLIST_LENGTH {
+        return Err(format!(
+            "Can't blacklist more than {MAX_BLACKLIST_LENGTH} things at once."
+        ));
+    }
+
+    Ok(sanitized_blacklist)
 }
 
 // Given a display name by the user, cleans it up. Returns None if the username is invalid or can't be easily cleaned.
@@ -369,3 +475,48 @@ struct ModifyUserPage {
     viewed_user: User,
     modifying_user: User,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn strings(entries: &[&str]) -> Vec<String> {
+        entries.iter().map(|entry| entry.to_string()).collect()
+    }
+
+    #[test]
+    fn blacklists_get_trimmed_and_deduped() {
+        let blacklist = strings(&["  gore ", "", "   ", "gore", "spiders"]);
+
+        assert_eq!(
+            sanitize_blacklist(&blacklist, true).unwrap(),
+            strings(&["gore", "spiders"])
+        );
+    }
+
+    #[test]
+    fn only_tag_blacklists_get_lowercased() {
+        let blacklist = strings(&["Some Artist", "some artist"]);
+
+        assert_eq!(
+            sanitize_blacklist(&blacklist, true).unwrap(),
+            strings(&["some artist"])
+        );
+        assert_eq!(sanitize_blacklist(&blacklist, false).unwrap(), blacklist);
+    }
+
+    #[test]
+    fn blacklists_have_a_limit() {
+        let blacklist: Vec<String> = (0..100).map(|index| format!("tag{index}")).collect();
+        assert_eq!(sanitize_blacklist(&blacklist, true).unwrap().len(), 100);
+
+        let mut too_long_blacklist = blacklist.clone();
+        too_long_blacklist.push("one more".to_string());
+        assert!(sanitize_blacklist(&too_long_blacklist, true).is_err());
+
+        // Duplicates don't count against it.
+        let mut duplicated_blacklist = blacklist;
+        duplicated_blacklist.push("tag0".to_string());
+        assert!(sanitize_blacklist(&duplicated_blacklist, true).is_ok());
+    }
+}
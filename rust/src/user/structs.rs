@@ -9,7 +9,7 @@ use std::env;
 use tower_cookies::cookie;
 use tower_cookies::{cookie::SameSite, Cookie, Cookies};
 
-use crate::{utils, RootErrors, ServerState};
+use crate::{art::content_warnings::ContentWarning, utils, RootErrors, ServerState};
 
 /// Relative links to various default profile pictures users may have.
 const USER_DEFAULT_PFPS: [&str; 9] = [
@@ -34,6 +34,57 @@ pub struct User {
     pub profile_pic_s3_key: Option<String>, // The S3 key of their pfp image. Assumed to be in public bucket.
     pub last_modified: DateTime<Utc>,       // The last time that this user's info was modified.
     pub creator_name: Option<String>, // The name which identifies this user in art posts and such.
+    pub creation_time: DateTime<Utc>, // When the user signed up.
+    #[serde(default)]
+    pub content_filters: ContentFilters,
+}
+
+/// What the user doesn't want to see. Applies to the art index, the random button and such.
+#[derive(Debug, Clone, Default, Deserialize, PartialEq)]
+pub struct ContentFilters {
+    /// Art with any of these tags gets filtered.
+    pub blacklisted_tags: Vec<String>,
+    /// Art by any of these creators gets filtered.
+    pub blacklisted_artists: Vec<String>,
+    /// If set, they never get the NSFW splash, even without the cookie.
+    pub show_nsfw_by_default: bool,
+    pub filtered_art_display: FilteredArtDisplay,
+    /// Content warnings they're fine with. Art with only these doesn't get covered up for them.
+    pub shown_content_warnings: Vec<ContentWarning>,
+}
+
+impl ContentFilters {
+    /// Whether there's anything to filter at all.
+    pub fn is_empty(&self) -> bool {
+        self.blacklisted_tags.is_empty() && self.blacklisted_artists.is_empty()
+    }
+
+    /// The blacklisted artists, lowercased and without duplicates.
+    /// They're kept as typed so the settings page shows them back the same way, but creators are matched case-insensitively.
+    pub fn get_lowercase_blacklisted_artists(&self) -> Vec<String> {
+        let mut lowercase_artists: Vec<String> = Vec::new();
+
+        for artist in &self.blacklisted_artists {
+            let artist = artist.to_lowercase();
+
+            if !lowercase_artists.contains(&artist) {
+                lowercase_artists.push(artist);
+            }
+        }
+
+        lowercase_artists
+    }
+}
+
+#[derive(FromSql, ToSql, Debug, Clone, Copy, Default, Deserialize, PartialEq)]
+#[postgres(name = "filtered_art_display", rename_all = "snake_case")]
+#[serde(rename_all = "snake_case")]
+pub enum FilteredArtDisplay {
+    /// Filtered art is left out, like it doesn't exist.
+    #[default]
+    Hide,
+    /// Filtered art is still listed, but blurred out.
+    Blur,
 }
 
 #[derive(FromSql, ToSql, Debug, Clone, Deserialize, PartialEq)]
@@ -111,6 +162,23 @@ impl User {
         resulted_row.map(Self::from_row)
     }
 
+    /// Returns the user who goes by the given creator name in art posts and such, if there is one. The name isn't case sensitive.
+    pub async fn get_by_creator_name(
+        db_connection: &Object<Manager>,
+        creator_name: &str,
+    ) -> Option<Self> {
+        // Nothing stops two users from having the same creator name, so just take the oldest account.
+        const GET_USER_QUERY: &str =
+            "SELECT * FROM site_user WHERE lower(creator_name) = lower($1) ORDER BY creation_time LIMIT 1";
+
+        db_connection
+            .query_opt(GET_USER_QUERY, &[&creator_name])
+            .await
+            .ok()
+            .flatten()
+            .map(Self::from_row)
+    }
+
     /// Given a valid site_user row, converts it to a User struct.
     fn from_row(row: Row) -> Self {
         Self {
@@ -120,6 +188,14 @@ impl User {
             profile_pic_s3_key: row.get("profile_picture_s3_key"),
             creator_name: row.get("creator_name"),
             last_modified: row.get("last_modified_date"),
+            creation_time: row.get("creation_time"),
+            content_filters: ContentFilters {
+                blacklisted_tags: row.get("blacklisted_tags"),
+                blacklisted_artists: row.get("blacklisted_artists"),
+                show_nsfw_by_default: row.get("show_nsfw_by_default"),
+                filtered_art_display: row.get("filtered_art_display"),
+                shown_content_warnings: row.get("shown_content_warnings"),
+            },
         }
     }
 
@@ -465,3 +541,61 @@ impl Oauth2Provider {
         resulted_row.map(User::from_row)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn blacklisting(tags: &[&str], artists: &[&str]) -> ContentFilters {
+        ContentFilters {
+            blacklisted_tags: tags.iter().map(|tag| tag.to_string()).collect(),
+            blacklisted_artists: artists.iter().map(|artist| artist.to_string()).collect(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn filters_without_blacklists_are_empty() {
+        assert!(ContentFilters::default().is_empty());
+        assert!(!blacklisting(&["gore"], &[]).is_empty());
+        assert!(!blacklisting(&[], &["Kate"]).is_empty());
+
+        // These change how things look, not what gets filtered.
+        let filters = ContentFilters {
+            show_nsfw_by_default: true,
+            filtered_art_display: FilteredArtDisplay::Blur,
+            ..Default::default()
+        };
+        assert!(filters.is_empty());
+    }
+
+    #[test]
+    fn blacklisted_artists_are_lowercased_and_deduped() {
+        let filters = blacklisting(&[], &["Kate", "ucas", "KATE", "Ucas", "Some Artist"]);
+
+        assert_eq!(
+            filters.get_lowercase_blacklisted_artists(),
+            vec!["kate", "ucas", "some artist"]
+        );
+        // Still shown back the way they were typed.
+        assert_eq!(filters.blacklisted_artists[0], "Kate");
+    }
+
+    #[test]
+    fn filtered_art_is_hidden_by_default() {
+        assert_eq!(FilteredArtDisplay::default(), FilteredArtDisplay::Hide);
+
+        let cases = [
+            ("\"hide\"", FilteredArtDisplay::Hide),
+            ("\"blur\"", FilteredArtDisplay::Blur),
+        ];
+
+        for (json, expected) in cases {
+            assert_eq!(
+                serde_json::from_str::<FilteredArtDisplay>(json).unwrap(),
+                expected,
+                "{json}"
+            );
+        }
+    }
+}
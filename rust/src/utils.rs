@@ -6,7 +6,7 @@ use crate::ServerState;
 use askama::Template;
 use aws_sdk_s3::presigning::PresigningConfig;
 use aws_sdk_s3::types::ObjectIdentifier;
-use axum::body::Body;
+use axum::body::{Body, Bytes};
 use axum::response::{Html, IntoResponse, Response};
 use chrono::{DateTime, Datelike, Utc};
 use http::Uri;
@@ -17,7 +17,10 @@ use std::time::Duration;
 
 pub mod arbitrary_values;
 pub mod file_compression;
+pub mod file_metadata;
+pub mod perceptual_hash;
 pub mod sql;
+pub mod zip_stream;
 
 pub fn format_date_to_human_readable(date: DateTime<Utc>) -> String {
     let day_number = date.day();
@@ -83,8 +86,22 @@ pub fn get_s3_public_object_url(file_key: &str) -> String {
     }
 }
 
+/// Where a temp file ended up after being moved, and what we found out about it along the way.
+#[derive(Debug)]
+pub struct MovedFile {
+    /// The key it was uploaded to, with the file extension.
+    pub key: String,
+    /// Describes the file as it was uploaded, after any conversion.
+    pub metadata: file_metadata::FileMetadata,
+    /// The perceptual hash of the file, if it was asked for and the file's an image we can decode.
+    /// Taken from the file as the user sent it, before any conversion.
+    pub perceptual_hash: Option<i64>,
+    /// The file as it was uploaded, after any conversion, so whatever comes next (like making smaller copies) doesn't have to download it again.
+    pub file_bytes: Bytes,
+}
+
 /// Given a file on the public bucket, attempts to optimize it and move it to the target bucket under the target key.
-/// Returns the key that it was uploaded to (with the file extension).
+/// Returns the key that it was uploaded to (with the file extension), the final file's metadata, and its perceptual hash.
 /// Mainly for usage with temp images uploaded by users.
 pub async fn move_temp_s3_file(
     s3_client: &aws_sdk_s3::Client,
@@ -92,7 +109,7 @@ pub async fn move_temp_s3_file(
     temp_file_key: &str,
     target_bucket_name: &str,
     target_file_key: &str,
-) -> Result<String, MoveTempS3FileErrs> {
+) -> Result<MovedFile, MoveTempS3FileErrs> {
     fn losslessly_convert_based_on_filetype(
         original_file_bytes: Vec<u8>,
         mime_type: &str,
@@ -125,6 +142,7 @@ pub async fn move_temp_s3_file(
         target_bucket_name,
         target_file_key,
         losslessly_convert_based_on_filetype,
+        true,
         "MOVE TEMP S3 FILE",
     )
     .await
@@ -172,13 +190,17 @@ pub async fn move_and_lossily_compress_temp_s3_img(
         target_bucket_name,
         target_file_key,
         move |x, y, z| lossily_compress_img(x, y, z, compression_settings),
+        false,
         "COMPRESS TEMP S3 IMG",
     )
     .await
+    .map(|moved_file| moved_file.key)
 }
 
 /// Helper function which downloads a temp file from the public bucket, runs a function on it, and moves it to a chosen final location in any bucket.
 /// The function should take the file data, mime type, and media type, and return the compressed file and optionally its new file extension (return None if the compression failed).
+/// Decoding a whole image just for its hash isn't free, so it's only calculated if `calculate_perceptual_hash` is set.
+#[allow(clippy::too_many_arguments)]
 async fn move_and_convert_temp_file<F>(
     s3_client: &aws_sdk_s3::Client,
     server_config: &crate::server_state::config::Config,
@@ -186,8 +208,9 @@ async fn move_and_convert_temp_file<F>(
     target_bucket_name: &str,
     target_file_key: &str,
     file_conversion_operation: F,
+    calculate_perceptual_hash: bool,
     function_name_for_debug_logging: &str,
-) -> Result<String, MoveTempS3FileErrs>
+) -> Result<MovedFile, MoveTempS3FileErrs>
 where
     F: FnOnce(Vec<u8>, &str, &str) -> Option<file_compression::CompressionResult>,
 {
@@ -246,8 +269,15 @@ where
             .unwrap_or(&"bin"),
     };
 
+    // Hashed before the conversion, since that's how the hashes of everything already on the site were taken.
+    let perceptual_hash = if calculate_perceptual_hash && mime_media_type == "image" {
+        perceptual_hash::calculate_difference_hash(&original_file_bytes)
+    } else {
+        None
+    };
+
     // Now run the relevant operation on the file.
-    let converted_file =
+    let mut converted_file =
         match file_conversion_operation(original_file_bytes, mime_type, mime_media_type) {
             Some(x) => x,
             None => {
@@ -272,10 +302,27 @@ where
             .unwrap_or(mime_type_extension)
     );
 
+    // The conversion might've changed the format, so check what actually came out of it.
+    let final_mime_type = infer::get(&converted_file.file_bytes)
+        .map(|file_type| file_type.mime_type())
+        .unwrap_or(mime_type);
+
+    // Whatever the conversion did, make sure nobody's GPS coordinates end up on the public bucket.
+    if mime_media_type == "image" {
+        converted_file.file_bytes = file_compression::strip_image_metadata(
+            converted_file.file_bytes,
+            final_mime_type,
+            server_config.keep_image_colour_profiles,
+        );
+    }
+    let metadata =
+        file_metadata::FileMetadata::from_file_bytes(&converted_file.file_bytes, final_mime_type);
+    let file_bytes = Bytes::from(converted_file.file_bytes);
+
     s3_client.put_object()
         .bucket(target_bucket_name)
         .key(&target_key_with_filename)
-        .body(converted_file.file_bytes.into())
+        .body(file_bytes.clone().into())
         .content_type(mime_type)
         .content_disposition(file_content_disposition)
         .send()
@@ -285,7 +332,12 @@ where
             MoveTempS3FileErrs::UploadFailed
         })?;
 
-    Ok(target_key_with_filename)
+    Ok(MovedFile {
+        key: target_key_with_filename,
+        metadata,
+        perceptual_hash,
+        file_bytes,
+    })
 }
 
 #[derive(Debug)]
@@ -1,8 +1,15 @@
+use image::codecs::gif::GifDecoder;
 use image::codecs::png::{CompressionType, FilterType, PngEncoder};
-use image::codecs::webp::WebPEncoder;
-use image::{DynamicImage, ImageEncoder, ImageFormat, ImageReader};
+use image::codecs::webp::{WebPDecoder, WebPEncoder};
+use image::{
+    AnimationDecoder, DynamicImage, ImageDecoder, ImageEncoder, ImageFormat, ImageReader, RgbaImage,
+};
 use std::io::Cursor;
 
+/// How much memory the decoded frames of an animation can take up before we give up on converting it.
+/// Every frame gets decoded to a full RGBA canvas, so a long GIF gets big fast.
+const MAX_DECODED_ANIMATION_BYTES: usize = 256 * 1024 * 1024;
+
 pub struct CompressionResult {
     pub file_bytes: Vec<u8>,
     pub new_file_extension: Option<String>,
@@ -31,11 +38,24 @@ pub fn compress_image_lossless(
             })
         }
         ImageFormat::Gif => {
-            // TODO: How do I compress gifs without killing the animation?
-            Ok(CompressionResult {
-                file_bytes: image_bytes,
-                new_file_extension: None,
-            })
+            // Animated GIFs become animated WebPs with the same frames and timing, stills become normal WebPs.
+            let compressed = match decode_animation(&image_bytes, format)? {
+                Some(animation) => encode_animated_webp(&animation, true, 100.0)?,
+                None => compress_to_webp_lossless(&img)?,
+            };
+
+            // GIFs are already palette-compressed, so every now and then the WebP ends up bigger. Keep the GIF then.
+            if compressed.len() < image_bytes.len() {
+                Ok(CompressionResult {
+                    file_bytes: compressed,
+                    new_file_extension: Some("webp".to_string()),
+                })
+            } else {
+                Ok(CompressionResult {
+                    file_bytes: image_bytes,
+                    new_file_extension: None,
+                })
+            }
         }
         ImageFormat::WebP => {
             // WebP could be lossy or lossless, but we can't easily tell
@@ -115,6 +135,8 @@ pub struct LossyCompressionSettings {
     pub max_width: Option<u32>,
     pub max_height: Option<u32>,
     pub quality: u8, // 1-100, higher = better quality
+    /// If the image is animated, whether the result should be animated too. If not, it's a still of the first frame.
+    pub keep_animation: bool,
 }
 
 impl Default for LossyCompressionSettings {
@@ -123,6 +145,7 @@ impl Default for LossyCompressionSettings {
             max_width: None,
             max_height: None,
             quality: 85,
+            keep_animation: false,
         }
     }
 }
@@ -138,26 +161,46 @@ pub fn compress_image_lossy(
     // Convert the infer type to ImageReader's format
     let format = ImageFormat::from_mime_type(mime_type).unwrap();
 
-    // Load the image
+    // If it's animated and we want to keep it that way, shrink every frame.
+    // If that fails (usually bc it's too long), a still thumbnail is better than no thumbnail, so fall through to that.
+    if settings.keep_animation {
+        if let Ok(Some(mut animation)) = decode_animation(&image_bytes, format) {
+            let (width, height) = animation.frames[0].0.dimensions();
+            let (new_width, new_height) = get_resized_dimensions(width, height, &settings);
+
+            if (new_width, new_height) != (width, height) {
+                for (frame, _) in &mut animation.frames {
+                    // Lanczos is too slow to run on every frame, and you can't tell the difference at thumbnail size.
+                    *frame = image::imageops::resize(
+                        frame,
+                        new_width,
+                        new_height,
+                        image::imageops::FilterType::Triangle,
+                    );
+                }
+            }
+
+            return Ok(CompressionResult {
+                file_bytes: encode_animated_webp(&animation, false, settings.quality as f32)?,
+                new_file_extension: Some("webp".to_string()),
+            });
+        }
+    }
+
+    // Load the image. For animated images, this is just the first frame.
     let mut reader = ImageReader::new(Cursor::new(&image_bytes));
     reader.set_format(format);
-    let mut img = reader.decode()?;
+    let mut decoder = reader.into_decoder()?;
+
+    // The WebP we make won't have the EXIF orientation, so rotate the pixels themselves, or phone pictures come out sideways.
+    let orientation = decoder.orientation()?;
+    let mut img = DynamicImage::from_decoder(decoder)?;
+    img.apply_orientation(orientation);
 
     // Resize if dimensions are specified
-    if let (Some(max_w), Some(max_h)) = (settings.max_width, settings.max_height) {
-        img = resize_if_needed(img, max_w, max_h);
-    } else if let Some(max_w) = settings.max_width {
-        if img.width() > max_w {
-            let ratio = max_w as f32 / img.width() as f32;
-            let new_height = (img.height() as f32 * ratio) as u32;
-            img = img.resize(max_w, new_height, image::imageops::FilterType::Lanczos3);
-        }
-    } else if let Some(max_h) = settings.max_height {
-        if img.height() > max_h {
-            let ratio = max_h as f32 / img.height() as f32;
-            let new_width = (img.width() as f32 * ratio) as u32;
-            img = img.resize(new_width, max_h, image::imageops::FilterType::Lanczos3);
-        }
+    let (new_width, new_height) = get_resized_dimensions(img.width(), img.height(), &settings);
+    if (new_width, new_height) != (img.width(), img.height()) {
+        img = img.resize_exact(new_width, new_height, image::imageops::FilterType::Lanczos3);
     }
 
     // Compress to lossy WebP
@@ -169,23 +212,155 @@ pub fn compress_image_lossy(
     })
 }
 
-fn resize_if_needed(img: DynamicImage, max_width: u32, max_height: u32) -> DynamicImage {
-    let (width, height) = (img.width(), img.height());
+/// Returns the size an image of the given size should be shrunk to, to fit the max width and height in the settings.
+/// Keeps the aspect ratio, and never scales up.
+fn get_resized_dimensions(
+    width: u32,
+    height: u32,
+    settings: &LossyCompressionSettings,
+) -> (u32, u32) {
+    // Calculate the scaling ratio to fit within bounds
+    let width_ratio = settings
+        .max_width
+        .map_or(1.0, |max_width| max_width as f32 / width as f32);
+    let height_ratio = settings
+        .max_height
+        .map_or(1.0, |max_height| max_height as f32 / height as f32);
+    let ratio = width_ratio.min(height_ratio);
 
     // Calculate if resizing is needed
-    if width <= max_width && height <= max_height {
-        return img;
+    if ratio >= 1.0 {
+        return (width, height);
     }
 
-    // Calculate the scaling ratio to fit within bounds
-    let width_ratio = max_width as f32 / width as f32;
-    let height_ratio = max_height as f32 / height as f32;
-    let ratio = width_ratio.min(height_ratio);
+    (
+        ((width as f32 * ratio) as u32).max(1),
+        ((height as f32 * ratio) as u32).max(1),
+    )
+}
 
-    let new_width = (width as f32 * ratio) as u32;
-    let new_height = (height as f32 * ratio) as u32;
+/// Every frame of an animation, as full canvases, ready to be re-encoded.
+struct DecodedAnimation {
+    /// Each frame and how long it's shown for, in milliseconds.
+    frames: Vec<(RgbaImage, i32)>,
+    /// How many times it plays, in WebP terms. 0 means forever.
+    loop_count: i32,
+}
+
+/// Decodes every frame of an animated GIF or WebP.
+/// Returns None if it isn't animated (or only has one frame), and an error if it's too big to be worth converting.
+fn decode_animation(
+    image_bytes: &[u8],
+    format: ImageFormat,
+) -> Result<Option<DecodedAnimation>, Box<dyn std::error::Error>> {
+    let (frames, loop_count) = match format {
+        ImageFormat::Gif => (
+            GifDecoder::new(Cursor::new(image_bytes))?.into_frames(),
+            read_gif_loop_count(image_bytes),
+        ),
+        ImageFormat::WebP => {
+            let decoder = WebPDecoder::new(Cursor::new(image_bytes))?;
 
-    img.resize(new_width, new_height, image::imageops::FilterType::Lanczos3)
+            if !decoder.has_animation() {
+                return Ok(None);
+            }
+
+            // The image crate doesn't tell us the WebP's loop count, and nearly all of em loop forever anyways.
+            (decoder.into_frames(), 0)
+        }
+        _ => return Ok(None),
+    };
+
+    let mut decoded_frames = Vec::new();
+    let mut decoded_bytes = 0;
+
+    for frame in frames {
+        let frame = frame?;
+        let (numerator, denominator) = frame.delay().numer_denom_ms();
+
+        // Browsers show GIF frames with a tiny (or no) delay for 100ms, and art is made with that in mind.
+        // WebPs get shown as-is, so without this they'd play way faster than they used to.
+        let duration = match (numerator / denominator.max(1)) as i32 {
+            0..=10 => 100,
+            duration => duration,
+        };
+
+        let frame_buffer = frame.into_buffer();
+        decoded_bytes += frame_buffer.as_raw().len();
+
+        if decoded_bytes > MAX_DECODED_ANIMATION_BYTES {
+            return Err("Animation is too big to convert".into());
+        }
+
+        decoded_frames.push((frame_buffer, duration));
+    }
+
+    if decoded_frames.len() <= 1 {
+        return Ok(None);
+    }
+
+    Ok(Some(DecodedAnimation {
+        frames: decoded_frames,
+        loop_count,
+    }))
+}
+
+/// Finds how many times a GIF should play, in WebP terms (0 is forever).
+/// That's in the NETSCAPE2.0 extension, which holds how many times to repeat *after* the first play. No extension means it plays once.
+fn read_gif_loop_count(image_bytes: &[u8]) -> i32 {
+    const NETSCAPE_EXTENSION_HEADER: &[u8] = b"\x21\xFF\x0BNETSCAPE2.0\x03\x01";
+
+    image_bytes
+        .windows(NETSCAPE_EXTENSION_HEADER.len())
+        .position(|window| window == NETSCAPE_EXTENSION_HEADER)
+        .and_then(|position| {
+            let loop_count_position = position + NETSCAPE_EXTENSION_HEADER.len();
+            image_bytes.get(loop_count_position..loop_count_position + 2)
+        })
+        .map_or(1, |loop_count_bytes| {
+            match u16::from_le_bytes([loop_count_bytes[0], loop_count_bytes[1]]) {
+                0 => 0,
+                repeats => i32::from(repeats) + 1,
+            }
+        })
+}
+
+/// Encodes the given animation as an animated WebP. For lossless, quality is how hard it tries to compress.
+fn encode_animated_webp(
+    animation: &DecodedAnimation,
+    lossless: bool,
+    quality: f32,
+) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+    let (width, height) = animation.frames[0].0.dimensions();
+
+    let mut config = webp::WebPConfig::new().map_err(|_| "Failed creating WebP config")?;
+    config.lossless = i32::from(lossless);
+    config.quality = quality;
+
+    let mut encoder = webp::AnimEncoder::new(width, height, &config);
+    encoder.set_loop_count(animation.loop_count);
+
+    // Timestamps are when each frame starts. The webp crate always ends the animation at timestamp 0,
+    // and that's where libwebp gets the last frame's duration from. So count up to 0 from below,
+    // otherwise the last frame gets some made-up average duration.
+    let total_duration: i32 = animation.frames.iter().map(|(_, duration)| duration).sum();
+    let mut timestamp = -total_duration;
+
+    for (frame, duration) in &animation.frames {
+        encoder.add_frame(webp::AnimFrame::from_rgba(
+            frame.as_raw(),
+            width,
+            height,
+            timestamp,
+        ));
+        timestamp += duration;
+    }
+
+    let encoded_animation = encoder
+        .try_encode()
+        .map_err(|err| format!("Failed encoding animated WebP: {err:?}"))?;
+
+    Ok(encoded_animation.to_vec())
 }
 
 fn compress_to_webp_lossy(
@@ -197,3 +372,885 @@ fn compress_to_webp_lossy(
     let webp = encoder.encode(quality as f32);
     Ok(webp.to_vec())
 }
+
+/// Removes EXIF, XMP, IPTC, comments and such from JPEG, PNG and WebP images, so stuff like GPS coordinates
+/// and camera serial numbers don't end up on the public bucket. Only touches the metadata, the pixel data is copied as-is.
+/// Colour profiles are only removed if [keep_colour_profile] is false, since removing them changes how the colours look.
+/// If the image can't be parsed, or isn't one of the above formats, returns it untouched.
+pub fn strip_image_metadata(
+    image_bytes: Vec<u8>,
+    mime_type: &str,
+    keep_colour_profile: bool,
+) -> Vec<u8> {
+    let stripped_image = match mime_type {
+        "image/jpeg" => strip_jpeg_metadata(&image_bytes, keep_colour_profile),
+        "image/png" => strip_png_metadata(&image_bytes, keep_colour_profile),
+        "image/webp" => strip_webp_metadata(&image_bytes, keep_colour_profile),
+        _ => None,
+    };
+
+    stripped_image.unwrap_or(image_bytes)
+}
+
+/// JPEGs are a list of segments, each starting with a 0xFF marker. Metadata lives in the APPn segments and comments.
+fn strip_jpeg_metadata(image_bytes: &[u8], keep_colour_profile: bool) -> Option<Vec<u8>> {
+    const START_OF_IMAGE: u8 = 0xD8;
+    const START_OF_SCAN: u8 = 0xDA;
+    const APP0_JFIF: u8 = 0xE0;
+    const APP1_EXIF_OR_XMP: u8 = 0xE1;
+    const APP2_ICC_PROFILE: u8 = 0xE2;
+    const APP14_ADOBE: u8 = 0xEE; // Says how the colours are encoded, the image breaks without it.
+
+    if image_bytes.get(0..2)? != [0xFF, START_OF_IMAGE] {
+        return None;
+    }
+
+    let mut output = vec![0xFF, START_OF_IMAGE];
+    let mut orientation = None;
+    let mut position = 2;
+
+    loop {
+        if *image_bytes.get(position)? != 0xFF {
+            return None;
+        }
+
+        let marker = *image_bytes.get(position + 1)?;
+
+        // Some encoders pad between segments with extra 0xFFs.
+        if marker == 0xFF {
+            position += 1;
+            continue;
+        }
+
+        // Everything after start of scan is the actual image data. Copy it over and we're done.
+        if marker == START_OF_SCAN {
+            // The EXIF orientation is the only thing browsers actually use, it's what makes phone pictures show up the right way around.
+            if let Some(orientation) = orientation.filter(|orientation| *orientation != 1) {
+                let exif_segment =
+                    [b"Exif\0\0".as_slice(), &minimal_exif_tiff(orientation)].concat();
+
+                output.extend_from_slice(&[0xFF, APP1_EXIF_OR_XMP]);
+                output.extend_from_slice(&(exif_segment.len() as u16 + 2).to_be_bytes());
+                output.extend_from_slice(&exif_segment);
+            }
+
+            output.extend_from_slice(&image_bytes[position..]);
+            return Some(output);
+        }
+
+        let segment_length = u16::from_be_bytes(
+            image_bytes
+                .get(position + 2..position + 4)?
+                .try_into()
+                .ok()?,
+        ) as usize;
+        let segment = image_bytes.get(position..position + 2 + segment_length)?;
+        let segment_data = segment.get(4..)?;
+
+        let keep_segment = match marker {
+            APP0_JFIF | APP14_ADOBE => true,
+            APP1_EXIF_OR_XMP => {
+                if let Some(exif) = segment_data.strip_prefix(b"Exif\0\0") {
+                    orientation = read_exif_orientation(exif);
+                }
+                false
+            }
+            APP2_ICC_PROFILE => keep_colour_profile && segment_data.starts_with(b"ICC_PROFILE\0"),
+            // Every other APPn is vendor metadata (IPTC, camera makernotes, embedded previews...)
+            0xE3..=0xEF => false,
+            // Comments.
+            0xFE => false,
+            // Everything else (quantization tables, huffman tables, frame headers) is needed to decode the image.
+            _ => true,
+        };
+
+        if keep_segment {
+            output.extend_from_slice(segment);
+        }
+
+        position += 2 + segment_length;
+    }
+}
+
+/// PNGs are a list of chunks, each with a length, a 4 letter type, the data, and a CRC.
+fn strip_png_metadata(image_bytes: &[u8], keep_colour_profile: bool) -> Option<Vec<u8>> {
+    const PNG_SIGNATURE: &[u8] = b"\x89PNG\r\n\x1a\n";
+
+    if !image_bytes.starts_with(PNG_SIGNATURE) {
+        return None;
+    }
+
+    let mut output = PNG_SIGNATURE.to_vec();
+    let mut position = PNG_SIGNATURE.len();
+
+    while position < image_bytes.len() {
+        let chunk_length =
+            u32::from_be_bytes(image_bytes.get(position..position + 4)?.try_into().ok()?) as usize;
+        let chunk_type = image_bytes.get(position + 4..position + 8)?;
+        // Length + type + data + CRC.
+        let chunk = image_bytes.get(position..position + 12 + chunk_length)?;
+
+        // Browsers barely respect eXIf orientation on PNGs, so unlike JPEGs there's no point saving it.
+        let keep_chunk = match chunk_type {
+            b"eXIf" | b"tEXt" | b"zTXt" | b"iTXt" | b"tIME" => false,
+            b"iCCP" => keep_colour_profile,
+            _ => true,
+        };
+
+        if keep_chunk {
+            output.extend_from_slice(chunk);
+        }
+
+        position += chunk.len();
+    }
+
+    Some(output)
+}
+
+/// WebPs are a RIFF file, which is a list of chunks, each with a 4 letter type, a length, and the data.
+/// Extended WebPs have a VP8X chunk up top with flags saying which other chunks exist, which we need to keep up to date.
+fn strip_webp_metadata(image_bytes: &[u8], keep_colour_profile: bool) -> Option<Vec<u8>> {
+    const VP8X_ICC_FLAG: u8 = 0b0010_0000;
+    const VP8X_EXIF_FLAG: u8 = 0b0000_1000;
+    const VP8X_XMP_FLAG: u8 = 0b0000_0100;
+
+    if image_bytes.get(0..4)? != b"RIFF" || image_bytes.get(8..12)? != b"WEBP" {
+        return None;
+    }
+
+    let mut chunks: Vec<([u8; 4], Vec<u8>)> = Vec::new();
+    let mut orientation = None;
+    let mut position = 12;
+
+    while position < image_bytes.len() {
+        let chunk_type: [u8; 4] = image_bytes.get(position..position + 4)?.try_into().ok()?;
+        let chunk_length = u32::from_le_bytes(
+            image_bytes
+                .get(position + 4..position + 8)?
+                .try_into()
+                .ok()?,
+        ) as usize;
+        let chunk_data = image_bytes.get(position + 8..position + 8 + chunk_length)?;
+
+        match &chunk_type {
+            b"EXIF" => orientation = read_exif_orientation(chunk_data),
+            b"XMP " => {}
+            b"ICCP" if !keep_colour_profile => {}
+            _ => chunks.push((chunk_type, chunk_data.to_vec())),
+        }
+
+        // Chunks are padded to an even length.
+        position += 8 + chunk_length + (chunk_length % 2);
+    }
+
+    // Same as with JPEGs, keep the orientation so the picture doesn't end up sideways.
+    let orientation = orientation.filter(|orientation| *orientation != 1);
+    if let Some(orientation) = orientation {
+        chunks.push((*b"EXIF", minimal_exif_tiff(orientation)));
+    }
+
+    if let Some((_, vp8x_data)) = chunks
+        .iter_mut()
+        .find(|(chunk_type, _)| chunk_type == b"VP8X")
+    {
+        let flags = vp8x_data.first_mut()?;
+
+        *flags &= !VP8X_XMP_FLAG;
+
+        if orientation.is_none() {
+            *flags &= !VP8X_EXIF_FLAG;
+        }
+
+        if !keep_colour_profile {
+            *flags &= !VP8X_ICC_FLAG;
+        }
+    } else if orientation.is_some() {
+        // Simple WebPs can't have EXIF in them, and making them extended isn't worth the headache. Drop it.
+        chunks.retain(|(chunk_type, _)| chunk_type != b"EXIF");
+    }
+
+    let mut riff_contents = b"WEBP".to_vec();
+
+    for (chunk_type, chunk_data) in chunks {
+        riff_contents.extend_from_slice(&chunk_type);
+        riff_contents.extend_from_slice(&(chunk_data.len() as u32).to_le_bytes());
+        riff_contents.extend_from_slice(&chunk_data);
+
+        if chunk_data.len() % 2 == 1 {
+            riff_contents.push(0);
+        }
+    }
+
+    let mut output = b"RIFF".to_vec();
+    output.extend_from_slice(&(riff_contents.len() as u32).to_le_bytes());
+    output.extend_from_slice(&riff_contents);
+
+    Some(output)
+}
+
+/// Given EXIF data (a TIFF header onwards), returns the orientation tag, if it has one.
+fn read_exif_orientation(exif: &[u8]) -> Option<u16> {
+    const ORIENTATION_TAG: u16 = 0x0112;
+
+    let is_little_endian = match exif.get(0..2)? {
+        b"II" => true,
+        b"MM" => false,
+        _ => return None,
+    };
+
+    let read_u16 = |offset: usize| -> Option<u16> {
+        let bytes: [u8; 2] = exif.get(offset..offset + 2)?.try_into().ok()?;
+        Some(if is_little_endian {
+            u16::from_le_bytes(bytes)
+        } else {
+            u16::from_be_bytes(bytes)
+        })
+    };
+
+    let first_ifd_offset = {
+        let bytes: [u8; 4] = exif.get(4..8)?.try_into().ok()?;
+        if is_little_endian {
+            u32::from_le_bytes(bytes)
+        } else {
+            u32::from_be_bytes(bytes)
+        }
+    } as usize;
+
+    let entry_count = read_u16(first_ifd_offset)? as usize;
+
+    // Each entry is 12 bytes: tag, type, count, then the value itself if it fits in 4 bytes.
+    (0..entry_count)
+        .map(|entry_index| first_ifd_offset + 2 + entry_index * 12)
+        .find(|entry_offset| read_u16(*entry_offset) == Some(ORIENTATION_TAG))
+        .and_then(|entry_offset| read_u16(entry_offset + 8))
+}
+
+/// Creates EXIF data (a TIFF header onwards) that only holds the given orientation.
+fn minimal_exif_tiff(orientation: u16) -> Vec<u8> {
+    let mut tiff = Vec::with_capacity(26);
+
+    tiff.extend_from_slice(b"MM\0\x2A"); // Big endian, then the TIFF magic number.
+    tiff.extend_from_slice(&8u32.to_be_bytes()); // The first IFD comes right after the header.
+    tiff.extend_from_slice(&1u16.to_be_bytes()); // One entry.
+    tiff.extend_from_slice(&0x0112u16.to_be_bytes()); // Orientation tag,
+    tiff.extend_from_slice(&3u16.to_be_bytes()); // of type SHORT,
+    tiff.extend_from_slice(&1u32.to_be_bytes()); // with one value,
+    tiff.extend_from_slice(&orientation.to_be_bytes()); // which is this,
+    tiff.extend_from_slice(&[0, 0]); // padded to 4 bytes.
+    tiff.extend_from_slice(&0u32.to_be_bytes()); // No more IFDs.
+
+    tiff
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::codecs::gif::{GifEncoder, Repeat};
+    use image::{Delay, Frame};
+
+    /// Stand-ins for the private stuff in real metadata. None of these may survive stripping.
+    const GPS_SECRET: &[u8] = b"GPS-52.3676N-4.9041E";
+    const XMP_SECRET: &[u8] = b"<x:xmpmeta>XMP-CREATOR-TOOL</x:xmpmeta>";
+    const TEXT_SECRET: &[u8] = b"Author\0PNG-TEXT-SECRET";
+
+    const ORIENTATION_TAG: u16 = 0x0112;
+    const GPS_IFD_TAG: u16 = 0x8825;
+
+    fn contains(haystack: &[u8], needle: &[u8]) -> bool {
+        haystack
+            .windows(needle.len())
+            .any(|window| window == needle)
+    }
+
+    /// EXIF data (TIFF header onwards) with the given (tag, value) entries, and some GPS data after the IFD.
+    fn exif_tiff(little_endian: bool, entries: &[(u16, u16)]) -> Vec<u8> {
+        let u16_bytes = |value: u16| {
+            if little_endian {
+                value.to_le_bytes()
+            } else {
+                value.to_be_bytes()
+            }
+        };
+        let u32_bytes = |value: u32| {
+            if little_endian {
+                value.to_le_bytes()
+            } else {
+                value.to_be_bytes()
+            }
+        };
+
+        let mut tiff = if little_endian {
+            b"II\x2A\0".to_vec()
+        } else {
+            b"MM\0\x2A".to_vec()
+        };
+        tiff.extend_from_slice(&u32_bytes(8));
+        tiff.extend_from_slice(&u16_bytes(entries.len() as u16));
+
+        for (tag, value) in entries {
+            tiff.extend_from_slice(&u16_bytes(*tag));
+            tiff.extend_from_slice(&u16_bytes(3)); // SHORT
+            tiff.extend_from_slice(&u32_bytes(1));
+            tiff.extend_from_slice(&u16_bytes(*value));
+            tiff.extend_from_slice(&[0, 0]);
+        }
+
+        tiff.extend_from_slice(&u32_bytes(0));
+        tiff.extend_from_slice(GPS_SECRET);
+
+        tiff
+    }
+
+    fn exif_with_orientation(orientation: u16) -> Vec<u8> {
+        exif_tiff(true, &[(GPS_IFD_TAG, 26), (ORIENTATION_TAG, orientation)])
+    }
+
+    fn jpeg_segment(marker: u8, data: &[u8]) -> Vec<u8> {
+        let mut segment = vec![0xFF, marker];
+        segment.extend_from_slice(&(data.len() as u16 + 2).to_be_bytes());
+        segment.extend_from_slice(data);
+        segment
+    }
+
+    const JPEG_QUANTIZATION_TABLE: &[u8] = b"\0fake quantization table";
+    const JPEG_SCAN_DATA: &[u8] = b"\xFF\xDA\x00\x04\x01\x02scan data\xFF\x00more\xFF\xD9";
+
+    fn test_jpeg(orientation: u16) -> Vec<u8> {
+        [
+            vec![0xFF, 0xD8],
+            jpeg_segment(0xE0, b"JFIF\0\x01\x01\0\0\x01\0\x01\0\0"),
+            jpeg_segment(
+                0xE1,
+                &[b"Exif\0\0".as_slice(), &exif_with_orientation(orientation)].concat(),
+            ),
+            jpeg_segment(
+                0xE1,
+                &[b"http://ns.adobe.com/xap/1.0/\0".as_slice(), XMP_SECRET].concat(),
+            ),
+            jpeg_segment(0xE2, b"ICC_PROFILE\0\x01\x01fake profile"),
+            jpeg_segment(0xED, b"Photoshop 3.0\0IPTC-SECRET"),
+            jpeg_segment(0xFE, b"COMMENT-SECRET"),
+            vec![0xFF], // Padding between segments.
+            jpeg_segment(0xDB, JPEG_QUANTIZATION_TABLE),
+            JPEG_SCAN_DATA.to_vec(),
+        ]
+        .concat()
+    }
+
+    /// Returns the (marker, data) of every segment before the scan.
+    fn jpeg_segments(jpeg: &[u8]) -> Vec<(u8, Vec<u8>)> {
+        let mut segments = Vec::new();
+        let mut position = 2;
+
+        while jpeg[position + 1] != 0xDA {
+            let length = u16::from_be_bytes([jpeg[position + 2], jpeg[position + 3]]) as usize;
+            segments.push((
+                jpeg[position + 1],
+                jpeg[position + 4..position + 2 + length].to_vec(),
+            ));
+            position += 2 + length;
+        }
+
+        segments
+    }
+
+    #[test]
+    fn jpeg_loses_metadata_but_keeps_orientation() {
+        let stripped = strip_jpeg_metadata(&test_jpeg(6), false).unwrap();
+
+        for secret in [
+            GPS_SECRET,
+            XMP_SECRET,
+            b"IPTC-SECRET",
+            b"COMMENT-SECRET",
+            b"fake profile",
+        ] {
+            assert!(
+                !contains(&stripped, secret),
+                "{}",
+                String::from_utf8_lossy(secret)
+            );
+        }
+
+        assert!(stripped.ends_with(JPEG_SCAN_DATA));
+
+        let segments = jpeg_segments(&stripped);
+        let markers: Vec<u8> = segments.iter().map(|(marker, _)| *marker).collect();
+        assert_eq!(markers, [0xE0, 0xDB, 0xE1]);
+        assert_eq!(segments[1].1, JPEG_QUANTIZATION_TABLE);
+
+        let exif = segments[2].1.strip_prefix(b"Exif\0\0").unwrap();
+        assert_eq!(read_exif_orientation(exif), Some(6));
+    }
+
+    #[test]
+    fn jpeg_keeps_colour_profile_if_asked() {
+        let stripped = strip_jpeg_metadata(&test_jpeg(1), true).unwrap();
+
+        assert!(contains(&stripped, b"fake profile"));
+        assert!(!contains(&stripped, GPS_SECRET));
+        assert!(!contains(&stripped, XMP_SECRET));
+
+        // Orientation 1 is the default, no need for any EXIF at all.
+        let markers: Vec<u8> = jpeg_segments(&stripped)
+            .iter()
+            .map(|(marker, _)| *marker)
+            .collect();
+        assert_eq!(markers, [0xE0, 0xE2, 0xDB]);
+    }
+
+    #[test]
+    fn broken_jpegs_are_left_alone() {
+        let jpeg = test_jpeg(6);
+
+        assert_eq!(strip_jpeg_metadata(b"", false), None);
+        assert_eq!(strip_jpeg_metadata(b"\x89PNG", false), None);
+        // Cut off before the scan.
+        assert_eq!(strip_jpeg_metadata(&jpeg[..40], false), None);
+        // A segment claiming to be longer than the file.
+        assert_eq!(
+            strip_jpeg_metadata(&[0xFF, 0xD8, 0xFF, 0xE1, 0xFF, 0xFF, 0], false),
+            None
+        );
+
+        // And the public function hands back the original.
+        assert_eq!(
+            strip_image_metadata(jpeg[..40].to_vec(), "image/jpeg", false),
+            &jpeg[..40]
+        );
+    }
+
+    #[test]
+    fn stripped_jpeg_still_decodes() {
+        let mut encoded_jpeg = Vec::new();
+        DynamicImage::new_rgb8(4, 3)
+            .write_to(
+                &mut std::io::Cursor::new(&mut encoded_jpeg),
+                image::ImageFormat::Jpeg,
+            )
+            .unwrap();
+
+        // Slip some EXIF in right after the start of image, like a camera would.
+        let jpeg = [
+            &encoded_jpeg[..2],
+            &jpeg_segment(
+                0xE1,
+                &[b"Exif\0\0".as_slice(), &exif_with_orientation(6)].concat(),
+            ),
+            &encoded_jpeg[2..],
+        ]
+        .concat();
+
+        let stripped = strip_jpeg_metadata(&jpeg, false).unwrap();
+        assert!(!contains(&stripped, GPS_SECRET));
+
+        let decoded = image::load_from_memory(&stripped).unwrap();
+        assert_eq!((decoded.width(), decoded.height()), (4, 3));
+    }
+
+    fn png_chunk(chunk_type: &[u8; 4], data: &[u8]) -> Vec<u8> {
+        let mut chunk = (data.len() as u32).to_be_bytes().to_vec();
+        chunk.extend_from_slice(chunk_type);
+        chunk.extend_from_slice(data);
+        chunk.extend_from_slice(
+            &crc32fast::hash(&[chunk_type.as_slice(), data].concat()).to_be_bytes(),
+        );
+        chunk
+    }
+
+    fn test_png() -> Vec<u8> {
+        [
+            b"\x89PNG\r\n\x1a\n".to_vec(),
+            png_chunk(b"IHDR", b"\0\0\0\x01\0\0\0\x01\x08\x06\0\0\0"),
+            png_chunk(b"tEXt", TEXT_SECRET),
+            png_chunk(
+                b"iTXt",
+                &[b"XML:com.adobe.xmp\0\0\0\0\0".as_slice(), XMP_SECRET].concat(),
+            ),
+            png_chunk(b"zTXt", b"Comment\0\0ZTXT-SECRET"),
+            png_chunk(b"eXIf", &exif_with_orientation(6)),
+            png_chunk(b"tIME", b"\x07\xE8\x03\x09\x0C\x00\x00"),
+            png_chunk(b"iCCP", b"profile\0\0fake profile"),
+            png_chunk(b"IDAT", b"pixel data"),
+            png_chunk(b"IEND", b""),
+        ]
+        .concat()
+    }
+
+    /// Returns the types of every chunk in the PNG.
+    fn png_chunk_types(png: &[u8]) -> Vec<[u8; 4]> {
+        let mut chunk_types = Vec::new();
+        let mut position = 8;
+
+        while position < png.len() {
+            let length =
+                u32::from_be_bytes(png[position..position + 4].try_into().unwrap()) as usize;
+            chunk_types.push(png[position + 4..position + 8].try_into().unwrap());
+            position += 12 + length;
+        }
+
+        assert_eq!(position, png.len());
+        chunk_types
+    }
+
+    #[test]
+    fn png_loses_text_and_exif() {
+        let stripped = strip_png_metadata(&test_png(), false).unwrap();
+
+        for secret in [
+            GPS_SECRET,
+            XMP_SECRET,
+            TEXT_SECRET,
+            b"ZTXT-SECRET",
+            b"fake profile",
+        ] {
+            assert!(
+                !contains(&stripped, secret),
+                "{}",
+                String::from_utf8_lossy(secret)
+            );
+        }
+
+        assert_eq!(png_chunk_types(&stripped), [*b"IHDR", *b"IDAT", *b"IEND"]);
+        // Kept chunks are copied whole, CRC and all.
+        assert!(contains(&stripped, &png_chunk(b"IDAT", b"pixel data")));
+    }
+
+    #[test]
+    fn png_keeps_colour_profile_if_asked() {
+        let stripped = strip_png_metadata(&test_png(), true).unwrap();
+
+        assert_eq!(
+            png_chunk_types(&stripped),
+            [*b"IHDR", *b"iCCP", *b"IDAT", *b"IEND"]
+        );
+    }
+
+    #[test]
+    fn broken_pngs_are_left_alone() {
+        let png = test_png();
+
+        assert_eq!(strip_png_metadata(b"\xFF\xD8", false), None);
+        assert_eq!(strip_png_metadata(&png[..png.len() - 3], false), None);
+    }
+
+    const VP8X_ALPHA_FLAG: u8 = 0b0001_0000;
+    const VP8X_ALL_METADATA_FLAGS: u8 = 0b0010_0000 | 0b0000_1000 | 0b0000_0100;
+
+    fn webp_chunk(chunk_type: &[u8; 4], data: &[u8]) -> Vec<u8> {
+        let mut chunk = chunk_type.to_vec();
+        chunk.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        chunk.extend_from_slice(data);
+        if data.len() % 2 == 1 {
+            chunk.push(0);
+        }
+        chunk
+    }
+
+    fn riff(chunks: &[Vec<u8>]) -> Vec<u8> {
+        let contents = [b"WEBP".to_vec(), chunks.concat()].concat();
+        [
+            b"RIFF".to_vec(),
+            (contents.len() as u32).to_le_bytes().to_vec(),
+            contents,
+        ]
+        .concat()
+    }
+
+    fn test_webp(orientation: u16) -> Vec<u8> {
+        riff(&[
+            webp_chunk(
+                b"VP8X",
+                &[
+                    VP8X_ALL_METADATA_FLAGS | VP8X_ALPHA_FLAG,
+                    0,
+                    0,
+                    0,
+                    0,
+                    0,
+                    0,
+                    0,
+                    0,
+                    0,
+                ],
+            ),
+            webp_chunk(b"ICCP", b"fake profile"),
+            // Odd length, so it gets padded.
+            webp_chunk(b"VP8L", b"lossless data"),
+            webp_chunk(b"EXIF", &exif_with_orientation(orientation)),
+            webp_chunk(b"XMP ", XMP_SECRET),
+        ])
+    }
+
+    /// Returns the (type, data) of every chunk in the WebP, checking the RIFF size on the way.
+    fn webp_chunks(webp: &[u8]) -> Vec<([u8; 4], Vec<u8>)> {
+        assert_eq!(&webp[0..4], b"RIFF");
+        assert_eq!(
+            u32::from_le_bytes(webp[4..8].try_into().unwrap()) as usize,
+            webp.len() - 8
+        );
+        assert_eq!(&webp[8..12], b"WEBP");
+
+        let mut chunks = Vec::new();
+        let mut position = 12;
+
+        while position < webp.len() {
+            let length =
+                u32::from_le_bytes(webp[position + 4..position + 8].try_into().unwrap()) as usize;
+            chunks.push((
+                webp[position..position + 4].try_into().unwrap(),
+                webp[position + 8..position + 8 + length].to_vec(),
+            ));
+            position += 8 + length + length % 2;
+        }
+
+        assert_eq!(position, webp.len());
+        chunks
+    }
+
+    #[test]
+    fn webp_loses_metadata_and_updates_flags() {
+        let stripped = strip_webp_metadata(&test_webp(8), false).unwrap();
+
+        for secret in [GPS_SECRET, XMP_SECRET, b"fake profile"] {
+            assert!(
+                !contains(&stripped, secret),
+                "{}",
+                String::from_utf8_lossy(secret)
+            );
+        }
+
+        let chunks = webp_chunks(&stripped);
+        let chunk_types: Vec<[u8; 4]> = chunks.iter().map(|(chunk_type, _)| *chunk_type).collect();
+        assert_eq!(chunk_types, [*b"VP8X", *b"VP8L", *b"EXIF"]);
+
+        // Only the EXIF flag is left, since the orientation is still there. Non-metadata flags are untouched.
+        assert_eq!(chunks[0].1[0], 0b0000_1000 | VP8X_ALPHA_FLAG);
+        assert_eq!(chunks[1].1, b"lossless data");
+        assert_eq!(read_exif_orientation(&chunks[2].1), Some(8));
+    }
+
+    #[test]
+    fn webp_without_orientation_loses_exif_flag() {
+        let stripped = strip_webp_metadata(&test_webp(1), true).unwrap();
+
+        let chunks = webp_chunks(&stripped);
+        let chunk_types: Vec<[u8; 4]> = chunks.iter().map(|(chunk_type, _)| *chunk_type).collect();
+        assert_eq!(chunk_types, [*b"VP8X", *b"ICCP", *b"VP8L"]);
+        assert_eq!(chunks[0].1[0], 0b0010_0000 | VP8X_ALPHA_FLAG);
+    }
+
+    #[test]
+    fn simple_webp_drops_exif_entirely() {
+        let webp = riff(&[
+            webp_chunk(b"VP8 ", b"lossy data"),
+            webp_chunk(b"EXIF", &exif_with_orientation(6)),
+        ]);
+
+        let stripped = strip_webp_metadata(&webp, false).unwrap();
+
+        assert!(!contains(&stripped, GPS_SECRET));
+        let chunk_types: Vec<[u8; 4]> = webp_chunks(&stripped)
+            .iter()
+            .map(|(chunk_type, _)| *chunk_type)
+            .collect();
+        assert_eq!(chunk_types, [*b"VP8 "]);
+    }
+
+    #[test]
+    fn broken_webps_are_left_alone() {
+        let webp = test_webp(6);
+
+        assert_eq!(strip_webp_metadata(b"RIFF\0\0\0\0WAVE", false), None);
+        assert_eq!(strip_webp_metadata(&webp[..webp.len() - 5], false), None);
+    }
+
+    #[test]
+    fn reads_orientation_in_both_byte_orders() {
+        for little_endian in [true, false] {
+            let exif = exif_tiff(little_endian, &[(GPS_IFD_TAG, 26), (ORIENTATION_TAG, 3)]);
+            assert_eq!(read_exif_orientation(&exif), Some(3));
+        }
+
+        assert_eq!(read_exif_orientation(&minimal_exif_tiff(7)), Some(7));
+    }
+
+    #[test]
+    fn broken_exif_has_no_orientation() {
+        let exif = exif_with_orientation(6);
+
+        // No orientation tag.
+        assert_eq!(
+            read_exif_orientation(&exif_tiff(true, &[(GPS_IFD_TAG, 26)])),
+            None
+        );
+        // Not TIFF.
+        assert_eq!(read_exif_orientation(b"XX\x2A\0\x08\0\0\0"), None);
+        // Cut off partway through the IFD.
+        assert_eq!(read_exif_orientation(&exif[..20]), None);
+        // IFD offset pointing past the end.
+        assert_eq!(read_exif_orientation(b"II\x2A\0\xFF\xFF\0\0"), None);
+        assert_eq!(read_exif_orientation(b""), None);
+    }
+
+    /// An animated GIF with a differently coloured square in each frame. None for repeat means no NETSCAPE2.0 extension.
+    fn animated_gif(frame_count: u8, delay_ms: u32, repeat: Option<Repeat>) -> Vec<u8> {
+        let mut gif = Vec::new();
+        {
+            let mut encoder = GifEncoder::new(&mut gif);
+            if let Some(repeat) = repeat {
+                encoder.set_repeat(repeat).unwrap();
+            }
+
+            let frames = (0..frame_count).map(|index| {
+                let buffer = RgbaImage::from_fn(64, 48, |x, y| {
+                    if x / 16 == u32::from(index) % 4 && y < 16 {
+                        image::Rgba([255, 0, 0, 255])
+                    } else {
+                        image::Rgba([0, 0, 255 - index * 20, 255])
+                    }
+                });
+                Frame::from_parts(buffer, 0, 0, Delay::from_numer_denom_ms(delay_ms, 1))
+            });
+            encoder.encode_frames(frames).unwrap();
+        }
+        gif
+    }
+
+    fn frame_durations(animation: &DecodedAnimation) -> Vec<i32> {
+        animation
+            .frames
+            .iter()
+            .map(|(_, duration)| *duration)
+            .collect()
+    }
+
+    #[test]
+    fn gif_loop_counts_become_webp_ones() {
+        let cases = [
+            (None, 1),
+            (Some(Repeat::Infinite), 0),
+            (Some(Repeat::Finite(1)), 2),
+            (Some(Repeat::Finite(4)), 5),
+        ];
+
+        for (repeat, expected) in cases {
+            assert_eq!(
+                read_gif_loop_count(&animated_gif(2, 100, repeat)),
+                expected,
+                "{repeat:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn decodes_every_gif_frame() {
+        let gif = animated_gif(3, 70, Some(Repeat::Infinite));
+        let animation = decode_animation(&gif, ImageFormat::Gif).unwrap().unwrap();
+
+        assert_eq!(frame_durations(&animation), vec![70, 70, 70]);
+        assert_eq!(animation.loop_count, 0);
+        assert_eq!(animation.frames[0].0.dimensions(), (64, 48));
+    }
+
+    #[test]
+    fn tiny_gif_delays_play_like_browsers_do() {
+        for delay_ms in [0, 10] {
+            let gif = animated_gif(2, delay_ms, Some(Repeat::Infinite));
+            let animation = decode_animation(&gif, ImageFormat::Gif).unwrap().unwrap();
+
+            assert_eq!(frame_durations(&animation), vec![100, 100], "{delay_ms}");
+        }
+    }
+
+    #[test]
+    fn single_frame_gifs_arent_animations() {
+        let gif = animated_gif(1, 100, None);
+
+        assert!(decode_animation(&gif, ImageFormat::Gif).unwrap().is_none());
+    }
+
+    #[test]
+    fn animated_webp_keeps_frames_and_timing() {
+        let gif = animated_gif(3, 70, Some(Repeat::Infinite));
+        let animation = decode_animation(&gif, ImageFormat::Gif).unwrap().unwrap();
+
+        let webp = encode_animated_webp(&animation, true, 100.0).unwrap();
+        let reencoded = decode_animation(&webp, ImageFormat::WebP).unwrap().unwrap();
+
+        assert_eq!(frame_durations(&reencoded), vec![70, 70, 70]);
+        // Lossless, so the pixels should survive too, give or take libwebp's rounding.
+        let max_difference = reencoded.frames[2]
+            .0
+            .as_raw()
+            .iter()
+            .zip(animation.frames[2].0.as_raw())
+            .map(|(a, b)| a.abs_diff(*b))
+            .max();
+        assert!(max_difference <= Some(2), "{max_difference:?}");
+    }
+
+    #[test]
+    fn animated_gifs_become_animated_webps() {
+        let gif = animated_gif(4, 100, Some(Repeat::Infinite));
+        let result = compress_image_lossless(gif, "image/gif").unwrap();
+
+        assert_eq!(result.new_file_extension.as_deref(), Some("webp"));
+        let animation = decode_animation(&result.file_bytes, ImageFormat::WebP)
+            .unwrap()
+            .unwrap();
+        assert_eq!(animation.frames.len(), 4);
+    }
+
+    #[test]
+    fn thumbnails_keep_animation_if_asked() {
+        let gif = animated_gif(3, 100, Some(Repeat::Infinite));
+        let settings = |keep_animation| {
+            Some(LossyCompressionSettings {
+                max_width: Some(32),
+                max_height: Some(32),
+                keep_animation,
+                ..Default::default()
+            })
+        };
+
+        let animated = compress_image_lossy(gif.clone(), "image/gif", settings(true)).unwrap();
+        let animation = decode_animation(&animated.file_bytes, ImageFormat::WebP)
+            .unwrap()
+            .unwrap();
+        assert_eq!(animation.frames.len(), 3);
+        assert_eq!(animation.frames[0].0.dimensions(), (32, 24));
+
+        let still = compress_image_lossy(gif, "image/gif", settings(false)).unwrap();
+        assert!(decode_animation(&still.file_bytes, ImageFormat::WebP)
+            .unwrap()
+            .is_none());
+    }
+
+    #[test]
+    fn resizing_keeps_aspect_ratio_and_never_scales_up() {
+        let settings = |max_width, max_height| LossyCompressionSettings {
+            max_width,
+            max_height,
+            ..Default::default()
+        };
+        let cases = [
+            ((1000, 500), settings(Some(500), None), (500, 250)),
+            ((1000, 500), settings(None, Some(100)), (200, 100)),
+            ((1000, 500), settings(Some(500), Some(100)), (200, 100)),
+            ((100, 50), settings(Some(500), Some(500)), (100, 50)),
+            ((100, 50), settings(None, None), (100, 50)),
+            ((10000, 1), settings(Some(100), None), (100, 1)),
+        ];
+
+        for ((width, height), settings, expected) in cases {
+            assert_eq!(
+                get_resized_dimensions(width, height, &settings),
+                expected,
+                "{width}x{height} into {:?}x{:?}",
+                settings.max_width,
+                settings.max_height
+            );
+        }
+    }
+}
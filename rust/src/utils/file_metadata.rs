@@ -0,0 +1,689 @@
+use image::ImageReader;
+use std::io::Cursor;
+
+/// What we know about an uploaded file. Anything we couldn't figure out (or files from before we kept track) is None.
+/// NOTE: The art index's orientation and min_resolution filters only match files with a known width and height,
+/// so art where none of the files have one (SVGs, videos in containers we can't read) never shows up in those searches.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct FileMetadata {
+    pub mime_type: Option<String>,
+    pub byte_size: Option<i64>,
+    pub width: Option<i32>,
+    pub height: Option<i32>,
+    /// Only for videos.
+    pub duration_seconds: Option<f64>,
+}
+
+impl FileMetadata {
+    /// Reads whatever it can out of the given file. Doesn't decode the whole thing, only the headers.
+    pub fn from_file_bytes(file_bytes: &[u8], mime_type: &str) -> Self {
+        let mut metadata = Self {
+            mime_type: Some(mime_type.to_string()),
+            byte_size: Some(file_bytes.len() as i64),
+            ..Default::default()
+        };
+
+        match mime_type {
+            // SVGs don't really have a size, they're as big as you want em to be.
+            "image/svg+xml" => {}
+            "video/mp4" | "video/quicktime" | "video/x-m4v" => {
+                if let Some(video_info) = read_mp4_info(file_bytes) {
+                    metadata.width = video_info.width;
+                    metadata.height = video_info.height;
+                    metadata.duration_seconds = video_info.duration_seconds;
+                }
+            }
+            // WebM is just Matroska with fewer codecs allowed, same container.
+            "video/webm" | "video/x-matroska" => {
+                if let Some(video_info) = read_matroska_info(file_bytes) {
+                    metadata.width = video_info.width;
+                    metadata.height = video_info.height;
+                    metadata.duration_seconds = video_info.duration_seconds;
+                }
+            }
+            _ if mime_type.starts_with("image/") => {
+                if let Some((width, height)) = ImageReader::new(Cursor::new(file_bytes))
+                    .with_guessed_format()
+                    .ok()
+                    .and_then(|reader| reader.into_dimensions().ok())
+                {
+                    metadata.width = i32::try_from(width).ok();
+                    metadata.height = i32::try_from(height).ok();
+                }
+            }
+            // Anything else (other video containers, audio, etc) just gets its type and size.
+            _ => {}
+        }
+
+        metadata
+    }
+
+    /// Reads the metadata columns out of a DB row that has them, like one from art_file.
+    pub fn from_db_row(row: &tokio_postgres::Row) -> Self {
+        Self {
+            mime_type: row.get("mime_type"),
+            byte_size: row.get("byte_size"),
+            width: row.get("width"),
+            height: row.get("height"),
+            duration_seconds: row.get("duration_seconds"),
+        }
+    }
+}
+
+#[derive(Debug, Default, PartialEq)]
+struct VideoInfo {
+    width: Option<i32>,
+    height: Option<i32>,
+    duration_seconds: Option<f64>,
+}
+
+/// Digs the resolution and duration out of an MP4/MOV file's moov box.
+/// Returns None if it can't find the moov box at all.
+fn read_mp4_info(file_bytes: &[u8]) -> Option<VideoInfo> {
+    let (_, moov) = read_mp4_boxes(file_bytes)
+        .into_iter()
+        .find(|(box_type, _)| box_type == b"moov")?;
+
+    let mut mp4_info = VideoInfo::default();
+
+    for (box_type, box_contents) in read_mp4_boxes(moov) {
+        match &box_type {
+            // Movie header. Holds the timescale (units per second) and the duration in those units.
+            b"mvhd" => {
+                let (timescale, duration) = match box_contents.first()? {
+                    1 => (read_u32(box_contents, 20)?, read_u64(box_contents, 24)?),
+                    _ => (
+                        read_u32(box_contents, 12)?,
+                        read_u32(box_contents, 16)? as u64,
+                    ),
+                };
+
+                if timescale > 0 {
+                    mp4_info.duration_seconds = Some(duration as f64 / timescale as f64);
+                }
+            }
+            // Every track has its own header with its size. Audio tracks are 0x0, so take the first one that isn't.
+            b"trak" if mp4_info.width.is_none() => {
+                let Some((_, track_header)) = read_mp4_boxes(box_contents)
+                    .into_iter()
+                    .find(|(box_type, _)| box_type == b"tkhd")
+                else {
+                    continue;
+                };
+
+                // The width and height are the last 8 bytes, as 16.16 fixed point numbers.
+                let size_offset = match track_header.first()? {
+                    1 => 88,
+                    _ => 76,
+                };
+
+                let width = read_u32(track_header, size_offset)? >> 16;
+                let height = read_u32(track_header, size_offset + 4)? >> 16;
+
+                if width > 0 && height > 0 {
+                    mp4_info.width = i32::try_from(width).ok();
+                    mp4_info.height = i32::try_from(height).ok();
+                }
+            }
+            _ => {}
+        }
+    }
+
+    Some(mp4_info)
+}
+
+/// Splits the given MP4 data into its top-level boxes, as (box type, box contents).
+/// Stops at the first box that doesn't make sense, so a truncated file just gives back whatever came before.
+fn read_mp4_boxes(mut data: &[u8]) -> Vec<([u8; 4], &[u8])> {
+    let mut boxes = Vec::new();
+
+    while data.len() >= 8 {
+        let Some(box_type) = data.get(4..8).and_then(|x| <[u8; 4]>::try_from(x).ok()) else {
+            break;
+        };
+
+        // A size of 1 means the real size is a u64 right after the type, 0 means "until the end of the file".
+        let (header_length, box_length) = match read_u32(data, 0) {
+            Some(0) => (8, data.len() as u64),
+            Some(1) => match read_u64(data, 8) {
+                Some(large_size) => (16, large_size),
+                None => break,
+            },
+            Some(size) => (8, size as u64),
+            None => break,
+        };
+
+        if box_length < header_length as u64 || box_length > data.len() as u64 {
+            break;
+        }
+
+        boxes.push((box_type, &data[header_length..box_length as usize]));
+        data = &data[box_length as usize..];
+    }
+
+    boxes
+}
+
+/// Matroska element IDs we care about. The path to each is Segment > Info > ... or Segment > Tracks > TrackEntry > ...
+mod matroska_ids {
+    pub const SEGMENT: u32 = 0x18538067;
+    pub const INFO: u32 = 0x1549A966;
+    pub const TIMESTAMP_SCALE: u32 = 0x2AD7B1;
+    pub const DURATION: u32 = 0x4489;
+    pub const TRACKS: u32 = 0x1654AE6B;
+    pub const TRACK_ENTRY: u32 = 0xAE;
+    pub const TRACK_TYPE: u32 = 0x83;
+    pub const VIDEO: u32 = 0xE0;
+    pub const PIXEL_WIDTH: u32 = 0xB0;
+    pub const PIXEL_HEIGHT: u32 = 0xBA;
+    /// Where the actual video data starts. Info and Tracks always come before it, so no need to go any further.
+    pub const CLUSTER: u32 = 0x1F43B675;
+}
+
+/// TrackType of video tracks.
+const MATROSKA_VIDEO_TRACK: u64 = 1;
+/// The default TimestampScale, in nanoseconds. Makes durations come out in milliseconds.
+const MATROSKA_DEFAULT_TIMESTAMP_SCALE: u64 = 1_000_000;
+
+/// Digs the resolution and duration out of a WebM/MKV file's Info and Tracks elements.
+/// Returns None if it can't find the Segment at all.
+fn read_matroska_info(file_bytes: &[u8]) -> Option<VideoInfo> {
+    let (_, segment) = read_ebml_elements(file_bytes)
+        .into_iter()
+        .find(|(element_id, _)| *element_id == matroska_ids::SEGMENT)?;
+
+    let mut matroska_info = VideoInfo::default();
+
+    for (element_id, element_contents) in read_ebml_elements(segment) {
+        match element_id {
+            matroska_ids::INFO => {
+                let info_elements = read_ebml_elements(element_contents);
+
+                let timestamp_scale = info_elements
+                    .iter()
+                    .find(|(element_id, _)| *element_id == matroska_ids::TIMESTAMP_SCALE)
+                    .and_then(|(_, contents)| read_ebml_uint(contents))
+                    .unwrap_or(MATROSKA_DEFAULT_TIMESTAMP_SCALE);
+
+                // Duration is a float, in units of timestamp_scale nanoseconds.
+                if let Some(duration) = info_elements
+                    .iter()
+                    .find(|(element_id, _)| *element_id == matroska_ids::DURATION)
+                    .and_then(|(_, contents)| read_ebml_float(contents))
+                {
+                    matroska_info.duration_seconds =
+                        Some(duration * timestamp_scale as f64 / 1_000_000_000.0);
+                }
+            }
+            // Same as MP4s, the first video track decides the size.
+            matroska_ids::TRACKS if matroska_info.width.is_none() => {
+                for (_, track_entry) in read_ebml_elements(element_contents)
+                    .into_iter()
+                    .filter(|(element_id, _)| *element_id == matroska_ids::TRACK_ENTRY)
+                {
+                    let track_elements = read_ebml_elements(track_entry);
+                    let find_element = |wanted_id: u32| {
+                        track_elements
+                            .iter()
+                            .find(|(element_id, _)| *element_id == wanted_id)
+                            .map(|(_, contents)| *contents)
+                    };
+
+                    if find_element(matroska_ids::TRACK_TYPE).and_then(read_ebml_uint)
+                        != Some(MATROSKA_VIDEO_TRACK)
+                    {
+                        continue;
+                    }
+
+                    let Some(video_elements) =
+                        find_element(matroska_ids::VIDEO).map(read_ebml_elements)
+                    else {
+                        continue;
+                    };
+                    let find_dimension = |wanted_id: u32| {
+                        video_elements
+                            .iter()
+                            .find(|(element_id, _)| *element_id == wanted_id)
+                            .and_then(|(_, contents)| read_ebml_uint(contents))
+                            .and_then(|dimension| i32::try_from(dimension).ok())
+                            .filter(|dimension| *dimension > 0)
+                    };
+
+                    if let (Some(width), Some(height)) = (
+                        find_dimension(matroska_ids::PIXEL_WIDTH),
+                        find_dimension(matroska_ids::PIXEL_HEIGHT),
+                    ) {
+                        matroska_info.width = Some(width);
+                        matroska_info.height = Some(height);
+                        break;
+                    }
+                }
+            }
+            matroska_ids::CLUSTER => break,
+            _ => {}
+        }
+    }
+
+    Some(matroska_info)
+}
+
+/// Splits the given EBML data into its top-level elements, as (element ID, element contents).
+/// Elements of unknown size (which live streamed files use for the Segment and Clusters) go until the end of the data.
+/// Stops at the first element that doesn't make sense, so a truncated file just gives back whatever came before.
+fn read_ebml_elements(mut data: &[u8]) -> Vec<(u32, &[u8])> {
+    let mut elements = Vec::new();
+
+    while !data.is_empty() {
+        // IDs keep their length marker bits, that's how they're written in the spec.
+        let Some((id_length, _)) = read_ebml_vint(data) else {
+            break;
+        };
+        if id_length > 4 {
+            break;
+        }
+        let element_id = data[..id_length]
+            .iter()
+            .fold(0u32, |id, byte| (id << 8) | *byte as u32);
+
+        let Some((size_length, size)) = data.get(id_length..).and_then(read_ebml_vint) else {
+            break;
+        };
+        let header_length = id_length + size_length;
+
+        // All the value bits set means "unknown size".
+        let is_unknown_size = size == (1u64 << (7 * size_length)) - 1;
+        let element_length = if is_unknown_size {
+            data.len() - header_length
+        } else {
+            match usize::try_from(size) {
+                Ok(size) if size <= data.len() - header_length => size,
+                _ => break,
+            }
+        };
+
+        elements.push((
+            element_id,
+            &data[header_length..header_length + element_length],
+        ));
+        data = &data[header_length + element_length..];
+    }
+
+    elements
+}
+
+/// Reads an EBML variable length integer, returning (how many bytes it took, its value without the length marker).
+/// The amount of leading zeros in the first byte says how many more bytes there are.
+fn read_ebml_vint(data: &[u8]) -> Option<(usize, u64)> {
+    let first_byte = *data.first()?;
+    let length = first_byte.leading_zeros() as usize + 1;
+
+    if length > 8 {
+        return None;
+    }
+
+    let value = data
+        .get(1..length)?
+        .iter()
+        .fold(first_byte as u64 & (0xFF >> length), |value, byte| {
+            (value << 8) | *byte as u64
+        });
+
+    Some((length, value))
+}
+
+/// Reads the contents of an unsigned integer element. They can be anywhere from 0 to 8 bytes long.
+fn read_ebml_uint(contents: &[u8]) -> Option<u64> {
+    if contents.len() > 8 {
+        return None;
+    }
+
+    Some(
+        contents
+            .iter()
+            .fold(0u64, |value, byte| (value << 8) | *byte as u64),
+    )
+}
+
+/// Reads the contents of a float element, which is either 4 or 8 bytes.
+fn read_ebml_float(contents: &[u8]) -> Option<f64> {
+    match contents.len() {
+        4 => Some(f32::from_be_bytes(contents.try_into().ok()?) as f64),
+        8 => Some(f64::from_be_bytes(contents.try_into().ok()?)),
+        _ => None,
+    }
+}
+
+fn read_u32(data: &[u8], offset: usize) -> Option<u32> {
+    Some(u32::from_be_bytes(
+        data.get(offset..offset + 4)?.try_into().ok()?,
+    ))
+}
+
+fn read_u64(data: &[u8], offset: usize) -> Option<u64> {
+    Some(u64::from_be_bytes(
+        data.get(offset..offset + 8)?.try_into().ok()?,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mp4_box(box_type: &[u8; 4], contents: &[u8]) -> Vec<u8> {
+        let mut mp4_box = ((contents.len() + 8) as u32).to_be_bytes().to_vec();
+        mp4_box.extend_from_slice(box_type);
+        mp4_box.extend_from_slice(contents);
+        mp4_box
+    }
+
+    fn large_mp4_box(box_type: &[u8; 4], contents: &[u8]) -> Vec<u8> {
+        let mut mp4_box = 1u32.to_be_bytes().to_vec();
+        mp4_box.extend_from_slice(box_type);
+        mp4_box.extend_from_slice(&((contents.len() + 16) as u64).to_be_bytes());
+        mp4_box.extend_from_slice(contents);
+        mp4_box
+    }
+
+    fn mvhd(version: u8, timescale: u32, duration: u64) -> Vec<u8> {
+        let mut contents = vec![0; if version == 1 { 32 } else { 20 }];
+        contents[0] = version;
+        if version == 1 {
+            contents[20..24].copy_from_slice(&timescale.to_be_bytes());
+            contents[24..32].copy_from_slice(&duration.to_be_bytes());
+        } else {
+            contents[12..16].copy_from_slice(&timescale.to_be_bytes());
+            contents[16..20].copy_from_slice(&(duration as u32).to_be_bytes());
+        }
+        mp4_box(b"mvhd", &contents)
+    }
+
+    fn trak(version: u8, width: u32, height: u32) -> Vec<u8> {
+        let size_offset = if version == 1 { 88 } else { 76 };
+        let mut contents = vec![0; size_offset + 8];
+        contents[0] = version;
+        contents[size_offset..size_offset + 4].copy_from_slice(&(width << 16).to_be_bytes());
+        contents[size_offset + 4..].copy_from_slice(&(height << 16).to_be_bytes());
+        mp4_box(b"trak", &mp4_box(b"tkhd", &contents))
+    }
+
+    fn mp4(moov_contents: &[Vec<u8>]) -> Vec<u8> {
+        let mut file = mp4_box(b"ftyp", b"isom");
+        file.extend(mp4_box(b"moov", &moov_contents.concat()));
+        file.extend(mp4_box(b"mdat", &[0; 16]));
+        file
+    }
+
+    #[test]
+    fn reads_mp4_boxes() {
+        let mut data = mp4_box(b"ftyp", b"isom");
+        data.extend(mp4_box(b"free", &[]));
+
+        let boxes = read_mp4_boxes(&data);
+        assert_eq!(boxes, vec![(*b"ftyp", &b"isom"[..]), (*b"free", &[][..])]);
+    }
+
+    #[test]
+    fn reads_64_bit_mp4_boxes() {
+        let mut data = large_mp4_box(b"mdat", &[1, 2, 3]);
+        data.extend(mp4_box(b"moov", &[4]));
+
+        let boxes = read_mp4_boxes(&data);
+        assert_eq!(
+            boxes,
+            vec![(*b"mdat", &[1, 2, 3][..]), (*b"moov", &[4][..])]
+        );
+    }
+
+    #[test]
+    fn reads_mp4_boxes_until_the_end() {
+        let mut data = mp4_box(b"ftyp", b"isom");
+        data.extend(0u32.to_be_bytes());
+        data.extend(b"mdat");
+        data.extend([1, 2, 3]);
+
+        let boxes = read_mp4_boxes(&data);
+        assert_eq!(
+            boxes,
+            vec![(*b"ftyp", &b"isom"[..]), (*b"mdat", &[1, 2, 3][..])]
+        );
+    }
+
+    #[test]
+    fn stops_at_truncated_mp4_boxes() {
+        let mut data = mp4_box(b"ftyp", b"isom");
+        let moov = mp4_box(b"moov", &[0; 32]);
+        data.extend_from_slice(&moov[..20]);
+
+        assert_eq!(read_mp4_boxes(&data), vec![(*b"ftyp", &b"isom"[..])]);
+
+        // A 64-bit box that's cut off before its size ends.
+        let mut data = mp4_box(b"ftyp", b"isom");
+        data.extend_from_slice(&large_mp4_box(b"mdat", &[0; 4])[..12]);
+
+        assert_eq!(read_mp4_boxes(&data), vec![(*b"ftyp", &b"isom"[..])]);
+
+        // A 64-bit box claiming to be bigger than the file.
+        let mut data = large_mp4_box(b"mdat", &[0; 4]);
+        data[8..16].copy_from_slice(&u64::MAX.to_be_bytes());
+
+        assert!(read_mp4_boxes(&data).is_empty());
+
+        // Sizes smaller than the header itself.
+        let mut data = mp4_box(b"free", &[0; 8]);
+        data[0..4].copy_from_slice(&4u32.to_be_bytes());
+
+        assert!(read_mp4_boxes(&data).is_empty());
+    }
+
+    #[test]
+    fn reads_mp4_info() {
+        let file = mp4(&[mvhd(0, 1000, 2500), trak(0, 1920, 1080)]);
+
+        assert_eq!(
+            read_mp4_info(&file),
+            Some(VideoInfo {
+                width: Some(1920),
+                height: Some(1080),
+                duration_seconds: Some(2.5),
+            })
+        );
+    }
+
+    #[test]
+    fn reads_version_1_mp4_info() {
+        let file = mp4(&[mvhd(1, 600, 6_000_000_000), trak(1, 720, 1280)]);
+
+        assert_eq!(
+            read_mp4_info(&file),
+            Some(VideoInfo {
+                width: Some(720),
+                height: Some(1280),
+                duration_seconds: Some(10_000_000.0),
+            })
+        );
+    }
+
+    #[test]
+    fn skips_audio_tracks() {
+        let file = mp4(&[mvhd(0, 1000, 1000), trak(0, 0, 0), trak(0, 640, 480)]);
+
+        let mp4_info = read_mp4_info(&file).unwrap();
+        assert_eq!(mp4_info.width, Some(640));
+        assert_eq!(mp4_info.height, Some(480));
+    }
+
+    #[test]
+    fn mp4_info_without_moov() {
+        let mut file = mp4_box(b"ftyp", b"isom");
+        file.extend(mp4_box(b"mdat", &[0; 16]));
+
+        assert_eq!(read_mp4_info(&file), None);
+        assert_eq!(read_mp4_info(&[]), None);
+    }
+
+    #[test]
+    fn truncated_mp4_info() {
+        // Cut off in the middle of the tkhd (the mdat after it is 24 bytes), so the moov box runs past the end.
+        let file = mp4(&[mvhd(0, 1000, 2500), trak(0, 1920, 1080)]);
+
+        assert_eq!(read_mp4_info(&file[..file.len() - 30]), None);
+
+        // An mvhd too short to have a timescale in it.
+        let file = mp4(&[mp4_box(b"mvhd", &[0; 8]), trak(0, 1920, 1080)]);
+
+        assert_eq!(read_mp4_info(&file), None);
+    }
+
+    /// Writes an EBML element, with the size always as 8 bytes to keep things simple.
+    fn ebml_element(element_id: u32, contents: &[u8]) -> Vec<u8> {
+        let mut element: Vec<u8> = element_id
+            .to_be_bytes()
+            .into_iter()
+            .skip_while(|byte| *byte == 0)
+            .collect();
+        element.push(0x01);
+        element.extend_from_slice(&(contents.len() as u64).to_be_bytes()[1..]);
+        element.extend_from_slice(contents);
+        element
+    }
+
+    fn unknown_size_ebml_element(element_id: u32, contents: &[u8]) -> Vec<u8> {
+        let mut element: Vec<u8> = element_id
+            .to_be_bytes()
+            .into_iter()
+            .skip_while(|byte| *byte == 0)
+            .collect();
+        element.push(0xFF);
+        element.extend_from_slice(contents);
+        element
+    }
+
+    fn webm_track(track_type: u8, width: u16, height: u16) -> Vec<u8> {
+        let mut track_entry = ebml_element(matroska_ids::TRACK_TYPE, &[track_type]);
+        if track_type == MATROSKA_VIDEO_TRACK as u8 {
+            track_entry.extend(ebml_element(
+                matroska_ids::VIDEO,
+                &[
+                    ebml_element(matroska_ids::PIXEL_WIDTH, &width.to_be_bytes()),
+                    ebml_element(matroska_ids::PIXEL_HEIGHT, &height.to_be_bytes()),
+                ]
+                .concat(),
+            ));
+        }
+        ebml_element(matroska_ids::TRACK_ENTRY, &track_entry)
+    }
+
+    fn webm(segment_contents: &[Vec<u8>]) -> Vec<u8> {
+        let mut file = ebml_element(0x1A45DFA3, &[0; 4]);
+        file.extend(ebml_element(
+            matroska_ids::SEGMENT,
+            &segment_contents.concat(),
+        ));
+        file
+    }
+
+    #[test]
+    fn reads_ebml_vints() {
+        assert_eq!(read_ebml_vint(&[0x81]), Some((1, 1)));
+        assert_eq!(read_ebml_vint(&[0x40, 0x02]), Some((2, 2)));
+        assert_eq!(
+            read_ebml_vint(&[0x1A, 0x45, 0xDF, 0xA3]),
+            Some((4, 0x0A45DFA3))
+        );
+        assert_eq!(read_ebml_vint(&[0x01, 0, 0, 0, 0, 0, 1, 0]), Some((8, 256)));
+        // No length marker in the first byte.
+        assert_eq!(read_ebml_vint(&[0x00, 0x81]), None);
+        // Truncated.
+        assert_eq!(read_ebml_vint(&[0x40]), None);
+        assert_eq!(read_ebml_vint(&[]), None);
+    }
+
+    #[test]
+    fn reads_webm_info() {
+        let file = webm(&[
+            ebml_element(
+                matroska_ids::INFO,
+                &ebml_element(matroska_ids::DURATION, &2500.0f64.to_be_bytes()),
+            ),
+            ebml_element(
+                matroska_ids::TRACKS,
+                &[webm_track(2, 0, 0), webm_track(1, 1280, 720)].concat(),
+            ),
+        ]);
+
+        assert_eq!(
+            read_matroska_info(&file),
+            Some(VideoInfo {
+                width: Some(1280),
+                height: Some(720),
+                duration_seconds: Some(2.5),
+            })
+        );
+    }
+
+    #[test]
+    fn reads_webm_with_custom_timestamp_scale() {
+        let file = webm(&[ebml_element(
+            matroska_ids::INFO,
+            &[
+                ebml_element(
+                    matroska_ids::TIMESTAMP_SCALE,
+                    &1_000_000_000u32.to_be_bytes(),
+                ),
+                ebml_element(matroska_ids::DURATION, &3.0f32.to_be_bytes()),
+            ]
+            .concat(),
+        )]);
+
+        let matroska_info = read_matroska_info(&file).unwrap();
+        assert_eq!(matroska_info.duration_seconds, Some(3.0));
+        assert_eq!(matroska_info.width, None);
+    }
+
+    #[test]
+    fn reads_live_streamed_webm() {
+        // Segment and Cluster of unknown size, like what MediaRecorder spits out. It also leaves out the duration.
+        let mut file = ebml_element(0x1A45DFA3, &[0; 4]);
+        file.extend(unknown_size_ebml_element(
+            matroska_ids::SEGMENT,
+            &[
+                ebml_element(matroska_ids::INFO, &[]),
+                ebml_element(matroska_ids::TRACKS, &webm_track(1, 640, 480)),
+                unknown_size_ebml_element(matroska_ids::CLUSTER, &[0xA3, 0x81, 0x00]),
+            ]
+            .concat(),
+        ));
+
+        assert_eq!(
+            read_matroska_info(&file),
+            Some(VideoInfo {
+                width: Some(640),
+                height: Some(480),
+                duration_seconds: None,
+            })
+        );
+    }
+
+    #[test]
+    fn truncated_webm_info() {
+        assert_eq!(read_matroska_info(&[]), None);
+        assert_eq!(read_matroska_info(&ebml_element(0x1A45DFA3, &[0; 4])), None);
+
+        // The Segment claims more than there is, so it can't be found.
+        let file = webm(&[ebml_element(matroska_ids::TRACKS, &webm_track(1, 640, 480))]);
+
+        assert_eq!(read_matroska_info(&file[..file.len() - 4]), None);
+    }
+
+    #[test]
+    fn webm_file_metadata() {
+        let file = webm(&[ebml_element(
+            matroska_ids::TRACKS,
+            &webm_track(1, 1920, 1080),
+        )]);
+
+        let metadata = FileMetadata::from_file_bytes(&file, "video/webm");
+        assert_eq!(metadata.width, Some(1920));
+        assert_eq!(metadata.height, Some(1080));
+    }
+}
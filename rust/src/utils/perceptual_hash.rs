@@ -0,0 +1,107 @@
+use image::imageops::FilterType;
+use image::ImageReader;
+use std::io::Cursor;
+
+/// Calculates a 64-bit difference hash (dHash) of the given image.
+/// Similar-looking images end up with hashes that only differ in a few bits, even after resizing or recompressing,
+/// so comparing the hamming distance between two hashes tells you how alike the images are.
+/// Returns None if the file can't be decoded as an image. For animated images, only the first frame is hashed.
+pub fn calculate_difference_hash(file_bytes: &[u8]) -> Option<i64> {
+    let img = ImageReader::new(Cursor::new(file_bytes))
+        .with_guessed_format()
+        .ok()?
+        .decode()
+        .ok()?;
+
+    // Shrink it to 9x8 so every row has 8 pairs of neighbouring pixels to compare. Colour doesn't matter, only brightness.
+    let shrunk_img = img.resize_exact(9, 8, FilterType::Triangle).into_luma8();
+
+    let mut hash: u64 = 0;
+
+    for y in 0..8 {
+        for x in 0..8 {
+            hash <<= 1;
+
+            if shrunk_img.get_pixel(x, y)[0] > shrunk_img.get_pixel(x + 1, y)[0] {
+                hash |= 1;
+            }
+        }
+    }
+
+    // Postgres has no unsigned ints, so store the same bits as a signed one.
+    Some(hash as i64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{DynamicImage, ImageFormat, RgbImage};
+
+    /// Same as what the duplicate check query does with bit_count.
+    fn hash_distance(a: i64, b: i64) -> u32 {
+        (a ^ b).count_ones()
+    }
+
+    /// A diagonal gradient, which has plenty of brightness differences between neighbouring pixels to hash.
+    fn gradient(width: u32, height: u32, flipped: bool) -> DynamicImage {
+        DynamicImage::ImageRgb8(RgbImage::from_fn(width, height, |x, y| {
+            let x = if flipped { width - 1 - x } else { x };
+            let brightness = ((x * 255 / width + y * 64 / height) % 256) as u8;
+            image::Rgb([brightness, brightness / 2, 255 - brightness])
+        }))
+    }
+
+    fn encode(img: &DynamicImage, format: ImageFormat) -> Vec<u8> {
+        let mut bytes = Cursor::new(Vec::new());
+        img.write_to(&mut bytes, format).unwrap();
+        bytes.into_inner()
+    }
+
+    #[test]
+    fn same_image_gets_same_hash() {
+        let png = encode(&gradient(300, 200, false), ImageFormat::Png);
+
+        assert_eq!(
+            calculate_difference_hash(&png),
+            calculate_difference_hash(&png)
+        );
+    }
+
+    #[test]
+    fn resized_and_recompressed_images_stay_close() {
+        let original =
+            calculate_difference_hash(&encode(&gradient(300, 200, false), ImageFormat::Png))
+                .unwrap();
+        let smaller_jpeg =
+            calculate_difference_hash(&encode(&gradient(150, 100, false), ImageFormat::Jpeg))
+                .unwrap();
+
+        assert!(
+            hash_distance(original, smaller_jpeg) <= 5,
+            "{}",
+            hash_distance(original, smaller_jpeg)
+        );
+    }
+
+    #[test]
+    fn different_images_are_far_apart() {
+        let original =
+            calculate_difference_hash(&encode(&gradient(300, 200, false), ImageFormat::Png))
+                .unwrap();
+        let flipped =
+            calculate_difference_hash(&encode(&gradient(300, 200, true), ImageFormat::Png))
+                .unwrap();
+
+        assert!(
+            hash_distance(original, flipped) > 20,
+            "{}",
+            hash_distance(original, flipped)
+        );
+    }
+
+    #[test]
+    fn non_images_have_no_hash() {
+        assert_eq!(calculate_difference_hash(b"not an image at all"), None);
+        assert_eq!(calculate_difference_hash(&[]), None);
+    }
+}
@@ -2,11 +2,132 @@ use postgres_types::{FromSql, ToSql};
 use serde::Deserialize;
 
 /// Enum representing the state of various user posts, like art, characters, and stories.
-#[derive(Clone, FromSql, ToSql, Deserialize, Debug, Default)]
+#[derive(Clone, FromSql, ToSql, Deserialize, Debug, Default, PartialEq, Eq)]
 #[postgres(name = "post_state", rename_all = "snake_case")]
 pub enum PostState {
     #[default]
     Public, // Publicly viewable, standard state.
     PendingApproval, // User-uploaded, pending admin review to be moved to public. Not visible.
     Processing,      // Currently mid-process by the server and/or database. Should not be viewable.
+    Rejected, // Reviewed by an admin and turned down. Not visible, the reason is kept alongside the post.
+}
+
+impl PostState {
+    /// The state a new post ends up in once it's done uploading.
+    /// Users who can post directly go straight to public, everyone else goes into the approval queue.
+    pub fn after_upload(uploader_can_post: bool) -> Self {
+        if uploader_can_post {
+            Self::Public
+        } else {
+            Self::PendingApproval
+        }
+    }
+
+    /// The state an existing post goes back to once it's done being edited.
+    /// Edits by users who can't post directly need approval again, unless the post was already public.
+    /// Public posts stay up, and the edit gets flagged for review instead. See [`Self::edit_needs_review`].
+    pub fn after_edit(&self, editor_can_post: bool) -> Self {
+        match (self, editor_can_post) {
+            (Self::Public, _) => Self::Public,
+            (_, false) => Self::PendingApproval,
+            // Left over from an edit that died halfway. It was public before that, or it wouldn't be editable.
+            (Self::Processing, true) => Self::Public,
+            (state, true) => state.clone(),
+        }
+    }
+
+    /// Whether an edit to a post in this state should be flagged in the approval queue, since it goes live without approval.
+    pub fn edit_needs_review(&self, editor_can_post: bool) -> bool {
+        !editor_can_post && *self == Self::Public
+    }
+
+    /// If a post in this state isn't public, returns a notice explaining why, for the submitter and admins looking at it.
+    pub fn get_review_notice(&self, rejection_reason: &Option<String>) -> Option<String> {
+        match self {
+            Self::Public | Self::Processing => None,
+            Self::PendingApproval => Some(
+                "This is waiting for an admin to approve it. Until then, only you and the admins can see it.".to_string(),
+            ),
+            Self::Rejected => Some(format!(
+                "An admin rejected this{}. Only you and the admins can see it. If you edit it, it goes back into the queue.",
+                rejection_reason
+                    .as_ref()
+                    .map(|reason| format!(", saying: \"{reason}\""))
+                    .unwrap_or_default()
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EVERY_STATE: [PostState; 4] = [
+        PostState::Public,
+        PostState::PendingApproval,
+        PostState::Processing,
+        PostState::Rejected,
+    ];
+
+    #[test]
+    fn uploads_only_skip_the_queue_for_posters() {
+        assert_eq!(PostState::after_upload(true), PostState::Public);
+        assert_eq!(PostState::after_upload(false), PostState::PendingApproval);
+    }
+
+    #[test]
+    fn edits_by_posters_keep_the_state() {
+        assert_eq!(PostState::Public.after_edit(true), PostState::Public);
+        assert_eq!(
+            PostState::PendingApproval.after_edit(true),
+            PostState::PendingApproval
+        );
+        assert_eq!(PostState::Rejected.after_edit(true), PostState::Rejected);
+        // A half-finished edit only happens to posts that were public.
+        assert_eq!(PostState::Processing.after_edit(true), PostState::Public);
+    }
+
+    #[test]
+    fn edits_by_non_posters_go_back_into_the_queue() {
+        assert_eq!(
+            PostState::PendingApproval.after_edit(false),
+            PostState::PendingApproval
+        );
+        assert_eq!(
+            PostState::Rejected.after_edit(false),
+            PostState::PendingApproval
+        );
+        assert_eq!(
+            PostState::Processing.after_edit(false),
+            PostState::PendingApproval
+        );
+    }
+
+    #[test]
+    fn public_posts_stay_public_and_get_flagged() {
+        assert_eq!(PostState::Public.after_edit(false), PostState::Public);
+        assert!(PostState::Public.edit_needs_review(false));
+
+        for state in EVERY_STATE {
+            assert!(!state.edit_needs_review(true), "{state:?}");
+        }
+        for state in &EVERY_STATE[1..] {
+            assert!(!state.edit_needs_review(false), "{state:?}");
+        }
+    }
+
+    #[test]
+    fn only_hidden_states_get_a_review_notice() {
+        assert!(PostState::Public.get_review_notice(&None).is_none());
+        assert!(PostState::Processing.get_review_notice(&None).is_none());
+        assert!(PostState::PendingApproval
+            .get_review_notice(&None)
+            .is_some());
+
+        let notice = PostState::Rejected
+            .get_review_notice(&Some("Wrong character".to_string()))
+            .unwrap();
+        assert!(notice.contains("\"Wrong character\""));
+    }
 }
@@ -0,0 +1,409 @@
+//! Writes ZIP archives straight into a response body, one file at a time, so we never hold a whole archive in memory.
+//! Files are stored as-is, without compression. Almost everything we zip is already-compressed images and videos,
+//! so deflating them would just burn CPU for nothing.
+
+use axum::body::{Body, Bytes};
+use chrono::{Datelike, NaiveDate};
+use std::io;
+use tokio::sync::mpsc;
+
+/// How many chunks can be waiting for the client before the writer has to wait for them.
+const BUFFERED_CHUNKS: usize = 16;
+
+const LOCAL_FILE_HEADER_SIGNATURE: u32 = 0x04034b50;
+const CENTRAL_DIRECTORY_HEADER_SIGNATURE: u32 = 0x02014b50;
+const END_OF_CENTRAL_DIRECTORY_SIGNATURE: u32 = 0x06054b50;
+
+/// ZIP 1.0, which is all stored files need.
+const ZIP_VERSION: u16 = 10;
+/// Bit 11 means the file names are UTF-8.
+/// NOTE: Bit 3 (sizes after the file, in a data descriptor) is deliberately not used. Stored files have no end marker,
+/// so anything reading the archive front to back, like `unzip` from a pipe, would have no idea where the file ends.
+const GENERAL_PURPOSE_FLAGS: u16 = 1 << 11;
+
+pub struct ZipStreamWriter {
+    sender: mpsc::Sender<Result<Bytes, io::Error>>,
+    bytes_written: u32,
+    central_directory: Vec<u8>,
+    entry_count: u16,
+    dos_date: u16,
+}
+
+impl ZipStreamWriter {
+    /// Returns a new writer, and the body that everything written to it goes to.
+    /// Every file in the archive will be dated to the given date.
+    pub fn new(files_date: NaiveDate) -> (Self, Body) {
+        let (sender, receiver) = mpsc::channel(BUFFERED_CHUNKS);
+
+        let body_stream = futures_util::stream::unfold(receiver, |mut receiver| async move {
+            receiver.recv().await.map(|chunk| (chunk, receiver))
+        });
+
+        let writer = Self {
+            sender,
+            bytes_written: 0,
+            central_directory: Vec::new(),
+            entry_count: 0,
+            dos_date: to_dos_date(files_date),
+        };
+
+        (writer, Body::from_stream(body_stream))
+    }
+
+    /// Adds a file to the archive. It has to be whole, since its CRC and size go before its contents.
+    pub async fn write_file(&mut self, name: &str, contents: Bytes) -> Result<(), io::Error> {
+        let name_length = u16::try_from(name.len())
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "File name too long"))?;
+        let size = u32::try_from(contents.len()).map_err(|_| too_big_error())?;
+        let crc = crc32fast::hash(&contents);
+        let header_offset = self.bytes_written;
+
+        let mut header = Vec::with_capacity(30 + name.len());
+        header.extend(LOCAL_FILE_HEADER_SIGNATURE.to_le_bytes());
+        header.extend(ZIP_VERSION.to_le_bytes()); // Version needed to extract
+        header.extend(GENERAL_PURPOSE_FLAGS.to_le_bytes());
+        header.extend(0u16.to_le_bytes()); // Compression method, 0 is stored.
+        header.extend(0u16.to_le_bytes()); // Modification time, midnight.
+        header.extend(self.dos_date.to_le_bytes());
+        header.extend(crc.to_le_bytes());
+        header.extend(size.to_le_bytes()); // Compressed size
+        header.extend(size.to_le_bytes()); // Uncompressed size
+        header.extend(name_length.to_le_bytes());
+        header.extend(0u16.to_le_bytes()); // Extra field length
+        header.extend(name.as_bytes());
+
+        self.send(header.into()).await?;
+        self.send(contents).await?;
+
+        let directory = &mut self.central_directory;
+        directory.extend(CENTRAL_DIRECTORY_HEADER_SIGNATURE.to_le_bytes());
+        directory.extend(ZIP_VERSION.to_le_bytes()); // Version made by
+        directory.extend(ZIP_VERSION.to_le_bytes()); // Version needed to extract
+        directory.extend(GENERAL_PURPOSE_FLAGS.to_le_bytes());
+        directory.extend(0u16.to_le_bytes()); // Compression method
+        directory.extend(0u16.to_le_bytes()); // Modification time
+        directory.extend(self.dos_date.to_le_bytes());
+        directory.extend(crc.to_le_bytes());
+        directory.extend(size.to_le_bytes()); // Compressed size
+        directory.extend(size.to_le_bytes()); // Uncompressed size
+        directory.extend(name_length.to_le_bytes());
+        directory.extend(0u16.to_le_bytes()); // Extra field length
+        directory.extend(0u16.to_le_bytes()); // Comment length
+        directory.extend(0u16.to_le_bytes()); // Disk number
+        directory.extend(0u16.to_le_bytes()); // Internal attributes
+        directory.extend(0u32.to_le_bytes()); // External attributes
+        directory.extend(header_offset.to_le_bytes());
+        directory.extend(name.as_bytes());
+
+        self.entry_count = self.entry_count.checked_add(1).ok_or_else(too_big_error)?;
+
+        Ok(())
+    }
+
+    /// Writes the archive's table of contents. Until this is called, the archive is broken.
+    pub async fn finish(mut self) -> Result<(), io::Error> {
+        let central_directory_offset = self.bytes_written;
+        let central_directory_size =
+            u32::try_from(self.central_directory.len()).map_err(|_| too_big_error())?;
+
+        let mut end_of_central_directory = std::mem::take(&mut self.central_directory);
+        end_of_central_directory.extend(END_OF_CENTRAL_DIRECTORY_SIGNATURE.to_le_bytes());
+        end_of_central_directory.extend(0u16.to_le_bytes()); // Number of this disk
+        end_of_central_directory.extend(0u16.to_le_bytes()); // Disk where the central directory starts
+        end_of_central_directory.extend(self.entry_count.to_le_bytes()); // Entries on this disk
+        end_of_central_directory.extend(self.entry_count.to_le_bytes()); // Entries in total
+        end_of_central_directory.extend(central_directory_size.to_le_bytes());
+        end_of_central_directory.extend(central_directory_offset.to_le_bytes());
+        end_of_central_directory.extend(0u16.to_le_bytes()); // Comment length
+
+        self.send(end_of_central_directory.into()).await
+    }
+
+    /// Gives up on the archive, and makes the response fail instead of ending with a broken ZIP that looks fine.
+    pub async fn abort(self, reason: &str) {
+        let _ = self
+            .sender
+            .send(Err(io::Error::other(reason.to_string())))
+            .await;
+    }
+
+    /// Sends the given bytes to the client, keeping track of how far into the archive we are.
+    async fn send(&mut self, chunk: Bytes) -> Result<(), io::Error> {
+        self.bytes_written = u32::try_from(chunk.len())
+            .ok()
+            .and_then(|chunk_size| self.bytes_written.checked_add(chunk_size))
+            .ok_or_else(too_big_error)?;
+
+        // The only way sending fails is if the client stopped listening, so there's no point going on.
+        self.sender
+            .send(Ok(chunk))
+            .await
+            .map_err(|_| io::Error::new(io::ErrorKind::BrokenPipe, "Client stopped downloading"))
+    }
+}
+
+// TODO: ZIP64, if someone ever manages to post 4GB of art in one go.
+fn too_big_error() -> io::Error {
+    io::Error::other("ZIP is over 4GB, which needs ZIP64")
+}
+
+/// Converts a date to the MS-DOS format ZIP uses. It can't go earlier than 1980, so older dates are clamped.
+fn to_dos_date(date: NaiveDate) -> u16 {
+    let year = date.year().clamp(1980, 1980 + 127) - 1980;
+
+    ((year as u16) << 9) | ((date.month() as u16) << 5) | (date.day() as u16)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn read_u16(bytes: &[u8], offset: usize) -> u16 {
+        u16::from_le_bytes(bytes[offset..offset + 2].try_into().unwrap())
+    }
+
+    fn read_u32(bytes: &[u8], offset: usize) -> u32 {
+        u32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap())
+    }
+
+    /// Runs the given writing, and returns everything the body got.
+    async fn write_zip<F, Fut>(files_date: NaiveDate, write: F) -> Result<Bytes, axum::Error>
+    where
+        F: FnOnce(ZipStreamWriter) -> Fut + Send + 'static,
+        Fut: std::future::Future<Output = ()> + Send + 'static,
+    {
+        let (zip_writer, body) = ZipStreamWriter::new(files_date);
+
+        // Written on its own task, like the download does, so a full channel can't deadlock the test.
+        tokio::spawn(write(zip_writer));
+
+        axum::body::to_bytes(body, usize::MAX).await
+    }
+
+    /// A file read back out of an archive, by going through the central directory like an unzipper would.
+    struct ReadFile {
+        name: String,
+        contents: Vec<u8>,
+    }
+
+    /// Reads an archive back, checking every header, size and CRC along the way.
+    fn read_zip(archive: &[u8], expected_dos_date: u16) -> Vec<ReadFile> {
+        let eocd_offset = archive.len() - 22;
+        assert_eq!(
+            read_u32(archive, eocd_offset),
+            END_OF_CENTRAL_DIRECTORY_SIGNATURE
+        );
+        assert_eq!(read_u16(archive, eocd_offset + 4), 0); // This disk
+        assert_eq!(read_u16(archive, eocd_offset + 6), 0); // Central directory disk
+        let entry_count = read_u16(archive, eocd_offset + 8);
+        assert_eq!(read_u16(archive, eocd_offset + 10), entry_count);
+        let central_directory_size = read_u32(archive, eocd_offset + 12) as usize;
+        let central_directory_offset = read_u32(archive, eocd_offset + 16) as usize;
+        assert_eq!(read_u16(archive, eocd_offset + 20), 0); // Comment length
+
+        // The central directory sits right before the EOCD, nothing in between.
+        assert_eq!(
+            central_directory_offset + central_directory_size,
+            eocd_offset
+        );
+
+        let mut files = Vec::new();
+        let mut entry_offset = central_directory_offset;
+        let mut previous_file_end = 0;
+
+        for _ in 0..entry_count {
+            let entry = &archive[entry_offset..];
+            assert_eq!(read_u32(entry, 0), CENTRAL_DIRECTORY_HEADER_SIGNATURE);
+            assert_eq!(read_u16(entry, 8), GENERAL_PURPOSE_FLAGS);
+            assert_eq!(read_u16(entry, 10), 0); // Stored
+            assert_eq!(read_u16(entry, 14), expected_dos_date);
+            let crc = read_u32(entry, 16);
+            let compressed_size = read_u32(entry, 20) as usize;
+            let uncompressed_size = read_u32(entry, 24) as usize;
+            assert_eq!(compressed_size, uncompressed_size);
+            let name_length = read_u16(entry, 28) as usize;
+            assert_eq!(read_u16(entry, 30), 0); // Extra field length
+            assert_eq!(read_u16(entry, 32), 0); // Comment length
+            let header_offset = read_u32(entry, 42) as usize;
+            let name = String::from_utf8(entry[46..46 + name_length].to_vec()).unwrap();
+
+            // Files are back to back, in order.
+            assert_eq!(header_offset, previous_file_end);
+
+            // The local header has to agree with the central directory.
+            let local_header = &archive[header_offset..];
+            assert_eq!(read_u32(local_header, 0), LOCAL_FILE_HEADER_SIGNATURE);
+            assert_eq!(read_u16(local_header, 6), GENERAL_PURPOSE_FLAGS);
+            assert_eq!(read_u16(local_header, 12), expected_dos_date);
+            assert_eq!(read_u32(local_header, 14), crc);
+            assert_eq!(read_u32(local_header, 18) as usize, compressed_size);
+            assert_eq!(read_u32(local_header, 22) as usize, uncompressed_size);
+            assert_eq!(read_u16(local_header, 26) as usize, name_length);
+            assert_eq!(&local_header[30..30 + name_length], name.as_bytes());
+
+            let data_offset = header_offset + 30 + name_length;
+            let contents = archive[data_offset..data_offset + compressed_size].to_vec();
+            assert_eq!(crc32fast::hash(&contents), crc, "CRC of {name}");
+
+            // No data descriptor after it, the next file starts right away.
+            previous_file_end = data_offset + compressed_size;
+            entry_offset += 46 + name_length;
+            files.push(ReadFile { name, contents });
+        }
+
+        // The last file ends where the central directory starts, and every entry was accounted for.
+        assert_eq!(previous_file_end, central_directory_offset);
+        assert_eq!(entry_offset, eocd_offset);
+
+        files
+    }
+
+    /// Reads an archive front to back without looking at the central directory, like `unzip` from a pipe or Java's ZipInputStream.
+    /// That only works if every local header says how big its file is.
+    fn read_zip_streaming(archive: &[u8]) -> Vec<ReadFile> {
+        let mut files = Vec::new();
+        let mut offset = 0;
+
+        while read_u32(archive, offset) == LOCAL_FILE_HEADER_SIGNATURE {
+            let local_header = &archive[offset..];
+            assert_eq!(read_u16(local_header, 6) & (1 << 3), 0, "sizes are known");
+            let crc = read_u32(local_header, 14);
+            let size = read_u32(local_header, 18) as usize;
+            let name_length = read_u16(local_header, 26) as usize;
+            let extra_length = read_u16(local_header, 28) as usize;
+            let name = String::from_utf8(local_header[30..30 + name_length].to_vec()).unwrap();
+
+            let data_offset = offset + 30 + name_length + extra_length;
+            let contents = archive[data_offset..data_offset + size].to_vec();
+            assert_eq!(crc32fast::hash(&contents), crc, "CRC of {name}");
+
+            offset = data_offset + size;
+            files.push(ReadFile { name, contents });
+        }
+
+        // Whatever's after the files has to be the central directory (or the EOCD if there's no files).
+        assert!(matches!(
+            read_u32(archive, offset),
+            CENTRAL_DIRECTORY_HEADER_SIGNATURE | END_OF_CENTRAL_DIRECTORY_SIGNATURE
+        ));
+
+        files
+    }
+
+    #[tokio::test]
+    async fn written_archive_reads_back() {
+        let files_date = NaiveDate::from_ymd_opt(2024, 3, 9).unwrap();
+
+        let archive = write_zip(files_date, |mut zip_writer| async move {
+            zip_writer
+                .write_file("info.txt", Bytes::from_static(b"Title: Test\n"))
+                .await
+                .unwrap();
+
+            let mut art_file = vec![0u8, 1, 2, 3];
+            art_file.extend([255; 1000]);
+            zip_writer
+                .write_file("art_01.png", art_file.into())
+                .await
+                .unwrap();
+
+            zip_writer
+                .write_file("empty.bin", Bytes::new())
+                .await
+                .unwrap();
+
+            zip_writer.finish().await.unwrap();
+        })
+        .await
+        .unwrap();
+
+        for files in [
+            read_zip(&archive, to_dos_date(files_date)),
+            read_zip_streaming(&archive),
+        ] {
+            assert_eq!(files.len(), 3);
+            assert_eq!(files[0].name, "info.txt");
+            assert_eq!(files[0].contents, b"Title: Test\n");
+            assert_eq!(files[1].name, "art_01.png");
+            assert_eq!(files[1].contents.len(), 1004);
+            assert_eq!(&files[1].contents[..4], &[0, 1, 2, 3]);
+            assert!(files[1].contents[4..].iter().all(|&byte| byte == 255));
+            assert_eq!(files[2].name, "empty.bin");
+            assert!(files[2].contents.is_empty());
+        }
+    }
+
+    #[tokio::test]
+    async fn utf8_file_names() {
+        let files_date = NaiveDate::from_ymd_opt(2024, 3, 9).unwrap();
+
+        let archive = write_zip(files_date, |mut zip_writer| async move {
+            zip_writer
+                .write_file("héllo_wörld.txt", Bytes::from_static(b"hi"))
+                .await
+                .unwrap();
+            zip_writer.finish().await.unwrap();
+        })
+        .await
+        .unwrap();
+
+        let files = read_zip(&archive, to_dos_date(files_date));
+        assert_eq!(files[0].name, "héllo_wörld.txt");
+    }
+
+    #[tokio::test]
+    async fn empty_archive_is_just_the_eocd() {
+        let files_date = NaiveDate::from_ymd_opt(2024, 3, 9).unwrap();
+
+        let archive = write_zip(files_date, |zip_writer| async move {
+            zip_writer.finish().await.unwrap();
+        })
+        .await
+        .unwrap();
+
+        assert_eq!(archive.len(), 22);
+        assert!(read_zip(&archive, to_dos_date(files_date)).is_empty());
+    }
+
+    #[tokio::test]
+    async fn aborting_fails_the_body() {
+        let files_date = NaiveDate::from_ymd_opt(2024, 3, 9).unwrap();
+
+        let result = write_zip(files_date, |mut zip_writer| async move {
+            zip_writer
+                .write_file("info.txt", Bytes::from_static(b"Title: Test\n"))
+                .await
+                .unwrap();
+            zip_writer.abort("S3 fell over").await;
+        })
+        .await;
+
+        assert!(result.is_err());
+    }
+
+    #[tokio::test]
+    async fn too_long_file_names_error() {
+        let (mut zip_writer, _body) =
+            ZipStreamWriter::new(NaiveDate::from_ymd_opt(2024, 3, 9).unwrap());
+
+        let name = "a".repeat(u16::MAX as usize + 1);
+        assert!(zip_writer
+            .write_file(&name, Bytes::from_static(b"hi"))
+            .await
+            .is_err());
+    }
+
+    #[test]
+    fn dos_dates() {
+        // Year since 1980 in the top 7 bits, then month, then day.
+        assert_eq!(
+            to_dos_date(NaiveDate::from_ymd_opt(2024, 3, 9).unwrap()),
+            (44 << 9) | (3 << 5) | 9
+        );
+        // Too early for DOS, clamped to 1980.
+        assert_eq!(
+            to_dos_date(NaiveDate::from_ymd_opt(1970, 1, 1).unwrap()),
+            (1 << 5) | 1
+        );
+    }
+}
@@ -50,4 +50,86 @@ async function updateArchivingProgressPin(buttonElement) {
     }
 
     // TODO: Check for errors and float them to user.
-}
\ No newline at end of file
+}
+// Function for the tag management page. Previews or runs the tag operation of the form the button is in.
+async function runTagOperation(buttonElement, isPreview) {
+    const formElement = buttonElement.closest('form');
+    const previewElement = formElement.querySelector(".preview");
+    const confirmButton = formElement.querySelector("button.confirm");
+
+    let body = { operation: formElement.dataset.operation, preview: isPreview };
+    formElement.querySelectorAll("input[type='text']").forEach((input) => { body[input.name] = input.value; });
+
+    let fetchResult = await fetch("/admin/tags", {
+        method: 'PATCH',
+        headers: {
+            "Content-Type": "application/json"
+        },
+        body: JSON.stringify(body)
+    });
+
+    if (!fetchResult.ok) {
+        previewElement.innerText = `Failed! ${await fetchResult.text()}`;
+        confirmButton.disabled = true;
+        return;
+    }
+
+    if (!isPreview) {
+        window.location.reload();
+        return;
+    }
+
+    const { affected_slugs } = await fetchResult.json();
+
+    previewElement.innerHTML = `<p>This will affect ${affected_slugs.length} art pieces:</p>`;
+    const slugList = document.createElement("ul");
+    affected_slugs.forEach((slug) => {
+        const listItem = document.createElement("li");
+        const link = document.createElement("a");
+        link.href = `/art/${slug}`;
+        link.innerText = slug;
+        listItem.appendChild(link);
+        slugList.appendChild(listItem);
+    });
+    previewElement.appendChild(slugList);
+
+    // If they change the inputs after previewing, they need to preview again.
+    confirmButton.disabled = false;
+    formElement.querySelectorAll("input[type='text']").forEach((input) => {
+        input.addEventListener('input', () => { confirmButton.disabled = true; }, { once: true });
+    });
+}
+
+// Approves or rejects the queue item in the same table row as the button.
+async function reviewQueueItem(buttonElement, decision) {
+    const rowElement = buttonElement.closest('tr');
+
+    let body = {
+        item_type: rowElement.dataset.itemType,
+        id: parseInt(rowElement.dataset.id),
+        decision: decision
+    };
+
+    if (decision === 'reject') {
+        const reason = prompt("Why is this being rejected? The submitter will see this.");
+        if (!reason) {
+            return;
+        }
+        body.reason = reason;
+    }
+
+    let fetchResult = await fetch("/admin/queue", {
+        method: 'PATCH',
+        headers: {
+            "Content-Type": "application/json"
+        },
+        body: JSON.stringify(body)
+    });
+
+    if (!fetchResult.ok) {
+        alert(`Failed! ${await fetchResult.text()}`);
+        return;
+    }
+
+    rowElement.remove();
+}
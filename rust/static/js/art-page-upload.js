@@ -51,6 +51,7 @@ async function attemptNewArtUpload(targetUrl = window.location.pathname) {
     title: postTitle,
     creation_date: document.getElementById("postCreationDate").value,
     is_nsfw: document.getElementById("postIsNsfw").checked,
+    content_warnings: Array.from(document.querySelectorAll(".postContentWarning:checked")).map((warningCheckbox) => warningCheckbox.value),
     creators: document.getElementById("postArtists").value.split(","),
     slug: document.getElementById("postSlug").value || postTitle.toLowerCase().replaceAll(" ", "-"),
   };
@@ -74,6 +75,15 @@ async function attemptNewArtUpload(targetUrl = window.location.pathname) {
     postInfo.tags = tags;
   }
 
+  // One source per line, since links can have commas in them.
+  const sources = document.getElementById("postSources").value
+    .split("\n")
+    .map((source) => source.trim())
+    .filter((source) => source);
+  if (sources.length > 0) {
+    postInfo.sources = sources;
+  }
+
   // Make sure that we got a date.
   if (!postInfo.creation_date) {
     updateErrorText(`<b>ERROR:</b> Did not set creation date.`);
@@ -186,7 +196,28 @@ async function attemptNewArtUpload(targetUrl = window.location.pathname) {
   updateErrorText(`Processing art metadata...`);
 
   // Now that it's all on S3, send the final result!
-  const finalUploadRequest = await fetch(targetUrl, finalMessageToSend);
+  let finalUploadRequest = await fetch(targetUrl, finalMessageToSend);
+
+  // 409 means the art looks like something already on the site. Let the user decide if it's really a duplicate.
+  // The files are still sitting in the temp folder, so we can just send the same metadata again.
+  if (finalUploadRequest.status == 409) {
+    const duplicateExplanation = await finalUploadRequest.text();
+
+    if (!confirm(`${duplicateExplanation}\n\nUpload anyways?`)) {
+      updateErrorText(`<b>Upload cancelled:</b> ${duplicateExplanation}`);
+      return;
+    }
+
+    updateErrorText(`Processing art metadata...`);
+
+    finalMessageToSend.body = JSON.stringify({
+      step: "2",
+      ignore_duplicates: true,
+      ...postInfo
+    });
+
+    finalUploadRequest = await fetch(targetUrl, finalMessageToSend);
+  }
 
   console.log(`UPLOAD COMPLETE! Result : ${JSON.stringify(finalUploadRequest)} `)
 
@@ -30,21 +30,129 @@ document.addEventListener('DOMContentLoaded', () => {
 function postComment() {
     const commentText = document.getElementById("commentArea").value;
 
-    fetch(`${window.location.pathname}/comment`, {
-        method: 'POST',
+    sendCommentRequest(`${window.location.pathname}/comment`, 'POST', commentText);
+}
+// Adds or removes the art from the user's favorites, depending on whether it's already there.
+function toggleFavorite(favoriteButton) {
+    const method = favoriteButton.classList.contains("on") ? 'DELETE' : 'POST';
+
+    fetch(`${window.location.pathname}/favorite`, { method: method })
+        .then(response => {
+            if (!response.ok) {
+                throw new Error(response.statusText);
+            }
+            return response.json();
+        })
+        .then(favoriteState => {
+            favoriteButton.classList.toggle("on", favoriteState.is_favorited);
+            favoriteButton.querySelector(".favorite-count").textContent = favoriteState.favorite_count;
+        })
+        .catch(error => {
+            console.error('Error:', error);
+        });
+}
+
+// Opens a textbox under the given comment for replying to it.
+function showReplyBox(replyButton) {
+    const commentElement = replyButton.closest(".comment");
+
+    // Only one reply box per comment, please.
+    if (commentElement.querySelector(".reply-box")) {
+        return;
+    }
+
+    const replyBox = document.createElement("div");
+    replyBox.className = "reply-box";
+    replyBox.innerHTML = `
+        <textarea placeholder="Write a reply..." autocomplete="off"></textarea>
+        <button class="post">Reply</button>
+    `;
+
+    replyBox.querySelector("button").addEventListener("click", () => {
+        const replyText = replyBox.querySelector("textarea").value;
+
+        sendCommentRequest(`${window.location.pathname}/comment?reply_to=${commentElement.dataset.commentId}`, 'POST', replyText);
+    });
+
+    commentElement.querySelector(".text").appendChild(replyBox);
+}
+
+// Swaps the contents of the given comment with a textbox for editing it.
+function showEditBox(editButton) {
+    const commentElement = editButton.closest(".comment");
+    const contentsElement = commentElement.querySelector(".comment-contents");
+
+    if (commentElement.querySelector(".edit-box")) {
+        return;
+    }
+
+    const editBox = document.createElement("div");
+    editBox.className = "edit-box";
+    editBox.innerHTML = `
+        <textarea autocomplete="off"></textarea>
+        <button class="post">Save</button>
+    `;
+
+    // Setting it through value, so whatever the user wrote isn't parsed as HTML.
+    editBox.querySelector("textarea").value = commentElement.querySelector(".raw-contents").value;
+
+    editBox.querySelector("button").addEventListener("click", () => {
+        const editedText = editBox.querySelector("textarea").value;
+
+        sendCommentRequest(`${window.location.pathname}/comment/${commentElement.dataset.commentId}`, 'PATCH', editedText);
+    });
+
+    contentsElement.hidden = true;
+    contentsElement.after(editBox);
+}
+
+function deleteComment(deleteButton) {
+    if (!confirm("Delete this comment?")) {
+        return;
+    }
+
+    const commentElement = deleteButton.closest(".comment");
+
+    sendCommentRequest(`${window.location.pathname}/comment/${commentElement.dataset.commentId}`, 'DELETE');
+}
+
+// Sends a comment-related request, and reloads the page if it worked.
+function sendCommentRequest(url, method, body) {
+    fetch(url, {
+        method: method,
         headers: {
             'Content-Type': 'text/plain'
         },
-        body: commentText
+        body: body
     })
         .then(response => {
             if (response.ok) {
                 window.location.reload();
             } else {
                 console.error('Error:', response.statusText);
+                showCommentError(response);
             }
         })
         .catch(error => {
             console.error('Error:', error);
         });
-}
\ No newline at end of file
+}
+
+// Tells the user why their comment didn't go through. If they're posting too fast, also tells them how long to wait.
+async function showCommentError(response) {
+    let message = await response.text();
+
+    const retryAfter = parseInt(response.headers.get("Retry-After"));
+    if (response.status === 429 && !isNaN(retryAfter)) {
+        const waitTime = retryAfter >= 60 ? `${Math.ceil(retryAfter / 60)} minutes` : `${retryAfter} seconds`;
+        message += `\nYou can try again in ${waitTime}.`;
+    }
+
+    alert(message);
+}
+
+// Takes the content warning overlay off the art display.
+function revealCoveredArt(overlay) {
+    overlay.closest('.art-display').classList.remove('covered');
+    overlay.remove();
+}
@@ -0,0 +1,28 @@
+// Assumed to exist:
+// <select class="search-select" data-parameter="{url parameter name}"> <option value="{value, empty for any}"> ... </select>
+// One of them being the sort, with data-parameter="sort".
+const searchSelects = document.querySelectorAll('.search-select');
+
+searchSelects.forEach((searchSelect) => {
+    searchSelect.addEventListener('change', () => {
+        let targetParameters = new URLSearchParams(window.location.search);
+        const parameterName = searchSelect.dataset.parameter;
+
+        // A different search or order means starting from the top.
+        targetParameters.delete("page");
+        targetParameters.delete("cursor");
+
+        // And a new order means a new shuffle if it's random.
+        if (parameterName == "sort") {
+            targetParameters.delete("seed");
+        }
+
+        if (searchSelect.value) {
+            targetParameters.set(parameterName, searchSelect.value);
+        } else {
+            targetParameters.delete(parameterName);
+        }
+
+        window.location.href = `${window.location.pathname}?${targetParameters.toString()}`;
+    });
+});
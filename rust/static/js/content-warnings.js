@@ -0,0 +1,9 @@
+// Thumbnails with content warnings take one click to uncover, and another to actually open.
+document.addEventListener('click', (event) => {
+    const warnedArt = event.target.closest('.content-warned');
+
+    if (warnedArt) {
+        event.preventDefault();
+        warnedArt.classList.remove('content-warned');
+    }
+});
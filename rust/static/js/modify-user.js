@@ -50,6 +50,21 @@ async function sendUserModifications() {
         changesDone.user_type = newUserType;
     }
 
+    // Only there when modifying yourself. Sent every time, since an empty blacklist is a change too.
+    if (document.getElementById("contentFilters")) {
+        const splitList = (inputId) => document.getElementById(inputId).value
+            .split(",")
+            .map((entry) => entry.trim())
+            .filter((entry) => entry);
+
+        changesDone.blacklisted_tags = splitList("userBlacklistedTags");
+        changesDone.blacklisted_artists = splitList("userBlacklistedArtists");
+        changesDone.filtered_art_display = document.getElementById("userFilteredArtDisplay").value;
+        changesDone.show_nsfw_by_default = document.getElementById("userShowNsfwByDefault").checked;
+        changesDone.shown_content_warnings = Array.from(document.querySelectorAll(".userShownContentWarning:checked"))
+            .map((warningCheckbox) => warningCheckbox.value);
+    }
+
     await fetch(targetUrl, {
         method: 'PATCH',
         headers: {
@@ -1,5 +1,5 @@
 // Tags is [], unless there's already .tag-name on initialization, at which point it'll be those tags.
-const tags = Array.from(document.querySelectorAll('.tag-name')).map(el => el.innerHTML);
+const tags = Array.from(document.querySelectorAll('.tag-name')).map(el => el.textContent);
 
 // Assumed to exist:
 // <div id="tag-search" data-base-url="{some target URL goes here}"> <input type="text" id="tag-input" autocomplete="off" /> </div>
@@ -11,7 +11,7 @@ const baseURL = tagsContainer.dataset.baseUrl;
 
 const tagRemoveOnClick = (event) => {
     const self = event.currentTarget;
-    const idx = tags.indexOf(self.parentElement.querySelector(".tag-name").innerHTML);
+    const idx = tags.indexOf(self.parentElement.querySelector(".tag-name").textContent);
     if (idx > -1) {
         tags.splice(idx, 1);
         self.parentElement.remove();
@@ -50,10 +50,12 @@ function createTargetUrl() {
     let targetParameters = new URLSearchParams(window.location.search);
 
     targetParameters.delete("page");
+    targetParameters.delete("cursor");
     targetParameters.delete("tags");
 
     if (tags.length > 0) {
-        targetParameters.set("tags", tags.join(","));
+        // Each tag gets encoded on its own, so commas in artist names don't split them up.
+        targetParameters.set("tags", tags.map(encodeURIComponent).join(","));
     }
 
     if (targetParameters.size > 0) {
@@ -0,0 +1,26 @@
+// Assumed to exist:
+// <form id="text-search" data-base-url="{some target URL goes here}"> <input type="search" id="text-search-input" /> </form>
+const textSearchForm = document.getElementById('text-search');
+const textSearchInput = document.getElementById('text-search-input');
+
+textSearchForm.addEventListener('submit', (e) => {
+    e.preventDefault();
+
+    // Same deal as the tag search, keep whatever's in the URL and only swap out the query.
+    let targetParameters = new URLSearchParams(window.location.search);
+
+    targetParameters.delete("page");
+    targetParameters.delete("cursor");
+    targetParameters.delete("q");
+
+    const searchQuery = textSearchInput.value.trim();
+    if (searchQuery) {
+        targetParameters.set("q", searchQuery);
+    }
+
+    if (targetParameters.size > 0) {
+        window.location.href = `${textSearchForm.dataset.baseUrl}?${targetParameters.toString()}`;
+    } else {
+        window.location.href = textSearchForm.dataset.baseUrl;
+    }
+});